@@ -1,10 +1,13 @@
 use client::{Client, Event};
 use common::{clock::Clock, comp};
 use log::{error, info};
-use std::time::Duration;
+use std::{env, time::Duration};
 
 const FPS: u64 = 60;
 
+/// Set this environment variable to a file path to capture the network traffic with the server.
+const CAPTURE_VAR: &str = "VELOREN_NET_CAPTURE";
+
 fn main() {
     // Initialize logging.
     pretty_env_logger::init();
@@ -18,6 +21,13 @@ fn main() {
     let mut client =
         Client::new(([127, 0, 0, 1], 59003), None).expect("Failed to create client instance");
 
+    if let Ok(path) = env::var(CAPTURE_VAR) {
+        info!("Capturing network traffic into '{}'", path);
+        client
+            .record_network(path)
+            .expect("Failed to start network capture");
+    }
+
     println!("Server info: {:?}", client.server_info);
 
     // TODO: Remove or move somewhere else, this doesn't work immediately after connecting
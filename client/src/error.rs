@@ -6,6 +6,7 @@ pub enum Error {
     ServerWentMad,
     ServerTimeout,
     ServerShutdown,
+    ProtocolMismatch { server_version: u32 },
     Other(String),
 }
 
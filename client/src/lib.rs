@@ -9,15 +9,20 @@ pub use specs::Entity as EcsEntity;
 
 use common::{
     comp,
-    msg::{ClientMsg, ClientState, ServerInfo, ServerMsg},
+    inventory::{
+        item::{EquipSlot, Item},
+        PICKUP_RANGE,
+    },
+    msg::{ChatMsg, ClientMsg, ClientState, ServerInfo, ServerMsg, PROTOCOL_VERSION},
     net::PostBox,
-    state::State,
+    state::{State, Uid},
     terrain::chonk::ChonkMetrics,
 };
 use log::{debug, info, log_enabled};
 use std::{
     collections::HashMap,
     net::SocketAddr,
+    path::Path,
     time::{Duration, Instant},
 };
 use threadpool::ThreadPool;
@@ -26,16 +31,27 @@ use vek::*;
 const SERVER_TIMEOUT: Duration = Duration::from_secs(20);
 
 pub enum Event {
-    Chat(String),
+    Chat(ChatMsg),
     Disconnect,
 }
 
+/// A freshly opened connection to a server, after the initial sync.
+struct Connection {
+    postbox: PostBox<ClientMsg, ServerMsg>,
+    state: State,
+    entity: EcsEntity,
+    server_info: ServerInfo,
+    session_token: u64,
+}
+
 pub struct Client {
     client_state: ClientState,
     thread_pool: ThreadPool,
     pub server_info: ServerInfo,
 
+    server_addr: SocketAddr,
     postbox: PostBox<ClientMsg, ServerMsg>,
+    session_token: u64,
 
     last_server_ping: Instant,
     last_ping_delta: f64,
@@ -54,24 +70,14 @@ impl Client {
     #[allow(dead_code)]
     pub fn new<A: Into<SocketAddr>>(addr: A, view_distance: Option<u32>) -> Result<Self, Error> {
         let client_state = ClientState::Connected;
-        let mut postbox = PostBox::to(addr)?;
-
-        // Wait for initial sync
-        let (state, entity, server_info) = match postbox.next_message() {
-            Some(ServerMsg::InitialSync {
-                ecs_state,
-                entity_uid,
-                server_info,
-            }) => {
-                let state = State::from_state_package(ecs_state);
-                let entity = state
-                    .ecs()
-                    .entity_from_uid(entity_uid)
-                    .ok_or(Error::ServerWentMad)?;
-                (state, entity, server_info)
-            }
-            _ => return Err(Error::ServerWentMad),
-        };
+        let server_addr = addr.into();
+        let Connection {
+            mut postbox,
+            state,
+            entity,
+            server_info,
+            session_token,
+        } = Self::connect(server_addr)?;
 
         postbox.send_message(ClientMsg::Ping);
 
@@ -86,7 +92,9 @@ impl Client {
             thread_pool,
             server_info,
 
+            server_addr,
             postbox,
+            session_token,
 
             last_server_ping: Instant::now(),
             last_ping_delta: 0.0,
@@ -101,6 +109,90 @@ impl Client {
         })
     }
 
+    /// Open a connection to the server and wait for the initial sync.
+    fn connect(addr: SocketAddr) -> Result<Connection, Error> {
+        let mut postbox = PostBox::to(addr)?;
+
+        postbox.send_message(ClientMsg::VersionInfo {
+            protocol_version: PROTOCOL_VERSION,
+        });
+
+        // Make sure we speak the same protocol before trying to understand anything else.
+        match postbox.next_message() {
+            Some(ServerMsg::VersionInfo { protocol_version }) => {
+                if protocol_version != PROTOCOL_VERSION {
+                    return Err(Error::ProtocolMismatch {
+                        server_version: protocol_version,
+                    });
+                }
+            }
+            _ => return Err(Error::ServerWentMad),
+        }
+
+        // Wait for initial sync
+        match postbox.next_message() {
+            Some(ServerMsg::InitialSync {
+                ecs_state,
+                entity_uid,
+                server_info,
+                session_token,
+            }) => {
+                let state = State::from_state_package(ecs_state);
+                let entity = state
+                    .ecs()
+                    .entity_from_uid(entity_uid)
+                    .ok_or(Error::ServerWentMad)?;
+                Ok(Connection {
+                    postbox,
+                    state,
+                    entity,
+                    server_info,
+                    session_token,
+                })
+            }
+            _ => Err(Error::ServerWentMad),
+        }
+    }
+
+    /// Reconnect to the server after the connection was lost and try to resume the previous
+    /// session. If the server still remembers the session, the client's entity and state are
+    /// restored. Otherwise the client ends up in `ClientState::Connected`.
+    #[allow(dead_code)]
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        let Connection {
+            mut postbox,
+            mut state,
+            entity,
+            server_info,
+            session_token,
+        } = Self::connect(self.server_addr)?;
+
+        postbox.send_message(ClientMsg::Resume {
+            session_token: self.session_token,
+        });
+        postbox.send_message(ClientMsg::Ping);
+
+        // The world hasn't changed, so keep the terrain we already have.
+        std::mem::swap(&mut *self.state.terrain_mut(), &mut *state.terrain_mut());
+
+        self.client_state = ClientState::Pending;
+        self.server_info = server_info;
+        self.postbox = postbox;
+        self.session_token = session_token;
+        self.last_server_ping = Instant::now();
+        self.state = state;
+        self.entity = entity;
+        self.pending_chunks.clear();
+
+        Ok(())
+    }
+
+    /// Record all network traffic with the server into a capture file.
+    #[allow(dead_code)]
+    pub fn record_network<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        Ok(self.postbox.record_to(path)?)
+    }
+
     #[allow(dead_code)]
     pub fn with_thread_pool(mut self, thread_pool: ThreadPool) -> Self {
         self.thread_pool = thread_pool;
@@ -200,6 +292,76 @@ impl Client {
         self.postbox.send_message(ClientMsg::Respawn)
     }
 
+    /// Equip the item in the given inventory slot.
+    #[allow(dead_code)]
+    pub fn equip(&mut self, cell: usize) {
+        self.postbox.send_message(ClientMsg::Equip { cell })
+    }
+
+    /// Pick up the closest item lying within reach, if there is one.
+    #[allow(dead_code)]
+    pub fn pick_up_nearest(&mut self) {
+        let ecs = self.state.ecs();
+        let pos = match ecs.read_storage::<comp::phys::Pos>().get(self.entity) {
+            Some(pos) => pos.0,
+            None => return,
+        };
+        let nearest = (
+            &ecs.read_storage::<Uid>(),
+            &ecs.read_storage::<comp::phys::Pos>(),
+            &ecs.read_storage::<Item>(),
+        )
+            .join()
+            .map(|(&uid, item_pos, _)| (uid, pos.distance(item_pos.0)))
+            .filter(|(_, dist)| *dist <= PICKUP_RANGE)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        if let Some((uid, _)) = nearest {
+            self.postbox.send_message(ClientMsg::Pickup(uid.into()));
+        }
+    }
+
+    /// Put the item in the given equipment slot back into the inventory.
+    #[allow(dead_code)]
+    pub fn unequip(&mut self, slot: EquipSlot) {
+        self.postbox.send_message(ClientMsg::Unequip(slot))
+    }
+
+    /// Invite the player with the given `Uid` to the group of this client.
+    #[allow(dead_code)]
+    pub fn invite_to_group(&mut self, uid: u64) {
+        self.postbox.send_message(ClientMsg::GroupInvite(uid))
+    }
+
+    /// Join the group this client was last invited to.
+    #[allow(dead_code)]
+    pub fn accept_group_invite(&mut self) {
+        self.postbox.send_message(ClientMsg::GroupAccept)
+    }
+
+    #[allow(dead_code)]
+    pub fn leave_group(&mut self) {
+        self.postbox.send_message(ClientMsg::GroupLeave)
+    }
+
+    /// Remove the player with the given `Uid` from the group this client leads.
+    #[allow(dead_code)]
+    pub fn kick_from_group(&mut self, uid: u64) {
+        self.postbox.send_message(ClientMsg::GroupKick(uid))
+    }
+
+    /// Tame the wild animal with the given `Uid`.
+    #[allow(dead_code)]
+    pub fn tame(&mut self, uid: u64) {
+        self.postbox.send_message(ClientMsg::Tame(uid))
+    }
+
+    /// Give an order to all pets of this client.
+    #[allow(dead_code)]
+    pub fn command_pets(&mut self, command: comp::PetCommand) {
+        self.postbox.send_message(ClientMsg::PetCommand(command))
+    }
+
     /// Remove all cached terrain
     #[allow(dead_code)]
     pub fn clear_terrain(&mut self) {
@@ -241,6 +403,9 @@ impl Client {
 
         // 4) Tick the client's LocalState
         self.state.tick(dt);
+        self.state.discard_fired_projectiles();
+        // Only the server tells players about the hits the PvP rules prevented.
+        self.state.take_blocked_hits();
 
         // 5) Terrain
         let pos = self
@@ -364,7 +529,9 @@ impl Client {
         if new_msgs.len() > 0 {
             for msg in new_msgs {
                 match msg {
-                    ServerMsg::InitialSync { .. } => return Err(Error::ServerWentMad),
+                    ServerMsg::VersionInfo { .. } | ServerMsg::InitialSync { .. } => {
+                        return Err(Error::ServerWentMad)
+                    }
                     ServerMsg::Shutdown => return Err(Error::ServerShutdown),
                     ServerMsg::Ping => self.postbox.send_message(ClientMsg::Pong),
                     ServerMsg::Pong => {
@@ -418,6 +585,9 @@ impl Client {
                     ServerMsg::Disconnect => {
                         frontend_events.push(Event::Disconnect);
                     }
+                    ServerMsg::InventoryUpdate(inventory) => {
+                        self.state.write_component(self.entity, inventory)
+                    }
                 }
             }
         } else if let Some(err) = self.postbox.error() {
@@ -0,0 +1,195 @@
+//! Utility-based decision making for AI agents.
+//!
+//! Every tick, each agent describes its `Situation` and scores every `Action` with the weights of
+//! its `Behaviour`. The action with the highest score wins. Behaviours are loaded from
+//! `common/agent_behaviours.json`.
+
+use crate::assets;
+use lazy_static::lazy_static;
+use serde_json;
+use std::collections::HashMap;
+
+/// Something an agent can decide to do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Idle,
+    Wander,
+    Follow,
+    Attack,
+    Flee,
+    Regroup,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Wander,
+        Action::Follow,
+        Action::Attack,
+        Action::Flee,
+        Action::Regroup,
+    ];
+}
+
+/// How much an agent cares about each of its actions.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    pub wander: f32,
+    pub follow: f32,
+    pub attack: f32,
+    pub flee: f32,
+    pub regroup: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Behaviour {
+    /// How far away (in blocks) the agent can see other entities.
+    pub sight_range: f32,
+    /// How long (in seconds) the agent remembers a threat it no longer sees.
+    pub memory: f64,
+    /// The fraction of its health below which the agent runs away from threats.
+    pub flee_health: f32,
+    /// Whether the agent considers every character it sees a threat, rather than only the ones
+    /// that attacked it or its pack.
+    pub aggressive: bool,
+    /// How close the agent has to be to its target to hit it.
+    pub attack_range: f32,
+    /// How far the agent lets its owner get away before following them.
+    pub follow_distance: f32,
+    /// How far the agent lets its pack get away before regrouping.
+    pub pack_radius: f32,
+    pub weights: Weights,
+}
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Self {
+            sight_range: 16.0,
+            memory: 10.0,
+            flee_health: 0.0,
+            aggressive: false,
+            attack_range: 2.0,
+            follow_distance: 5.0,
+            pack_radius: 8.0,
+            weights: Weights {
+                wander: 1.0,
+                ..Weights::default()
+            },
+        }
+    }
+}
+
+/// What an agent knows about its surroundings when it makes a decision.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Situation {
+    /// The fraction of its health the agent has left.
+    pub health: f32,
+    pub owner_dist: Option<f32>,
+    /// The distance to the closest threat the agent remembers.
+    pub threat_dist: Option<f32>,
+    /// The distance to the centre of the agent's pack.
+    pub pack_dist: Option<f32>,
+}
+
+// Wandering around is what agents do when nothing else needs doing.
+const WANDER_UTILITY: f32 = 0.2;
+
+impl Behaviour {
+    /// How useful the given action would be in the given situation, from 0 upwards.
+    pub fn score(&self, action: Action, situation: &Situation) -> f32 {
+        let w = &self.weights;
+        match action {
+            Action::Idle => 0.0,
+            Action::Wander => w.wander * WANDER_UTILITY,
+            Action::Follow => situation.owner_dist.map_or(0.0, |dist| {
+                w.follow
+                    * ((dist - self.follow_distance) / self.follow_distance)
+                        .max(0.0)
+                        .min(1.0)
+            }),
+            Action::Attack => situation.threat_dist.map_or(0.0, |dist| {
+                // Closer threats are more urgent and healthier agents are bolder.
+                let closeness = 1.0 - (dist / self.sight_range).max(0.0).min(1.0) * 0.5;
+                w.attack * closeness * situation.health
+            }),
+            Action::Flee => match situation.threat_dist {
+                Some(_) if situation.health < self.flee_health => w.flee,
+                _ => 0.0,
+            },
+            Action::Regroup => situation.pack_dist.map_or(0.0, |dist| {
+                w.regroup
+                    * ((dist - self.pack_radius) / self.pack_radius)
+                        .max(0.0)
+                        .min(1.0)
+            }),
+        }
+    }
+
+    /// The most useful action in the given situation.
+    pub fn choose(&self, situation: &Situation) -> Action {
+        Action::ALL
+            .iter()
+            .map(|&action| (action, self.score(action, situation)))
+            .fold((Action::Idle, 0.0), |best, scored| {
+                if scored.1 > best.1 {
+                    scored
+                } else {
+                    best
+                }
+            })
+            .0
+    }
+}
+
+lazy_static! {
+    static ref BEHAVIOURS: HashMap<String, Behaviour> = serde_json::from_value(
+        (*assets::load_expect::<serde_json::Value>("common/agent_behaviours.json")).clone()
+    )
+    .expect("parsing agent behaviours");
+    static ref DEFAULT_BEHAVIOUR: Behaviour = Behaviour::default();
+}
+
+/// The behaviour with the given name, or a passive default one if there is no such behaviour.
+pub fn behaviour(name: &str) -> &'static Behaviour {
+    BEHAVIOURS.get(name).unwrap_or(&DEFAULT_BEHAVIOUR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn situation(health: f32, threat_dist: Option<f32>) -> Situation {
+        Situation {
+            health,
+            threat_dist,
+            ..Situation::default()
+        }
+    }
+
+    #[test]
+    fn behaviours_load() {
+        for name in &["wanderer", "pet", "enemy"] {
+            assert!(BEHAVIOURS.contains_key(*name), "missing behaviour {}", name);
+        }
+        assert!(behaviour("enemy").aggressive);
+    }
+
+    #[test]
+    fn enemy_attacks_and_flees_when_hurt() {
+        let enemy = behaviour("enemy");
+        assert_eq!(enemy.choose(&situation(1.0, None)), Action::Wander);
+        assert_eq!(enemy.choose(&situation(1.0, Some(10.0))), Action::Attack);
+        assert_eq!(enemy.choose(&situation(0.1, Some(10.0))), Action::Flee);
+    }
+
+    #[test]
+    fn pet_follows_distant_owner() {
+        let pet = behaviour("pet");
+        let mut situation = situation(1.0, None);
+        situation.owner_dist = Some(3.0);
+        assert_eq!(pet.choose(&situation), Action::Wander);
+        situation.owner_dist = Some(20.0);
+        assert_eq!(pet.choose(&situation), Action::Follow);
+    }
+}
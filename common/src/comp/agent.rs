@@ -1,18 +1,176 @@
+use crate::pathfinding::Path;
 use specs::{Component, Entity as EcsEntity, VecStorage};
+use std::sync::Arc;
 use vek::*;
 
-#[derive(Copy, Clone, Debug)]
-pub enum Agent {
-    Wanderer(Vec2<f32>),
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AgentKind {
+    Wanderer,
+    /// A tamed animal, following the orders of the player with the `Uid` `owner`.
     Pet {
-        target: EcsEntity,
-        offset: Vec2<f32>,
-    },
-    Enemy {
-        target: Option<EcsEntity>,
+        owner: u64,
+        command: PetCommand,
     },
+    Enemy,
+}
+
+/// What a pet was last told to do by its owner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PetCommand {
+    Follow,
+    /// Stand still instead of following the owner around, unless something attacks.
+    Stay,
+    /// Go after the entity with the given `Uid` until it's dead or gone.
+    Attack(u64),
+}
+
+/// An entity an agent is wary of, and where it last saw it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Threat {
+    pub entity: EcsEntity,
+    pub last_seen_pos: Vec3<f32>,
+    pub last_seen: f64,
+}
+
+/// A path through the terrain an agent is following.
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub from: Vec3<i32>,
+    pub to: Vec3<i32>,
+    /// `None` until the path has been found.
+    pub path: Option<Arc<Path>>,
+    /// The index of the next node of the path to walk to.
+    pub next: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Agent {
+    pub kind: AgentKind,
+    /// The name of the behaviour (see `ai::behaviour`) that drives this agent.
+    pub behaviour: String,
+    /// Agents in the same pack stick together and share the threats they know about.
+    pub pack: Option<u32>,
+    pub threats: Vec<Threat>,
+    /// The direction the agent is wandering in.
+    pub bearing: Vec2<f32>,
+    /// Where the agent stays relative to its owner.
+    pub offset: Vec2<f32>,
+    pub route: Option<Route>,
+}
+
+impl Agent {
+    fn new(kind: AgentKind, behaviour: &str) -> Self {
+        Self {
+            kind,
+            behaviour: behaviour.to_owned(),
+            pack: None,
+            threats: Vec::new(),
+            bearing: Vec2::zero(),
+            offset: Vec2::zero(),
+            route: None,
+        }
+    }
+
+    pub fn wanderer() -> Self {
+        Self::new(AgentKind::Wanderer, "wanderer")
+    }
+
+    pub fn pet(owner: u64) -> Self {
+        Self::new(
+            AgentKind::Pet {
+                owner,
+                command: PetCommand::Follow,
+            },
+            "pet",
+        )
+    }
+
+    pub fn enemy() -> Self {
+        Self::new(AgentKind::Enemy, "enemy")
+    }
+
+    pub fn with_behaviour(mut self, behaviour: &str) -> Self {
+        self.behaviour = behaviour.to_owned();
+        self
+    }
+
+    pub fn with_pack(mut self, pack: u32) -> Self {
+        self.pack = Some(pack);
+        self
+    }
+
+    /// The `Uid` of the owner of this agent, if it's a pet.
+    pub fn owner(&self) -> Option<u64> {
+        match self.kind {
+            AgentKind::Pet { owner, .. } => Some(owner),
+            _ => None,
+        }
+    }
+
+    pub fn command(&self) -> Option<PetCommand> {
+        match self.kind {
+            AgentKind::Pet { command, .. } => Some(command),
+            _ => None,
+        }
+    }
+
+    /// Remember seeing a threat, keeping only the most recent sighting of each entity.
+    pub fn remember(&mut self, threat: Threat) {
+        match self.threats.iter_mut().find(|t| t.entity == threat.entity) {
+            Some(known) if known.last_seen < threat.last_seen => *known = threat,
+            Some(_) => {}
+            None => self.threats.push(threat),
+        }
+    }
+
+    /// Forget the threats that were last seen before `time`.
+    pub fn forget_before(&mut self, time: f64) {
+        self.threats.retain(|threat| threat.last_seen >= time);
+    }
+
+    /// The remembered threat closest to `pos`.
+    pub fn nearest_threat(&self, pos: Vec3<f32>) -> Option<&Threat> {
+        self.threats.iter().min_by(|a, b| {
+            a.last_seen_pos
+                .distance_squared(pos)
+                .partial_cmp(&b.last_seen_pos.distance_squared(pos))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
 }
 
 impl Component for Agent {
     type Storage = VecStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, World};
+
+    #[test]
+    fn threat_memory() {
+        let mut world = World::new();
+        let a = world.create_entity().build();
+        let b = world.create_entity().build();
+        let threat = |entity, x, last_seen| Threat {
+            entity,
+            last_seen_pos: Vec3::new(x, 0.0, 0.0),
+            last_seen,
+        };
+
+        let mut agent = Agent::enemy();
+        agent.remember(threat(a, 10.0, 1.0));
+        agent.remember(threat(b, 5.0, 2.0));
+        // An older sighting doesn't replace a newer one
+        agent.remember(threat(a, 20.0, 0.5));
+        assert_eq!(agent.threats.len(), 2);
+        assert_eq!(agent.nearest_threat(Vec3::zero()).unwrap().entity, b);
+
+        agent.remember(threat(a, 1.0, 3.0));
+        assert_eq!(agent.nearest_threat(Vec3::zero()).unwrap().entity, a);
+
+        agent.forget_before(2.5);
+        assert_eq!(agent.threats, vec![threat(a, 1.0, 3.0)]);
+    }
+}
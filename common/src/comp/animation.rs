@@ -7,6 +7,7 @@ pub enum Animation {
     Jump,
     Gliding,
     Attack,
+    Swim,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
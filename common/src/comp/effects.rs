@@ -0,0 +1,210 @@
+use crate::state::Uid;
+use specs::{Component, FlaggedStorage, VecStorage};
+
+/// How often (in seconds) effects that change health do so.
+pub const EFFECT_TICK: f32 = 1.0;
+// The most poison effects an entity can suffer from at once
+const MAX_POISON_STACKS: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EffectKind {
+    /// Deals `strength` damage every `EFFECT_TICK`.
+    Poison,
+    /// Slows movement down by `strength` percent.
+    Slow,
+    /// Heals `strength` health every `EFFECT_TICK`.
+    Regeneration,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 3] = [
+        EffectKind::Poison,
+        EffectKind::Slow,
+        EffectKind::Regeneration,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectKind::Poison => "Poison",
+            EffectKind::Slow => "Slow",
+            EffectKind::Regeneration => "Regeneration",
+        }
+    }
+
+    pub fn is_harmful(&self) -> bool {
+        match self {
+            EffectKind::Poison | EffectKind::Slow => true,
+            EffectKind::Regeneration => false,
+        }
+    }
+}
+
+/// A status effect that wears off after a while.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub strength: i32,
+    /// How long (in seconds) the effect lasts for.
+    pub remaining: f32,
+    /// The time (in seconds) since the effect last ticked.
+    pub since_tick: f32,
+    /// The entity that inflicted the effect, which gets the blame for its damage.
+    pub by: Option<Uid>,
+}
+
+impl Effect {
+    pub fn new(kind: EffectKind, strength: i32, duration: f32) -> Self {
+        Self {
+            kind,
+            strength,
+            remaining: duration,
+            since_tick: 0.0,
+            by: None,
+        }
+    }
+
+    /// The same effect, inflicted by the given entity.
+    pub fn inflicted_by(self, by: Uid) -> Self {
+        Self {
+            by: Some(by),
+            ..self
+        }
+    }
+}
+
+/// The status effects an entity is under.
+///
+/// Poison stacks: every dose adds to the damage, up to `MAX_POISON_STACKS` doses, after which
+/// new doses replace the one closest to wearing off. Slow and regeneration don't stack: the
+/// strongest one applies, and applying one again renews its duration.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Effects(pub Vec<Effect>);
+
+impl Effects {
+    pub fn add(&mut self, effect: Effect) {
+        match effect.kind {
+            EffectKind::Poison => {
+                let doses = self
+                    .0
+                    .iter()
+                    .filter(|e| e.kind == EffectKind::Poison)
+                    .count();
+                if doses < MAX_POISON_STACKS {
+                    self.0.push(effect);
+                } else if let Some(weakest) = self
+                    .0
+                    .iter_mut()
+                    .filter(|e| e.kind == EffectKind::Poison)
+                    .min_by(|a, b| {
+                        a.remaining
+                            .partial_cmp(&b.remaining)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                {
+                    *weakest = effect;
+                }
+            }
+            EffectKind::Slow | EffectKind::Regeneration => {
+                match self.0.iter_mut().find(|e| e.kind == effect.kind) {
+                    Some(existing) => {
+                        existing.strength = existing.strength.max(effect.strength);
+                        existing.remaining = existing.remaining.max(effect.remaining);
+                    }
+                    None => self.0.push(effect),
+                }
+            }
+        }
+    }
+
+    /// Let time pass for the effects, removing the ones that wore off. Returns the change in
+    /// health caused by the effects that ticked, split into damage and healing, and who inflicted
+    /// the last dose of poison that ticked.
+    pub fn tick(&mut self, dt: f32) -> (i32, i32, Option<Uid>) {
+        let (mut damage, mut healing, mut poisoner) = (0, 0, None);
+        for effect in &mut self.0 {
+            effect.remaining -= dt;
+            effect.since_tick += dt;
+            if effect.since_tick >= EFFECT_TICK {
+                effect.since_tick -= EFFECT_TICK;
+                match effect.kind {
+                    EffectKind::Poison => {
+                        damage += effect.strength;
+                        poisoner = effect.by.or(poisoner);
+                    }
+                    EffectKind::Regeneration => healing += effect.strength,
+                    EffectKind::Slow => {}
+                }
+            }
+        }
+        self.0.retain(|effect| effect.remaining > 0.0);
+        (damage, healing, poisoner)
+    }
+
+    /// What the movement speed of the entity gets multiplied with.
+    pub fn speed_factor(&self) -> f32 {
+        self.0
+            .iter()
+            .filter(|effect| effect.kind == EffectKind::Slow)
+            .map(|effect| 1.0 - effect.strength.max(0).min(100) as f32 / 100.0)
+            .fold(1.0, f32::min)
+    }
+}
+
+impl Component for Effects {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+    use specs::Builder;
+
+    #[test]
+    fn poison_stacks() {
+        let mut effects = Effects::default();
+        for i in 0..MAX_POISON_STACKS + 2 {
+            effects.add(Effect::new(EffectKind::Poison, 2, 5.0 + i as f32));
+        }
+        assert_eq!(effects.0.len(), MAX_POISON_STACKS);
+
+        // Every dose deals damage
+        assert_eq!(
+            effects.tick(EFFECT_TICK),
+            (2 * MAX_POISON_STACKS as i32, 0, None)
+        );
+        assert_eq!(effects.tick(EFFECT_TICK / 2.0), (0, 0, None));
+    }
+
+    #[test]
+    fn poison_blames_the_last_poisoner() {
+        // Only synced worlds hand out `Uid`s
+        let mut state = State::new();
+        let mut uid = || {
+            let entity = state.ecs_mut().create_entity_synced().build();
+            state.ecs().uid_from_entity(entity).unwrap()
+        };
+        let (first, second) = (uid(), uid());
+        let mut effects = Effects::default();
+        effects.add(Effect::new(EffectKind::Poison, 2, 5.0).inflicted_by(first));
+        effects.add(Effect::new(EffectKind::Poison, 3, 5.0).inflicted_by(second));
+        effects.add(Effect::new(EffectKind::Poison, 1, 5.0));
+        assert_eq!(effects.tick(EFFECT_TICK), (6, 0, Some(second)));
+    }
+
+    #[test]
+    fn slow_and_regeneration_refresh() {
+        let mut effects = Effects::default();
+        effects.add(Effect::new(EffectKind::Slow, 20, 2.0));
+        effects.add(Effect::new(EffectKind::Slow, 50, 1.0));
+        effects.add(Effect::new(EffectKind::Regeneration, 3, 4.0));
+        assert_eq!(effects.0.len(), 2);
+        assert_eq!(effects.speed_factor(), 0.5);
+
+        assert_eq!(effects.tick(EFFECT_TICK), (0, 3, None));
+        // The slow wears off after its longest duration
+        effects.tick(1.5);
+        assert_eq!(effects.speed_factor(), 1.0);
+        assert_eq!(effects.0.len(), 1);
+    }
+}
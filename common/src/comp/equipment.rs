@@ -0,0 +1,225 @@
+use crate::{
+    comp::{actor, Body, HumanoidBody},
+    inventory::item::{Armor, EquipSlot, Item, Rarity, WeaponStats},
+};
+use specs::{Component, FlaggedStorage, VecStorage};
+
+/// The items an entity wears and fights with, one per slot.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub head: Option<Item>,
+    pub shoulders: Option<Item>,
+    pub chest: Option<Item>,
+    pub belt: Option<Item>,
+    pub hands: Option<Item>,
+    pub legs: Option<Item>,
+    pub feet: Option<Item>,
+    pub back: Option<Item>,
+    pub tabard: Option<Item>,
+    pub gem: Option<Item>,
+    pub neck: Option<Item>,
+    pub weapon: Option<Item>,
+}
+
+impl Equipment {
+    /// What a character with the given body starts out with: humanoids wear plain clothes that
+    /// look like the ones they were created with.
+    pub fn for_body(body: &Body) -> Self {
+        let clothes = |variant| {
+            Some(Item::Armor {
+                defense: 0,
+                health_bonus: 0,
+                rarity: Rarity::Common,
+                variant,
+            })
+        };
+        match body {
+            Body::Humanoid(body) => Self {
+                shoulders: clothes(Armor::Shoulders(body.shoulder)),
+                chest: clothes(Armor::Chestplate(body.chest)),
+                belt: clothes(Armor::Belt(body.belt)),
+                legs: clothes(Armor::Pants(body.pants)),
+                feet: clothes(Armor::Boots(body.foot)),
+                weapon: Some(Item::starter_weapon(body.weapon)),
+                ..Self::default()
+            },
+            Body::Quadruped(_) | Body::QuadrupedMedium(_) => Self::default(),
+        }
+    }
+
+    pub fn get(&self, slot: EquipSlot) -> Option<&Item> {
+        match slot {
+            EquipSlot::Head => self.head.as_ref(),
+            EquipSlot::Shoulders => self.shoulders.as_ref(),
+            EquipSlot::Chest => self.chest.as_ref(),
+            EquipSlot::Belt => self.belt.as_ref(),
+            EquipSlot::Hands => self.hands.as_ref(),
+            EquipSlot::Legs => self.legs.as_ref(),
+            EquipSlot::Feet => self.feet.as_ref(),
+            EquipSlot::Back => self.back.as_ref(),
+            EquipSlot::Tabard => self.tabard.as_ref(),
+            EquipSlot::Gem => self.gem.as_ref(),
+            EquipSlot::Neck => self.neck.as_ref(),
+            EquipSlot::Weapon => self.weapon.as_ref(),
+        }
+    }
+
+    fn get_mut(&mut self, slot: EquipSlot) -> &mut Option<Item> {
+        match slot {
+            EquipSlot::Head => &mut self.head,
+            EquipSlot::Shoulders => &mut self.shoulders,
+            EquipSlot::Chest => &mut self.chest,
+            EquipSlot::Belt => &mut self.belt,
+            EquipSlot::Hands => &mut self.hands,
+            EquipSlot::Legs => &mut self.legs,
+            EquipSlot::Feet => &mut self.feet,
+            EquipSlot::Back => &mut self.back,
+            EquipSlot::Tabard => &mut self.tabard,
+            EquipSlot::Gem => &mut self.gem,
+            EquipSlot::Neck => &mut self.neck,
+            EquipSlot::Weapon => &mut self.weapon,
+        }
+    }
+
+    /// Put an item into the slot it belongs in, returning the item that was there before. Items
+    /// that can't be equipped are given back as the error.
+    pub fn equip(&mut self, item: Item) -> Result<Option<Item>, Item> {
+        match item.slot() {
+            Some(slot) => Ok(self.get_mut(slot).replace(item)),
+            None => Err(item),
+        }
+    }
+
+    pub fn unequip(&mut self, slot: EquipSlot) -> Option<Item> {
+        self.get_mut(slot).take()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        EquipSlot::ALL
+            .iter()
+            .filter_map(move |&slot| self.get(slot))
+    }
+
+    pub fn weapon_stats(&self) -> WeaponStats {
+        self.weapon
+            .and_then(|weapon| weapon.weapon_stats())
+            .unwrap_or(WeaponStats::UNARMED)
+    }
+
+    pub fn defense(&self) -> i32 {
+        self.items().map(|item| item.defense()).sum()
+    }
+
+    pub fn health_bonus(&self) -> i32 {
+        self.items().map(|item| item.health_bonus()).sum()
+    }
+
+    /// Dress a humanoid in the equipped items. Slots without an item show the plain look.
+    pub fn dress(&self, body: &mut HumanoidBody) {
+        let armor = |slot| match self.get(slot) {
+            Some(Item::Armor { variant, .. }) => Some(*variant),
+            _ => None,
+        };
+
+        body.shoulder = match armor(EquipSlot::Shoulders) {
+            Some(Armor::Shoulders(shoulder)) => shoulder,
+            _ => actor::Shoulder::Default,
+        };
+        body.chest = match armor(EquipSlot::Chest) {
+            Some(Armor::Chestplate(chest)) => chest,
+            _ => actor::Chest::Default,
+        };
+        body.belt = match armor(EquipSlot::Belt) {
+            Some(Armor::Belt(belt)) => belt,
+            _ => actor::Belt::Dark,
+        };
+        body.hand = match armor(EquipSlot::Hands) {
+            Some(Armor::Gloves(hand)) => hand,
+            _ => actor::Hand::Default,
+        };
+        body.pants = match armor(EquipSlot::Legs) {
+            Some(Armor::Pants(pants)) => pants,
+            _ => actor::Pants::Default,
+        };
+        body.foot = match armor(EquipSlot::Feet) {
+            Some(Armor::Boots(foot)) => foot,
+            _ => actor::Foot::Default,
+        };
+        // There's no look for empty hands, so characters keep showing their last weapon.
+        if let Some(Item::Weapon { variant, .. }) = self.weapon {
+            body.weapon = variant;
+        }
+    }
+}
+
+impl Component for Equipment {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{comp::QuadrupedBody, inventory::item::Ingredient};
+
+    fn armor(defense: i32, health_bonus: i32, variant: Armor) -> Item {
+        Item::Armor {
+            defense,
+            health_bonus,
+            rarity: Rarity::Common,
+            variant,
+        }
+    }
+
+    #[test]
+    fn weapons_and_armor() {
+        let mut body = HumanoidBody::random();
+        body.weapon = actor::Weapon::Hammer;
+        let mut equipment = Equipment::for_body(&Body::Humanoid(body));
+        let hammer = equipment.weapon_stats();
+        assert!(hammer.reach > WeaponStats::UNARMED.reach);
+        assert_eq!(hammer.damage_against(0), 16);
+
+        equipment.equip(armor(100, 0, Armor::Back)).unwrap();
+        assert_eq!(hammer.damage_against(equipment.defense()), 8);
+        assert_eq!(WeaponStats::UNARMED.damage_against(10_000), 1);
+
+        let pig = Equipment::for_body(&Body::Quadruped(QuadrupedBody::random()));
+        assert_eq!(pig.weapon_stats(), WeaponStats::UNARMED);
+    }
+
+    #[test]
+    fn slots_and_bonuses() {
+        let mut equipment = Equipment::default();
+        let blue = armor(10, 20, Armor::Chestplate(actor::Chest::Blue));
+        let green = armor(15, 5, Armor::Chestplate(actor::Chest::Green));
+
+        assert_eq!(equipment.equip(blue), Ok(None));
+        assert_eq!(equipment.equip(armor(5, 10, Armor::Helmet)), Ok(None));
+        assert_eq!(equipment.defense(), 15);
+        assert_eq!(equipment.health_bonus(), 30);
+
+        // Items replace the one in their slot
+        assert_eq!(equipment.equip(green), Ok(Some(blue)));
+        let meat = Item::Ingredient {
+            kind: Ingredient::Meat,
+            amount: 1,
+        };
+        assert_eq!(equipment.equip(meat), Err(meat));
+        assert_eq!(equipment.get(EquipSlot::Chest), Some(&green));
+
+        let mut body = HumanoidBody::random();
+        body.chest = actor::Chest::Brown;
+        let mut dressed = body;
+        Equipment::for_body(&Body::Humanoid(body)).dress(&mut dressed);
+        assert_eq!(dressed, body);
+
+        equipment.dress(&mut body);
+        assert_eq!(body.chest, actor::Chest::Green);
+
+        assert_eq!(equipment.unequip(EquipSlot::Chest), Some(green));
+        assert_eq!(equipment.unequip(EquipSlot::Chest), None);
+        equipment.dress(&mut body);
+        assert_eq!(body.chest, actor::Chest::Default);
+        assert_eq!(equipment.health_bonus(), 10);
+    }
+}
@@ -0,0 +1,42 @@
+use specs::{Component, FlaggedStorage, VecStorage};
+
+/// The most members a group can have.
+pub const MAX_GROUP_SIZE: usize = 6;
+
+/// The group a player belongs to. Members of a group share the experience of their kills, can't
+/// hurt each other and can talk in group chat.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Group {
+    /// Identifies the group for as long as it exists.
+    pub id: u64,
+    /// The `Uid` of the member that can invite and kick others.
+    pub leader: u64,
+}
+
+impl Group {
+    /// Whether two entities with the given groups are in the same one.
+    pub fn same(a: Option<&Group>, b: Option<&Group>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.id == b.id,
+            _ => false,
+        }
+    }
+}
+
+impl Component for Group {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+/// An invitation to join the group of another player, which becomes a new group if they aren't
+/// in one yet.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupInvite {
+    /// The `Uid` of the inviting player.
+    pub from: u64,
+    /// When the invitation was sent.
+    pub time: f64,
+}
+
+impl Component for GroupInvite {
+    type Storage = VecStorage<Self>;
+}
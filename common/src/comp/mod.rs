@@ -1,9 +1,14 @@
 pub mod actor;
 pub mod agent;
 pub mod animation;
+pub mod effects;
+pub mod equipment;
+pub mod group;
 pub mod inputs;
 pub mod phys;
 pub mod player;
+pub mod projectile;
+pub mod pvp;
 pub mod stats;
 
 // Reexports
@@ -13,14 +18,26 @@ pub use actor::HumanoidBody;
 pub use actor::QuadrupedBody;
 pub use actor::QuadrupedMediumBody;
 pub use agent::Agent;
+pub use agent::AgentKind;
+pub use agent::PetCommand;
 pub use animation::Animation;
 pub use animation::AnimationInfo;
+pub use effects::Effect;
+pub use effects::EffectKind;
+pub use effects::Effects;
+pub use equipment::Equipment;
+pub use group::Group;
 pub use inputs::Attacking;
 pub use inputs::Control;
 pub use inputs::Gliding;
 pub use inputs::Jumping;
 pub use inputs::Respawning;
 pub use player::Player;
+pub use projectile::Projectile;
+pub use pvp::PvpFlag;
 pub use stats::Dying;
 pub use stats::HealthSource;
+pub use stats::LifeState;
 pub use stats::Stats;
+pub use stats::MAX_BREATH;
+pub use stats::MAX_ENERGY;
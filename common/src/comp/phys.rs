@@ -1,3 +1,5 @@
+use super::actor::{Body, Race};
+use crate::terrain::FluidKind;
 use specs::{Component, NullStorage, VecStorage};
 use vek::*;
 
@@ -36,3 +38,112 @@ pub struct ForceUpdate;
 impl Component for ForceUpdate {
     type Storage = NullStorage<Self>;
 }
+
+// Collider
+
+/// The axis-aligned bounding box of an entity. The position of the entity is at the centre of
+/// the base of the box.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Collider {
+    pub size: Vec3<f32>,
+}
+
+impl Collider {
+    pub fn for_body(body: &Body) -> Self {
+        let size = match body {
+            Body::Humanoid(body) if body.race == Race::Dwarf => Vec3::new(0.9, 0.9, 1.5),
+            Body::Humanoid(_) => Vec3::new(0.9, 0.9, 1.9),
+            Body::Quadruped(_) => Vec3::new(0.8, 0.8, 0.9),
+            Body::QuadrupedMedium(_) => Vec3::new(1.2, 1.2, 1.3),
+        };
+        Self { size }
+    }
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self {
+            size: Vec3::new(0.9, 0.9, 1.9),
+        }
+    }
+}
+
+impl Component for Collider {
+    type Storage = VecStorage<Self>;
+}
+
+// Mass
+
+/// The mass of an entity in kilograms. Lighter entities get knocked back further.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Mass(pub f32);
+
+impl Mass {
+    pub fn for_body(body: &Body) -> Self {
+        Self(match body {
+            Body::Humanoid(_) => 60.0,
+            Body::Quadruped(_) => 20.0,
+            Body::QuadrupedMedium(_) => 40.0,
+        })
+    }
+}
+
+impl Default for Mass {
+    fn default() -> Self {
+        Self(60.0)
+    }
+}
+
+impl Component for Mass {
+    type Storage = VecStorage<Self>;
+}
+
+// Friction
+
+/// How strongly an entity grips the ground, relative to a humanoid.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Friction(pub f32);
+
+impl Friction {
+    pub fn for_body(body: &Body) -> Self {
+        Self(match body {
+            Body::Humanoid(_) => 1.0,
+            Body::Quadruped(_) => 0.8,
+            Body::QuadrupedMedium(_) => 1.2,
+        })
+    }
+}
+
+impl Default for Friction {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl Component for Friction {
+    type Storage = VecStorage<Self>;
+}
+
+// InFluid
+
+/// Marks entities that are (partly) in a fluid.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct InFluid {
+    /// The fraction of the height of the entity that is submerged.
+    pub submersion: f32,
+    pub kind: FluidKind,
+}
+
+impl Component for InFluid {
+    type Storage = VecStorage<Self>;
+}
+
+// OnGround
+
+/// Marks entities that are standing on solid terrain.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OnGround;
+
+impl Component for OnGround {
+    type Storage = NullStorage<Self>;
+}
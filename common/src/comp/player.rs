@@ -1,4 +1,5 @@
 use specs::{Component, FlaggedStorage, NullStorage, VecStorage};
+use vek::*;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Player {
@@ -24,3 +25,10 @@ pub struct Respawn;
 impl Component for Respawn {
     type Storage = NullStorage<Self>;
 }
+
+/// Where a character comes back to life after dying, instead of the world spawn point.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RespawnPoint(pub Vec3<f32>);
+impl Component for RespawnPoint {
+    type Storage = VecStorage<Self>;
+}
@@ -0,0 +1,80 @@
+use crate::state::Uid;
+use specs::{Component, FlaggedStorage, VecStorage};
+use vek::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectileKind {
+    Arrow,
+    Fireball,
+}
+
+impl ProjectileKind {
+    /// How fast (in blocks per second) the projectile leaves the weapon.
+    pub fn speed(&self) -> f32 {
+        match self {
+            ProjectileKind::Arrow => 40.0,
+            ProjectileKind::Fireball => 25.0,
+        }
+    }
+
+    /// How strongly the projectile is pulled down, relative to other entities.
+    pub fn gravity(&self) -> f32 {
+        match self {
+            ProjectileKind::Arrow => 0.5,
+            ProjectileKind::Fireball => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    /// The entity that fired the projectile, which gets the blame for its hits.
+    pub owner: Uid,
+    /// Whether a player or one of their pets fired the projectile, so the PvP rules still apply
+    /// to its hits after the owner is gone.
+    pub from_player: bool,
+    pub damage: i32,
+    pub strength: i32,
+    /// How long (in seconds) the projectile has been flying.
+    pub age: f32,
+    /// Set once the projectile hit something, after which the server removes it.
+    pub done: bool,
+}
+
+impl Projectile {
+    pub fn new(
+        kind: ProjectileKind,
+        owner: Uid,
+        from_player: bool,
+        damage: i32,
+        strength: i32,
+    ) -> Self {
+        Self {
+            kind,
+            owner,
+            from_player,
+            damage,
+            strength,
+            age: 0.0,
+            done: false,
+        }
+    }
+}
+
+impl Component for Projectile {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+/// A projectile fired during a step, with its initial position and velocity.
+#[derive(Clone, Debug)]
+pub struct FiredProjectile {
+    pub pos: Vec3<f32>,
+    pub vel: Vec3<f32>,
+    pub projectile: Projectile,
+}
+
+/// A resource collecting the projectiles fired by the systems, to be turned into entities by
+/// the server (see `State::maintain_projectiles`).
+#[derive(Default)]
+pub struct FiredProjectiles(pub Vec<FiredProjectile>);
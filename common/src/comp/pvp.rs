@@ -0,0 +1,172 @@
+use super::{Agent, Player};
+use crate::state::Uid;
+use specs::{Component, Entity as EcsEntity, FlaggedStorage, NullStorage, ReadStorage};
+use std::fmt;
+use vek::*;
+
+/// Whether players can hurt each other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PvpMode {
+    Off,
+    On,
+    /// Only players that turned on their `PvpFlag` can hurt each other.
+    OptIn,
+}
+
+/// A region of the world where players can't hurt each other, whatever the `PvpMode`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SafeZone {
+    pub name: String,
+    /// The corners of the zone on the horizontal plane. Zones reach from the bottom of the world
+    /// to the sky.
+    pub min: Vec2<f32>,
+    pub max: Vec2<f32>,
+}
+
+impl SafeZone {
+    pub fn contains(&self, pos: Vec3<f32>) -> bool {
+        pos.x >= self.min.x && pos.x <= self.max.x && pos.y >= self.min.y && pos.y <= self.max.y
+    }
+}
+
+/// A resource with the rules for fights between players, set by the server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PvpRules {
+    pub mode: PvpMode,
+    pub safe_zones: Vec<SafeZone>,
+}
+
+impl Default for PvpRules {
+    fn default() -> Self {
+        Self {
+            mode: PvpMode::On,
+            safe_zones: Vec::new(),
+        }
+    }
+}
+
+impl PvpRules {
+    /// The safe zone at the given position, if there is one.
+    pub fn safe_zone_at(&self, pos: Vec3<f32>) -> Option<&SafeZone> {
+        self.safe_zones.iter().find(|zone| zone.contains(pos))
+    }
+
+    /// Whether a player at `attacker_pos` may hurt a player at `target_pos`, given whether either
+    /// of them turned on their `PvpFlag`.
+    pub fn check(
+        &self,
+        attacker_pos: Vec3<f32>,
+        attacker_flag: bool,
+        target_pos: Vec3<f32>,
+        target_flag: bool,
+    ) -> Result<(), PvpBlock> {
+        if self.mode == PvpMode::Off {
+            return Err(PvpBlock::Off);
+        }
+        if let Some(zone) = self
+            .safe_zone_at(attacker_pos)
+            .or_else(|| self.safe_zone_at(target_pos))
+        {
+            return Err(PvpBlock::SafeZone(zone.name.clone()));
+        }
+        match self.mode {
+            PvpMode::OptIn if !attacker_flag => Err(PvpBlock::AttackerNotFlagged),
+            PvpMode::OptIn if !target_flag => Err(PvpBlock::TargetNotFlagged),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The `Uid` of the player behind the attacks of an entity: the entity itself if it's a player,
+/// or the owner of a pet. The rules for fights between players apply to the attacks of both.
+pub fn player_behind(
+    entity: EcsEntity,
+    uids: &ReadStorage<Uid>,
+    players: &ReadStorage<Player>,
+    agents: &ReadStorage<Agent>,
+) -> Option<u64> {
+    if players.get(entity).is_some() {
+        uids.get(entity).map(|&uid| uid.into())
+    } else {
+        agents.get(entity)?.owner()
+    }
+}
+
+/// Why a player couldn't hurt another one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PvpBlock {
+    Off,
+    SafeZone(String),
+    AttackerNotFlagged,
+    TargetNotFlagged,
+}
+
+impl fmt::Display for PvpBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PvpBlock::Off => write!(f, "Players can't hurt each other on this server."),
+            PvpBlock::SafeZone(name) => write!(f, "Players can't hurt each other in {}.", name),
+            PvpBlock::AttackerNotFlagged => {
+                write!(f, "Type /pvp to be able to fight other players.")
+            }
+            PvpBlock::TargetNotFlagged => write!(f, "That player doesn't want to fight."),
+        }
+    }
+}
+
+/// Marks players that want to fight other players when the `PvpMode` is `OptIn`.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PvpFlag;
+
+impl Component for PvpFlag {
+    type Storage = FlaggedStorage<Self, NullStorage<Self>>;
+}
+
+/// An attack of a player on another one that the rules prevented.
+#[derive(Clone, Debug)]
+pub struct BlockedHit {
+    pub attacker: Uid,
+    pub reason: PvpBlock,
+}
+
+/// A resource collecting the hits the systems prevented, for the server to tell the attackers
+/// about (see `State::take_blocked_hits`).
+#[derive(Default)]
+pub struct BlockedHits(pub Vec<BlockedHit>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_zones_and_flags() {
+        let mut rules = PvpRules {
+            mode: PvpMode::OptIn,
+            safe_zones: vec![SafeZone {
+                name: "Town".to_owned(),
+                min: Vec2::new(0.0, 0.0),
+                max: Vec2::new(10.0, 10.0),
+            }],
+        };
+        let (inside, outside) = (Vec3::new(5.0, 5.0, 100.0), Vec3::new(20.0, 5.0, 0.0));
+
+        assert_eq!(rules.check(outside, true, outside, true), Ok(()));
+        assert_eq!(
+            rules.check(outside, true, outside, false),
+            Err(PvpBlock::TargetNotFlagged)
+        );
+        // Neither side of a fight may stand in a safe zone
+        assert_eq!(
+            rules.check(outside, true, inside, true),
+            Err(PvpBlock::SafeZone("Town".to_owned()))
+        );
+
+        rules.mode = PvpMode::On;
+        assert_eq!(rules.check(outside, false, outside, false), Ok(()));
+        rules.mode = PvpMode::Off;
+        assert_eq!(
+            rules.check(outside, true, outside, true),
+            Err(PvpBlock::Off)
+        );
+    }
+}
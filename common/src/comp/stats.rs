@@ -6,6 +6,12 @@ pub enum HealthSource {
     Attack { by: Uid }, // TODO: Implement weapon
     Suicide,
     Unknown,
+    Drowning,
+    Fall,
+    Lava,
+    Void,
+    Poison { by: Option<Uid> },
+    Heal,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -30,13 +36,52 @@ impl Health {
         self.current = (self.current as i32 + amount).max(0) as u32;
         self.last_change = Some((amount, 0.0, cause));
     }
+    /// Change the maximum health, keeping the current health within it.
+    pub fn set_maximum(&mut self, amount: u32) {
+        self.maximum = amount;
+        self.current = self.current.min(amount);
+    }
+}
+
+/// The maximum health of a level 1 entity without any bonuses.
+pub const BASE_HEALTH: u32 = 100;
+/// The maximum health each level adds.
+pub const HEALTH_PER_LEVEL: u32 = 20;
+/// How much more damage (as a fraction) an entity deals with each level.
+pub const DAMAGE_PER_LEVEL: f32 = 0.1;
+// The experience a kill gives for each level of the victim
+const KILL_XP: u32 = 10;
+
+/// The experience needed to advance from the given level to the next one.
+pub fn xp_needed(level: u32) -> u32 {
+    100 * level
 }
 
+/// How long (in seconds) an entity can stay under water before it starts drowning.
+pub const MAX_BREATH: f32 = 10.0;
+/// The most energy an entity can have.
+pub const MAX_ENERGY: f32 = 100.0;
+/// How much energy is regained per second.
+pub const ENERGY_REGEN: f32 = 10.0;
+/// The energy every attack costs.
+pub const ATTACK_ENERGY: f32 = 10.0;
+/// The energy gliding costs per second, on top of the energy regained in that time.
+pub const GLIDE_ENERGY: f32 = 5.0;
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Stats {
     pub hp: Health,
+    pub level: u32,
+    /// The experience gained since reaching the current level.
     pub xp: u32,
-    pub is_dead: bool,
+    /// The number of seconds the entity can still stay under water.
+    pub breath: f32,
+    /// What attacking and gliding use up, regained over time.
+    pub energy: f32,
+    /// The number of seconds until lava burns the entity again. Only the side simulating the
+    /// entity needs it.
+    #[serde(skip)]
+    pub burn: f32,
 }
 
 impl Stats {
@@ -44,18 +89,49 @@ impl Stats {
         // TODO: Remove
         self.hp.current == 0
     }
+
+    /// The maximum health of the entity at its level, without bonuses from equipment.
+    pub fn base_health(&self) -> u32 {
+        BASE_HEALTH + HEALTH_PER_LEVEL * (self.level.max(1) - 1)
+    }
+
+    /// What the damage the entity deals gets multiplied with.
+    pub fn damage_scale(&self) -> f32 {
+        1.0 + DAMAGE_PER_LEVEL * (self.level.max(1) - 1) as f32
+    }
+
+    /// The experience whoever kills the entity gets.
+    pub fn xp_reward(&self) -> u32 {
+        KILL_XP * self.level.max(1)
+    }
+
+    /// Gain experience, advancing as many levels as it takes. Returns the number of levels
+    /// gained.
+    pub fn gain_xp(&mut self, amount: u32) -> u32 {
+        let mut levels = 0;
+        self.xp += amount;
+        while self.xp >= xp_needed(self.level) {
+            self.xp -= xp_needed(self.level);
+            self.level += 1;
+            levels += 1;
+        }
+        levels
+    }
 }
 
 impl Default for Stats {
     fn default() -> Self {
         Self {
             hp: Health {
-                current: 100,
-                maximum: 100,
+                current: BASE_HEALTH,
+                maximum: BASE_HEALTH,
                 last_change: None,
             },
+            level: 1,
             xp: 0,
-            is_dead: false,
+            breath: MAX_BREATH,
+            energy: MAX_ENERGY,
+            burn: 0.0,
         }
     }
 }
@@ -64,6 +140,30 @@ impl Component for Stats {
     type Storage = FlaggedStorage<Self, VecStorage<Self>>;
 }
 
+/// Whether an entity is alive or dead. Entities without one count as alive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifeState {
+    Alive,
+    /// Dead entities don't move, act or get hit until they respawn.
+    Dead,
+}
+
+impl LifeState {
+    pub fn is_alive(&self) -> bool {
+        *self == LifeState::Alive
+    }
+}
+
+impl Default for LifeState {
+    fn default() -> Self {
+        LifeState::Alive
+    }
+}
+
+impl Component for LifeState {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Dying {
     pub cause: HealthSource,
@@ -72,3 +172,24 @@ pub struct Dying {
 impl Component for Dying {
     type Storage = VecStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levelling() {
+        let mut stats = Stats::default();
+        assert_eq!(stats.gain_xp(50), 0);
+        assert_eq!(stats.level, 1);
+
+        // 50 + 300 = 100 (to level 2) + 200 (to level 3) + 50
+        assert_eq!(stats.gain_xp(300), 2);
+        assert_eq!(stats.level, 3);
+        assert_eq!(stats.xp, 50);
+
+        assert_eq!(stats.base_health(), BASE_HEALTH + 2 * HEALTH_PER_LEVEL);
+        assert!(stats.damage_scale() > Stats::default().damage_scale());
+        assert_eq!(stats.xp_reward(), 3 * KILL_XP);
+    }
+}
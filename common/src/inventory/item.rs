@@ -1,10 +1,13 @@
-use crate::comp::actor;
-use specs::{Component, VecStorage};
+use crate::comp::{
+    actor,
+    effects::{Effect, EffectKind},
+    projectile::ProjectileKind,
+};
+use specs::{Component, FlaggedStorage, VecStorage};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Armor {
-    // TODO: Don't make armor be a body part. Wearing enemy's head is funny but also creepy thing to do.
-    Helmet(actor::Head),
+    Helmet,
     Shoulders(actor::Shoulder),
     Chestplate(actor::Chest),
     Belt(actor::Belt),
@@ -17,6 +20,70 @@ pub enum Armor {
     Necklace,
 }
 
+impl Armor {
+    pub fn slot(&self) -> EquipSlot {
+        match self {
+            Armor::Helmet => EquipSlot::Head,
+            Armor::Shoulders(_) => EquipSlot::Shoulders,
+            Armor::Chestplate(_) => EquipSlot::Chest,
+            Armor::Belt(_) => EquipSlot::Belt,
+            Armor::Gloves(_) => EquipSlot::Hands,
+            Armor::Pants(_) => EquipSlot::Legs,
+            Armor::Boots(_) => EquipSlot::Feet,
+            Armor::Back => EquipSlot::Back,
+            Armor::Tabard => EquipSlot::Tabard,
+            Armor::Gem => EquipSlot::Gem,
+            Armor::Necklace => EquipSlot::Neck,
+        }
+    }
+}
+
+/// Where on a character an item is worn or held.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipSlot {
+    Head,
+    Shoulders,
+    Chest,
+    Belt,
+    Hands,
+    Legs,
+    Feet,
+    Back,
+    Tabard,
+    Gem,
+    Neck,
+    Weapon,
+}
+
+impl EquipSlot {
+    pub const ALL: [EquipSlot; 12] = [
+        EquipSlot::Head,
+        EquipSlot::Shoulders,
+        EquipSlot::Chest,
+        EquipSlot::Belt,
+        EquipSlot::Hands,
+        EquipSlot::Legs,
+        EquipSlot::Feet,
+        EquipSlot::Back,
+        EquipSlot::Tabard,
+        EquipSlot::Gem,
+        EquipSlot::Neck,
+        EquipSlot::Weapon,
+    ];
+}
+
+/// Things creatures leave behind that are only good for crafting and trading.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Ingredient {
+    Meat,
+    Pelt,
+    Bone,
+    Coin,
+}
+
+/// The most items of the same kind that fit into one inventory slot.
+pub const MAX_STACK: u32 = 99;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Rarity {
     Common,
@@ -39,8 +106,175 @@ pub enum Item {
         rarity: Rarity,
         variant: Armor,
     },
+    Ingredient {
+        kind: Ingredient,
+        amount: u32,
+    },
 }
 
 impl Component for Item {
-    type Storage = VecStorage<Self>;
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+/// How hard, how far, how wide and how often a weapon hits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WeaponStats {
+    pub damage: i32,
+    /// Scales how far hits knock targets back, in percent.
+    pub strength: i32,
+    /// How far away (in blocks) targets can be hit.
+    pub reach: f32,
+    /// How far (in degrees) targets can be off to the side of where the attacker is facing.
+    pub arc: f32,
+    /// How long (in seconds) an attack lasts before the next one can start.
+    pub cooldown: f32,
+    /// What ranged weapons fire, instead of hitting what's in reach.
+    pub projectile: Option<ProjectileKind>,
+    /// What melee hits inflict on the target besides damage.
+    pub effect: Option<Effect>,
+}
+
+impl WeaponStats {
+    /// Fists, claws and teeth.
+    pub const UNARMED: WeaponStats = WeaponStats {
+        damage: 10,
+        strength: 100,
+        reach: 3.0,
+        arc: 60.0,
+        cooldown: 0.4,
+        projectile: None,
+        effect: None,
+    };
+
+    /// The damage a hit deals to a target with the given armor defense. Every 100 points of
+    /// defense halve the damage again, but hits always deal some damage.
+    pub fn damage_against(&self, defense: i32) -> i32 {
+        mitigate(self.damage, defense)
+    }
+}
+
+/// The damage that gets through armor with the given defense, see `WeaponStats::damage_against`.
+pub fn mitigate(damage: i32, defense: i32) -> i32 {
+    (damage * 100 / (100 + defense.max(0))).max(1)
+}
+
+/// The reach (in blocks), arc (in degrees) and cooldown (in seconds) of each kind of weapon.
+fn handling(variant: actor::Weapon) -> (f32, f32, f32) {
+    match variant {
+        actor::Weapon::Daggers => (2.5, 50.0, 0.3),
+        actor::Weapon::SwordShield => (3.0, 50.0, 0.5),
+        actor::Weapon::Sword => (3.5, 60.0, 0.5),
+        actor::Weapon::Axe => (3.5, 70.0, 0.7),
+        actor::Weapon::Hammer => (4.0, 80.0, 1.0),
+        actor::Weapon::Bow => (2.0, 40.0, 0.6),
+        actor::Weapon::Staff => (3.0, 50.0, 0.6),
+    }
+}
+
+impl Item {
+    /// A common weapon of the given kind, like the ones characters start out with.
+    pub fn starter_weapon(variant: actor::Weapon) -> Self {
+        let damage = match variant {
+            actor::Weapon::Daggers => 6,
+            actor::Weapon::SwordShield => 8,
+            actor::Weapon::Sword => 10,
+            actor::Weapon::Axe => 12,
+            actor::Weapon::Hammer => 16,
+            actor::Weapon::Bow | actor::Weapon::Staff => 8,
+        };
+        Item::Weapon {
+            damage,
+            strength: 100,
+            rarity: Rarity::Common,
+            variant,
+        }
+    }
+
+    pub fn weapon_stats(&self) -> Option<WeaponStats> {
+        match *self {
+            Item::Weapon {
+                damage,
+                strength,
+                variant,
+                ..
+            } => {
+                let (reach, arc, cooldown) = handling(variant);
+                Some(WeaponStats {
+                    damage,
+                    strength,
+                    reach,
+                    arc,
+                    cooldown,
+                    projectile: match variant {
+                        actor::Weapon::Bow => Some(ProjectileKind::Arrow),
+                        actor::Weapon::Staff => Some(ProjectileKind::Fireball),
+                        _ => None,
+                    },
+                    effect: match variant {
+                        actor::Weapon::Daggers => Some(Effect::new(EffectKind::Poison, 2, 5.0)),
+                        actor::Weapon::Hammer => Some(Effect::new(EffectKind::Slow, 40, 2.0)),
+                        _ => None,
+                    },
+                })
+            }
+            Item::Armor { .. } | Item::Ingredient { .. } => None,
+        }
+    }
+
+    pub fn defense(&self) -> i32 {
+        match *self {
+            Item::Armor { defense, .. } => defense,
+            Item::Weapon { .. } | Item::Ingredient { .. } => 0,
+        }
+    }
+
+    /// How much the item raises the maximum health of whoever wears it.
+    pub fn health_bonus(&self) -> i32 {
+        match *self {
+            Item::Armor { health_bonus, .. } => health_bonus,
+            Item::Weapon { .. } | Item::Ingredient { .. } => 0,
+        }
+    }
+
+    /// The equipment slot the item goes into, if it can be equipped at all.
+    pub fn slot(&self) -> Option<EquipSlot> {
+        match self {
+            Item::Weapon { .. } => Some(EquipSlot::Weapon),
+            Item::Armor { variant, .. } => Some(variant.slot()),
+            Item::Ingredient { .. } => None,
+        }
+    }
+
+    /// How many items of the kind this item stands for.
+    pub fn amount(&self) -> u32 {
+        match *self {
+            Item::Ingredient { amount, .. } => amount,
+            Item::Weapon { .. } | Item::Armor { .. } => 1,
+        }
+    }
+
+    /// Move as many of the items in `other` onto this stack as fit, returning the rest.
+    pub fn stack(&mut self, other: Item) -> Option<Item> {
+        match (self, other) {
+            (
+                Item::Ingredient { kind, amount },
+                Item::Ingredient {
+                    kind: other_kind,
+                    amount: other_amount,
+                },
+            ) if *kind == other_kind => {
+                let moved = other_amount.min(MAX_STACK.saturating_sub(*amount));
+                *amount += moved;
+                if moved < other_amount {
+                    Some(Item::Ingredient {
+                        kind: other_kind,
+                        amount: other_amount - moved,
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => Some(other),
+        }
+    }
 }
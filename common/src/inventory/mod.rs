@@ -1,10 +1,12 @@
-use specs::{Component, VecStorage};
+use specs::{Component, NullStorage, VecStorage};
 
 //Re-Exports
 pub mod item;
 
 use item::Item;
-use std::mem::swap;
+
+/// How far away (in blocks) characters can pick up items lying in the world.
+pub const PICKUP_RANGE: f32 = 3.0;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Inventory {
@@ -23,24 +25,107 @@ impl Inventory {
         self.slots.get(cell).cloned().flatten()
     }
 
-    // Insert an item to a slot if its empty
-    pub fn swap(&mut self, cell: usize, item: Item) -> Option<Item> {
-        //TODO: Check if a slot is empty first.
-        self.slots.get_mut(cell).and_then(|cell| cell.replace(item))
-    }
+    /// Put an item into the inventory, stacking it onto items of the same kind first and then
+    /// into the first empty slot. Whatever doesn't fit is given back.
+    pub fn push(&mut self, item: Item) -> Option<Item> {
+        let mut rest = item;
+        for stack in self.slots.iter_mut().filter_map(|slot| slot.as_mut()) {
+            match stack.stack(rest) {
+                Some(left) => rest = left,
+                None => return None,
+            }
+        }
 
-    // Remove an item from the slot
-    pub fn remove(&mut self, cell: usize, item: Item) -> Option<Item> {
-        let mut tmp_item = Some(item);
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(rest);
+                None
+            }
+            None => Some(rest),
+        }
+    }
 
-        if let Some(old_item) = self.slots.get_mut(cell) {
-            swap(old_item, &mut tmp_item);
+    /// Put an item into a slot, returning the item that was there before. Slots that don't exist
+    /// give the item back.
+    pub fn swap(&mut self, cell: usize, item: Item) -> Option<Item> {
+        match self.slots.get_mut(cell) {
+            Some(slot) => slot.replace(item),
+            None => Some(item),
         }
+    }
+
+    /// Take the item out of a slot.
+    pub fn remove(&mut self, cell: usize) -> Option<Item> {
+        self.slots.get_mut(cell).and_then(|slot| slot.take())
+    }
 
-        tmp_item
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_some())
     }
 }
 
 impl Component for Inventory {
     type Storage = VecStorage<Self>;
 }
+
+/// Marks an inventory that changed and needs to be sent to its owner.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct InventoryUpdate;
+
+impl Component for InventoryUpdate {
+    type Storage = NullStorage<Self>;
+}
+
+/// When an item lying in the world was dropped, so that the server can remove it after a while.
+#[derive(Copy, Clone, Debug)]
+pub struct Dropped(pub f64);
+
+impl Component for Dropped {
+    type Storage = VecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comp::actor;
+    use item::{Ingredient, MAX_STACK};
+
+    #[test]
+    fn push_swap_remove() {
+        let sword = Item::starter_weapon(actor::Weapon::Sword);
+        let axe = Item::starter_weapon(actor::Weapon::Axe);
+        let mut inventory = Inventory::new();
+
+        assert_eq!(inventory.push(sword), None);
+        assert_eq!(inventory.get(0), Some(sword));
+        assert_eq!(inventory.swap(0, axe), Some(sword));
+        assert_eq!(inventory.swap(100, sword), Some(sword));
+        assert_eq!(inventory.remove(0), Some(axe));
+        assert_eq!(inventory.remove(0), None);
+
+        for _ in 0..24 {
+            assert_eq!(inventory.push(sword), None);
+        }
+        assert!(inventory.is_full());
+        assert_eq!(inventory.push(axe), Some(axe));
+    }
+
+    #[test]
+    fn stacking() {
+        let pelts = |amount| Item::Ingredient {
+            kind: Ingredient::Pelt,
+            amount,
+        };
+        let mut inventory = Inventory::new();
+        for cell in 1..24 {
+            inventory.swap(cell, Item::starter_weapon(actor::Weapon::Bow));
+        }
+
+        assert_eq!(inventory.push(pelts(60)), None);
+        assert_eq!(inventory.push(pelts(30)), None);
+        assert_eq!(inventory.get(0), Some(pelts(90)));
+        // Only 9 more fit into the only stack, and there's no room for another one
+        assert_eq!(inventory.push(pelts(20)), Some(pelts(11)));
+        assert_eq!(inventory.get(0), Some(pelts(MAX_STACK)));
+    }
+}
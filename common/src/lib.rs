@@ -11,14 +11,18 @@ extern crate serde_derive;
 #[macro_use]
 extern crate log;
 
+pub mod ai;
 pub mod assets;
 pub mod clock;
 pub mod comp;
 pub mod figure;
 pub mod inventory;
+pub mod loot;
 pub mod msg;
 pub mod npc;
+pub mod pathfinding;
 pub mod ray;
+pub mod replay;
 pub mod state;
 pub mod sys;
 pub mod terrain;
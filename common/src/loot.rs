@@ -0,0 +1,109 @@
+//! What NPCs leave behind when they die.
+//!
+//! Every `NpcKind` has a loot table in `common/loot_tables.json`: a list of items, each with the
+//! chance that it drops and how many of it drop.
+
+use crate::{assets, inventory::item::Item, npc::NpcKind};
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde_json;
+use std::collections::HashMap;
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LootEntry {
+    pub item: Item,
+    /// The chance (from 0 to 1) that the item drops.
+    pub chance: f32,
+    /// How many items of a stackable kind drop, at least...
+    #[serde(default = "one")]
+    pub min: u32,
+    /// ...and at most.
+    #[serde(default = "one")]
+    pub max: u32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LootTable(pub Vec<LootEntry>);
+
+impl LootTable {
+    /// Decide what drops.
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> Vec<Item> {
+        self.0
+            .iter()
+            .filter(|entry| rng.gen::<f32>() < entry.chance)
+            .map(|entry| match entry.item {
+                Item::Ingredient { kind, .. } => Item::Ingredient {
+                    kind,
+                    amount: rng.gen_range(entry.min, entry.max.max(entry.min) + 1),
+                },
+                item => item,
+            })
+            .collect()
+    }
+}
+
+lazy_static! {
+    static ref LOOT_TABLES: HashMap<String, LootTable> = serde_json::from_value(
+        (*assets::load_expect::<serde_json::Value>("common/loot_tables.json")).clone()
+    )
+    .expect("parsing loot tables");
+    static ref NO_LOOT: LootTable = LootTable::default();
+}
+
+/// The loot table of the given kind of NPC, which is empty if it has none.
+pub fn loot_table(kind: NpcKind) -> &'static LootTable {
+    LOOT_TABLES.get(kind.as_str()).unwrap_or(&NO_LOOT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::Ingredient;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn loot_tables_load() {
+        for kind in &[NpcKind::Humanoid, NpcKind::Wolf, NpcKind::Pig] {
+            assert!(!loot_table(*kind).0.is_empty(), "no loot for {:?}", kind);
+        }
+    }
+
+    #[test]
+    fn rolls_respect_chances_and_amounts() {
+        let table = LootTable(vec![
+            LootEntry {
+                item: Item::Ingredient {
+                    kind: Ingredient::Meat,
+                    amount: 1,
+                },
+                chance: 1.0,
+                min: 2,
+                max: 4,
+            },
+            LootEntry {
+                item: Item::Ingredient {
+                    kind: Ingredient::Bone,
+                    amount: 1,
+                },
+                chance: 0.0,
+                min: 1,
+                max: 1,
+            },
+        ]);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            match table.roll(&mut rng).as_slice() {
+                [Item::Ingredient {
+                    kind: Ingredient::Meat,
+                    amount,
+                }] => assert!(*amount >= 2 && *amount <= 4),
+                loot => panic!("unexpected loot {:?}", loot),
+            }
+        }
+    }
+}
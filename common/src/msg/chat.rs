@@ -0,0 +1,88 @@
+use std::fmt;
+
+/// The channel a chat message was sent through.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ChatKind {
+    /// Spoken out loud, heard by nearby players.
+    Say,
+    /// A private message to the player with the given alias.
+    Whisper { to: String },
+    /// A message to the members of the sender's group.
+    Group,
+    /// Server notifications, like players logging in.
+    System,
+    /// Somebody died.
+    Death,
+    /// The response to a chat command.
+    Command,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatMsg {
+    pub kind: ChatKind,
+    /// The alias of the player that sent the message, if it came from a player.
+    pub sender: Option<String>,
+    pub text: String,
+}
+
+impl ChatMsg {
+    pub fn say(sender: Option<String>, text: String) -> Self {
+        Self {
+            kind: ChatKind::Say,
+            sender,
+            text,
+        }
+    }
+
+    pub fn whisper(sender: String, to: String, text: String) -> Self {
+        Self {
+            kind: ChatKind::Whisper { to },
+            sender: Some(sender),
+            text,
+        }
+    }
+
+    pub fn group(sender: String, text: String) -> Self {
+        Self {
+            kind: ChatKind::Group,
+            sender: Some(sender),
+            text,
+        }
+    }
+
+    pub fn system(text: String) -> Self {
+        Self {
+            kind: ChatKind::System,
+            sender: None,
+            text,
+        }
+    }
+
+    pub fn death(text: String) -> Self {
+        Self {
+            kind: ChatKind::Death,
+            sender: None,
+            text,
+        }
+    }
+
+    pub fn command(text: String) -> Self {
+        Self {
+            kind: ChatKind::Command,
+            sender: None,
+            text,
+        }
+    }
+}
+
+impl fmt::Display for ChatMsg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sender = self.sender.as_ref().map(|s| s.as_str()).unwrap_or("<anon>");
+        match &self.kind {
+            ChatKind::Say => write!(f, "[{}] {}", sender, self.text),
+            ChatKind::Whisper { to } => write!(f, "[{} -> {}] {}", sender, to, self.text),
+            ChatKind::Group => write!(f, "[Group] [{}] {}", sender, self.text),
+            ChatKind::System | ChatKind::Death | ChatKind::Command => write!(f, "{}", self.text),
+        }
+    }
+}
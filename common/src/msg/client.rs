@@ -1,9 +1,21 @@
 use super::ClientState;
-use crate::comp;
+use crate::{
+    comp::{self, agent::PetCommand},
+    inventory::item::EquipSlot,
+};
 use vek::*;
 
+/// Messages sent from the client to the server.
+///
+/// Every variant has a stable numeric ID (see `ClientMsg::id`) that matches its position in this
+/// enum, which is also the tag bincode uses on the wire. Never reorder or remove variants: new
+/// messages go at the end and require bumping `PROTOCOL_VERSION`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClientMsg {
+    // VersionInfo MUST always stay first in this enum.
+    VersionInfo {
+        protocol_version: u32,
+    },
     Register {
         player: comp::Player,
     },
@@ -11,6 +23,9 @@ pub enum ClientMsg {
         name: String,
         body: comp::Body,
     },
+    Resume {
+        session_token: u64,
+    },
     Attack,
     Respawn,
     RequestState(ClientState),
@@ -28,4 +43,55 @@ pub enum ClientMsg {
         key: Vec2<i32>,
     },
     Disconnect,
+    /// Equip the item in the given inventory slot, swapping it with what's in its equipment slot.
+    Equip {
+        cell: usize,
+    },
+    /// Put the item in the given equipment slot back into the inventory.
+    Unequip(EquipSlot),
+    /// Pick up the item entity with the given `Uid`.
+    Pickup(u64),
+    /// Invite the player with the given `Uid` into the group.
+    GroupInvite(u64),
+    /// Join the group of whoever sent the last invitation.
+    GroupAccept,
+    GroupLeave,
+    /// Remove the player with the given `Uid` from the group, which only its leader can do.
+    GroupKick(u64),
+    /// Tame the wild animal with the given `Uid`.
+    Tame(u64),
+    /// Give an order to all pets of the player.
+    PetCommand(PetCommand),
+}
+
+impl ClientMsg {
+    /// Get the stable numeric ID of this message.
+    pub fn id(&self) -> u32 {
+        match self {
+            ClientMsg::VersionInfo { .. } => 0,
+            ClientMsg::Register { .. } => 1,
+            ClientMsg::Character { .. } => 2,
+            ClientMsg::Resume { .. } => 3,
+            ClientMsg::Attack => 4,
+            ClientMsg::Respawn => 5,
+            ClientMsg::RequestState(_) => 6,
+            ClientMsg::SetViewDistance(_) => 7,
+            ClientMsg::Ping => 8,
+            ClientMsg::Pong => 9,
+            ClientMsg::Chat(_) => 10,
+            ClientMsg::PlayerAnimation(_) => 11,
+            ClientMsg::PlayerPhysics { .. } => 12,
+            ClientMsg::TerrainChunkRequest { .. } => 13,
+            ClientMsg::Disconnect => 14,
+            ClientMsg::Equip { .. } => 15,
+            ClientMsg::Unequip(_) => 16,
+            ClientMsg::Pickup(_) => 17,
+            ClientMsg::GroupInvite(_) => 18,
+            ClientMsg::GroupAccept => 19,
+            ClientMsg::GroupLeave => 20,
+            ClientMsg::GroupKick(_) => 21,
+            ClientMsg::Tame(_) => 22,
+            ClientMsg::PetCommand(_) => 23,
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{comp, state};
+use crate::{comp, inventory, state};
 use serde_derive::{Deserialize, Serialize};
 use std::marker::PhantomData;
 
@@ -9,6 +9,7 @@ sphynx::sum_type! {
     pub enum EcsResPacket {
         Time(state::Time),
         TimeOfDay(state::TimeOfDay),
+        PvpRules(comp::pvp::PvpRules),
     }
 }
 impl sphynx::ResPacket for EcsResPacket {}
@@ -24,6 +25,13 @@ sphynx::sum_type! {
         Player(comp::Player),
         Stats(comp::Stats),
         Attacking(comp::Attacking),
+        Projectile(comp::Projectile),
+        Equipment(comp::Equipment),
+        Item(inventory::item::Item),
+        Effects(comp::Effects),
+        LifeState(comp::LifeState),
+        Group(comp::Group),
+        PvpFlag(comp::PvpFlag),
     }
 }
 // Automatically derive From<T> for EcsCompPhantom
@@ -38,6 +46,13 @@ sphynx::sum_type! {
         Player(PhantomData<comp::Player>),
         Stats(PhantomData<comp::Stats>),
         Attacking(PhantomData<comp::Attacking>),
+        Projectile(PhantomData<comp::Projectile>),
+        Equipment(PhantomData<comp::Equipment>),
+        Item(PhantomData<inventory::item::Item>),
+        Effects(PhantomData<comp::Effects>),
+        LifeState(PhantomData<comp::LifeState>),
+        Group(PhantomData<comp::Group>),
+        PvpFlag(PhantomData<comp::PvpFlag>),
     }
 }
 impl sphynx::CompPacket for EcsCompPacket {
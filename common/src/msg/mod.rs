@@ -1,8 +1,10 @@
+pub mod chat;
 pub mod client;
 pub mod ecs_packet;
 pub mod server;
 
 // Reexports
+pub use self::chat::{ChatKind, ChatMsg};
 pub use self::client::ClientMsg;
 pub use self::ecs_packet::{EcsCompPacket, EcsResPacket};
 pub use self::server::{RequestStateError, ServerInfo, ServerMsg};
@@ -16,3 +18,295 @@ pub enum ClientState {
     Dead,
     Character,
 }
+
+/// The version of the network protocol. Clients and servers only talk to each other if their
+/// versions match exactly.
+///
+/// Bump this whenever the serialized layout of a message changes. The `layout` test below checks
+/// the current layout against the committed `layout.snapshot`. After bumping this, record the new
+/// layout with `UPDATE_LAYOUT=1 cargo test -p veloren-common layout`.
+pub const PROTOCOL_VERSION: u32 = 14;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        comp,
+        inventory::{
+            item::{EquipSlot, Ingredient, Item},
+            Inventory,
+        },
+        state::State,
+        terrain::{Block, TerrainChunk, TerrainChunkMeta},
+        vol::Vox,
+    };
+    use serde::Serialize;
+    use specs::Builder;
+    use std::{env, fs, path::Path};
+    use vek::*;
+
+    const LAYOUT_SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/msg/layout.snapshot");
+
+    fn body() -> comp::Body {
+        comp::Body::Humanoid(comp::HumanoidBody {
+            race: comp::actor::Race::Human,
+            body_type: comp::actor::BodyType::Female,
+            head: comp::actor::Head::Default,
+            chest: comp::actor::Chest::Blue,
+            belt: comp::actor::Belt::Dark,
+            pants: comp::actor::Pants::Green,
+            hand: comp::actor::Hand::Default,
+            foot: comp::actor::Foot::Dark,
+            weapon: comp::actor::Weapon::Sword,
+            shoulder: comp::actor::Shoulder::Default,
+            draw: comp::actor::Draw::Default,
+        })
+    }
+
+    fn physics() -> (comp::phys::Pos, comp::phys::Vel, comp::phys::Ori) {
+        (
+            comp::phys::Pos(Vec3::new(1.0, 2.0, 3.0)),
+            comp::phys::Vel(Vec3::new(4.0, 5.0, 6.0)),
+            comp::phys::Ori(Vec3::unit_y()),
+        )
+    }
+
+    /// One sample of every client message, in ID order.
+    fn client_msgs() -> Vec<ClientMsg> {
+        let (pos, vel, ori) = physics();
+        vec![
+            ClientMsg::VersionInfo {
+                protocol_version: PROTOCOL_VERSION,
+            },
+            ClientMsg::Register {
+                player: comp::Player::new("test".to_owned(), Some(5)),
+            },
+            ClientMsg::Character {
+                name: "test".to_owned(),
+                body: body(),
+            },
+            ClientMsg::Resume {
+                session_token: 1337,
+            },
+            ClientMsg::Attack,
+            ClientMsg::Respawn,
+            ClientMsg::RequestState(ClientState::Spectator),
+            ClientMsg::SetViewDistance(5),
+            ClientMsg::Ping,
+            ClientMsg::Pong,
+            ClientMsg::Chat("Hello!".to_owned()),
+            ClientMsg::PlayerAnimation(comp::AnimationInfo::default()),
+            ClientMsg::PlayerPhysics { pos, vel, ori },
+            ClientMsg::TerrainChunkRequest {
+                key: Vec2::new(1, 2),
+            },
+            ClientMsg::Disconnect,
+            ClientMsg::Equip { cell: 3 },
+            ClientMsg::Unequip(EquipSlot::Chest),
+            ClientMsg::Pickup(42),
+            ClientMsg::GroupInvite(42),
+            ClientMsg::GroupAccept,
+            ClientMsg::GroupLeave,
+            ClientMsg::GroupKick(42),
+            ClientMsg::Tame(42),
+            ClientMsg::PetCommand(comp::agent::PetCommand::Attack(42)),
+        ]
+    }
+
+    /// One sample of every server message, in ID order.
+    fn server_msgs() -> Vec<ServerMsg> {
+        let mut state = State::new();
+        let (pos, vel, ori) = physics();
+        vec![
+            ServerMsg::VersionInfo {
+                protocol_version: PROTOCOL_VERSION,
+            },
+            ServerMsg::InitialSync {
+                ecs_state: state.ecs().gen_state_package(),
+                entity_uid: 42,
+                server_info: ServerInfo {
+                    name: "name".to_owned(),
+                    description: "description".to_owned(),
+                },
+                session_token: 1337,
+            },
+            ServerMsg::StateAnswer(Err((RequestStateError::Denied, ClientState::Registered))),
+            ServerMsg::ForceState(ClientState::Dead),
+            ServerMsg::Ping,
+            ServerMsg::Pong,
+            ServerMsg::Chat(ChatMsg::whisper(
+                "sender".to_owned(),
+                "receiver".to_owned(),
+                "Hello!".to_owned(),
+            )),
+            ServerMsg::SetPlayerEntity(42),
+            ServerMsg::EcsSync(state.ecs_mut().next_sync_package()),
+            ServerMsg::EntityPhysics {
+                entity: 42,
+                pos,
+                vel,
+                ori,
+            },
+            ServerMsg::EntityAnimation {
+                entity: 42,
+                animation_info: comp::AnimationInfo::default(),
+            },
+            ServerMsg::TerrainChunkUpdate {
+                key: Vec2::new(1, 2),
+                chunk: Box::new(TerrainChunk::new(
+                    0,
+                    Block::new(1, Rgb::new(1, 2, 3)),
+                    Block::empty(),
+                    TerrainChunkMeta::void(),
+                )),
+            },
+            ServerMsg::Disconnect,
+            ServerMsg::Shutdown,
+            ServerMsg::InventoryUpdate(Inventory::new()),
+        ]
+    }
+
+    /// Samples of every synced component, whose layout is part of the protocol too.
+    fn comp_packets() -> Vec<(&'static str, EcsCompPacket)> {
+        let (pos, vel, ori) = physics();
+        // Only synced worlds hand out `Uid`s
+        let mut state = State::new();
+        let owner = state.ecs_mut().create_entity_synced().build();
+        let owner = state.ecs().uid_from_entity(owner).unwrap();
+        vec![
+            ("Pos", pos.into()),
+            ("Vel", vel.into()),
+            ("Ori", ori.into()),
+            (
+                "Actor",
+                comp::Actor::Character {
+                    name: "test".to_owned(),
+                    body: body(),
+                }
+                .into(),
+            ),
+            ("Player", comp::Player::new("test".to_owned(), None).into()),
+            ("Stats", comp::Stats::default().into()),
+            ("Attacking", comp::Attacking::start().into()),
+            (
+                "Projectile",
+                comp::Projectile::new(
+                    comp::projectile::ProjectileKind::Arrow,
+                    owner,
+                    true,
+                    10,
+                    100,
+                )
+                .into(),
+            ),
+            ("Equipment", comp::Equipment::for_body(&body()).into()),
+            (
+                "Item",
+                Item::Ingredient {
+                    kind: Ingredient::Pelt,
+                    amount: 3,
+                }
+                .into(),
+            ),
+            (
+                "Effects",
+                comp::Effects(vec![
+                    comp::Effect::new(comp::EffectKind::Poison, 2, 5.0).inflicted_by(owner),
+                    comp::Effect::new(comp::EffectKind::Slow, 40, 2.0),
+                ])
+                .into(),
+            ),
+            ("LifeState", comp::LifeState::Dead.into()),
+            (
+                "Group",
+                comp::Group {
+                    id: 1,
+                    leader: owner.into(),
+                }
+                .into(),
+            ),
+            ("PvpFlag", comp::PvpFlag.into()),
+        ]
+    }
+
+    fn layout_line<T: Serialize>(name: &str, msg: &T) -> String {
+        let bytes = bincode::serialize(msg).unwrap();
+        format!("{} {:016x}\n", name, fxhash::hash64(&bytes))
+    }
+
+    fn current_layout() -> String {
+        let mut layout = format!("version {}\n", PROTOCOL_VERSION);
+        for msg in client_msgs() {
+            layout += &layout_line(&format!("ClientMsg::{}", msg.id()), &msg);
+        }
+        for msg in server_msgs() {
+            layout += &layout_line(&format!("ServerMsg::{}", msg.id()), &msg);
+        }
+        for (name, packet) in comp_packets() {
+            layout += &layout_line(&format!("EcsCompPacket::{}", name), &packet);
+        }
+        layout
+    }
+
+    #[test]
+    fn ids_match_wire_tags() {
+        // Bincode writes the index of an enum variant as a little-endian `u32` in front of it.
+        let tag = |bytes: Vec<u8>| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        for (i, msg) in client_msgs().into_iter().enumerate() {
+            assert_eq!(msg.id(), i as u32, "Missing sample for a client message");
+            assert_eq!(
+                msg.id(),
+                tag(bincode::serialize(&msg).unwrap()),
+                "{:?}",
+                msg
+            );
+        }
+        for (i, msg) in server_msgs().into_iter().enumerate() {
+            assert_eq!(msg.id(), i as u32, "Missing sample for a server message");
+            assert_eq!(
+                msg.id(),
+                tag(bincode::serialize(&msg).unwrap()),
+                "{:?}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let path = Path::new(LAYOUT_SNAPSHOT);
+        let layout = current_layout();
+
+        // Recording a new layout is a deliberate step, so that changes can't slip in unnoticed.
+        if env::var_os("UPDATE_LAYOUT").is_some() {
+            fs::write(path, layout).unwrap();
+            return;
+        }
+
+        let snapshot = fs::read_to_string(path)
+            .expect("Missing layout snapshot, record it with `UPDATE_LAYOUT=1 cargo test`");
+        let snapshot_version = snapshot
+            .lines()
+            .next()
+            .and_then(|line| line.trim_start_matches("version ").parse::<u32>().ok())
+            .expect("Malformed layout snapshot");
+
+        assert!(
+            snapshot_version <= PROTOCOL_VERSION,
+            "`PROTOCOL_VERSION` went backwards"
+        );
+        if snapshot_version == PROTOCOL_VERSION {
+            assert_eq!(
+                snapshot, layout,
+                "The serialized layout of the protocol changed, bump `PROTOCOL_VERSION` and \
+                 record the new layout with `UPDATE_LAYOUT=1 cargo test`!"
+            );
+        } else {
+            panic!(
+                "`PROTOCOL_VERSION` was bumped, record the new layout with \
+                 `UPDATE_LAYOUT=1 cargo test`!"
+            );
+        }
+    }
+}
@@ -1,5 +1,5 @@
-use super::{ClientState, EcsCompPacket, EcsResPacket};
-use crate::{comp, terrain::TerrainChunk};
+use super::{ChatMsg, ClientState, EcsCompPacket, EcsResPacket};
+use crate::{comp, inventory::Inventory, terrain::TerrainChunk};
 use vek::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,18 +16,28 @@ pub struct ServerInfo {
     pub description: String,
 }
 
+/// Messages sent from the server to the client.
+///
+/// Every variant has a stable numeric ID (see `ServerMsg::id`) that matches its position in this
+/// enum, which is also the tag bincode uses on the wire. Never reorder or remove variants: new
+/// messages go at the end and require bumping `PROTOCOL_VERSION`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerMsg {
+    // VersionInfo MUST always stay first in this enum.
+    VersionInfo {
+        protocol_version: u32,
+    },
     InitialSync {
         ecs_state: sphynx::StatePackage<EcsCompPacket, EcsResPacket>,
         entity_uid: u64,
         server_info: ServerInfo,
+        session_token: u64,
     },
     StateAnswer(Result<ClientState, (RequestStateError, ClientState)>),
     ForceState(ClientState),
     Ping,
     Pong,
-    Chat(String),
+    Chat(ChatMsg),
     SetPlayerEntity(u64),
     EcsSync(sphynx::SyncPackage<EcsCompPacket, EcsResPacket>),
     EntityPhysics {
@@ -46,4 +56,29 @@ pub enum ServerMsg {
     },
     Disconnect,
     Shutdown,
+    /// The contents of the inventory of the client's character.
+    InventoryUpdate(Inventory),
+}
+
+impl ServerMsg {
+    /// Get the stable numeric ID of this message.
+    pub fn id(&self) -> u32 {
+        match self {
+            ServerMsg::VersionInfo { .. } => 0,
+            ServerMsg::InitialSync { .. } => 1,
+            ServerMsg::StateAnswer(_) => 2,
+            ServerMsg::ForceState(_) => 3,
+            ServerMsg::Ping => 4,
+            ServerMsg::Pong => 5,
+            ServerMsg::Chat(_) => 6,
+            ServerMsg::SetPlayerEntity(_) => 7,
+            ServerMsg::EcsSync(_) => 8,
+            ServerMsg::EntityPhysics { .. } => 9,
+            ServerMsg::EntityAnimation { .. } => 10,
+            ServerMsg::TerrainChunkUpdate { .. } => 11,
+            ServerMsg::Disconnect => 12,
+            ServerMsg::Shutdown => 13,
+            ServerMsg::InventoryUpdate(_) => 14,
+        }
+    }
 }
@@ -0,0 +1,115 @@
+use super::post2::Error;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+/// Whether a captured message was sent or received by the side that recorded it.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A single message in a capture file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    /// Seconds since the recording started.
+    pub time: f64,
+    pub direction: Direction,
+    /// The bincode-serialized message.
+    pub msg: Vec<u8>,
+}
+
+impl CaptureEntry {
+    /// Deserialize the captured message.
+    pub fn msg<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(bincode::deserialize(&self.msg)?)
+    }
+}
+
+/// Writes every message passing through a `PostBox` to a capture file.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Start a new capture at the given path, replacing any existing file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record<M: Serialize>(&mut self, direction: Direction, msg: &M) -> Result<(), Error> {
+        let entry = CaptureEntry {
+            time: self.start.elapsed().as_secs_f64(),
+            direction,
+            msg: bincode::serialize(msg)?,
+        };
+        bincode::serialize_into(&mut self.writer, &entry)?;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// Read all entries of a capture file.
+pub fn read_capture<P: AsRef<Path>>(path: P) -> Result<Vec<CaptureEntry>, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => {
+                // The end of the file has been reached.
+                if let bincode::ErrorKind::Io(io_err) = &*err {
+                    if io_err.kind() == io::ErrorKind::UnexpectedEof {
+                        break;
+                    }
+                }
+                return Err(err.into());
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_read() {
+        let path = std::env::temp_dir().join("veloren-capture-test.vcap");
+
+        {
+            let mut recorder = Recorder::create(&path).unwrap();
+            recorder.record(Direction::Sent, &1337u32).unwrap();
+            recorder
+                .record(Direction::Received, &String::from("Hello!"))
+                .unwrap();
+        }
+
+        let entries = read_capture(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].direction, Direction::Sent);
+        assert_eq!(entries[0].msg::<u32>().unwrap(), 1337);
+        assert_eq!(entries[1].direction, Direction::Received);
+        assert_eq!(entries[1].msg::<String>().unwrap(), "Hello!");
+        assert!(entries[0].time <= entries[1].time);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod capture;
 pub mod data;
 //pub mod post;
 pub mod post2;
@@ -1,3 +1,4 @@
+use super::capture::{Direction, Recorder};
 use log::warn;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
@@ -6,6 +7,7 @@ use std::{
     io::{self, Read, Write},
     marker::PhantomData,
     net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc, Arc,
@@ -47,6 +49,8 @@ const MAX_MSG_SIZE: usize = 1 << 20;
 pub struct PostOffice<S: PostMsg, R: PostMsg> {
     listener: TcpListener,
     error: Option<Error>,
+    capture_dir: Option<PathBuf>,
+    captures: u64,
     phantom: PhantomData<(S, R)>,
 }
 
@@ -58,10 +62,20 @@ impl<S: PostMsg, R: PostMsg> PostOffice<S, R> {
         Ok(Self {
             listener,
             error: None,
+            capture_dir: None,
+            captures: 0,
             phantom: PhantomData,
         })
     }
 
+    /// Record the traffic of every new connection into its own capture file in the given
+    /// directory.
+    pub fn record_to<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Error> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        self.capture_dir = Some(dir.as_ref().to_owned());
+        Ok(())
+    }
+
     pub fn error(&self) -> Option<Error> {
         self.error.clone()
     }
@@ -75,7 +89,17 @@ impl<S: PostMsg, R: PostMsg> PostOffice<S, R> {
 
         loop {
             match self.listener.accept() {
-                Ok((stream, _sock)) => new.push(PostBox::from_stream(stream).unwrap()),
+                Ok((stream, _sock)) => {
+                    let mut postbox = PostBox::from_stream(stream).unwrap();
+                    if let Some(dir) = &self.capture_dir {
+                        let path = dir.join(format!("connection-{}.vcap", self.captures));
+                        self.captures += 1;
+                        if let Err(err) = postbox.record_to(&path) {
+                            warn!("Failed to start capture at {:?}: {:?}", path, err);
+                        }
+                    }
+                    new.push(postbox)
+                }
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
                 Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
                 Err(e) => {
@@ -95,6 +119,7 @@ pub struct PostBox<S: PostMsg, R: PostMsg> {
     worker: Option<thread::JoinHandle<()>>,
     running: Arc<AtomicBool>,
     error: Option<Error>,
+    recorder: Option<Recorder>,
 }
 
 impl<S: PostMsg, R: PostMsg> PostBox<S, R> {
@@ -119,14 +144,31 @@ impl<S: PostMsg, R: PostMsg> PostBox<S, R> {
             worker: Some(worker),
             running,
             error: None,
+            recorder: None,
         })
     }
 
+    /// Record every message sent and received from now on into a capture file.
+    pub fn record_to<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.recorder = Some(Recorder::create(path)?);
+        Ok(())
+    }
+
+    fn record(&mut self, direction: Direction, msg: &impl Serialize) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.record(direction, msg) {
+                warn!("Failed to record message, stopping capture: {:?}", err);
+                self.recorder = None;
+            }
+        }
+    }
+
     pub fn error(&self) -> Option<Error> {
         self.error.clone()
     }
 
     pub fn send_message(&mut self, msg: S) {
+        self.record(Direction::Sent, &msg);
         let _ = self.send_tx.send(msg);
     }
 
@@ -136,7 +178,10 @@ impl<S: PostMsg, R: PostMsg> PostBox<S, R> {
         }
 
         match self.recv_rx.recv().ok()? {
-            Ok(msg) => Some(msg),
+            Ok(msg) => {
+                self.record(Direction::Received, &msg);
+                Some(msg)
+            }
             Err(e) => {
                 self.error = Some(e);
                 None
@@ -153,7 +198,10 @@ impl<S: PostMsg, R: PostMsg> PostBox<S, R> {
 
         loop {
             match self.recv_rx.try_recv() {
-                Ok(Ok(msg)) => new.push(msg),
+                Ok(Ok(msg)) => {
+                    self.record(Direction::Received, &msg);
+                    new.push(msg)
+                }
                 Err(mpsc::TryRecvError::Empty) => break,
                 Err(e) => {
                     self.error = Some(e.into());
@@ -1,9 +1,10 @@
-use crate::assets;
+use crate::{assets, comp::Body};
 use lazy_static::lazy_static;
 use rand::seq::SliceRandom;
 use serde_json;
 use std::sync::Arc;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum NpcKind {
     Humanoid,
     Wolf,
@@ -11,7 +12,16 @@ pub enum NpcKind {
 }
 
 impl NpcKind {
-    fn as_str(&self) -> &'static str {
+    /// The kind of NPC a body belongs to.
+    pub fn from_body(body: &Body) -> Self {
+        match body {
+            Body::Humanoid(_) => NpcKind::Humanoid,
+            Body::Quadruped(_) => NpcKind::Pig,
+            Body::QuadrupedMedium(_) => NpcKind::Wolf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
         match *self {
             NpcKind::Humanoid => "humanoid",
             NpcKind::Wolf => "wolf",
@@ -0,0 +1,384 @@
+//! A* pathfinding over the terrain for entities that walk, jump and drop down ledges.
+//!
+//! Searches run on the rayon thread pool. To keep the simulation deterministic, the result of a
+//! search only becomes available a fixed number of steps after it was requested, waiting for the
+//! search to finish at that point if needs be.
+
+use crate::{
+    terrain::{Block, TerrainMap},
+    vol::ReadVol,
+};
+use fxhash::FxHashMap;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{mpsc, Arc, Mutex},
+};
+use vek::*;
+
+/// How many blocks high a walker can jump onto.
+pub const JUMP_HEIGHT: i32 = 2;
+/// How many blocks down a walker is willing to drop.
+pub const MAX_DROP: i32 = 6;
+// Searches give up after visiting this many nodes
+const MAX_ITERS: usize = 4096;
+// How far beyond the start and the goal a search may wander, in blocks
+const SEARCH_MARGIN: i32 = 24;
+// Steps between requesting a path and it being available
+const PATH_LATENCY: u64 = 6;
+// Steps a cached path is kept without being used
+const CACHE_STEPS: u64 = 600;
+
+/// The cells (the air blocks that a walker stands in) to walk through, in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    pub nodes: Vec<Vec3<i32>>,
+    /// Whether the path reaches the goal, rather than getting as close to it as possible.
+    pub complete: bool,
+}
+
+/// Whether a walker `height` blocks tall can stand in the given cell.
+fn can_stand<V: ReadVol<Vox = Block>>(vol: &V, pos: Vec3<i32>, height: i32) -> bool {
+    let solid = |pos: Vec3<i32>| vol.get(pos).map(|b| b.is_solid()).unwrap_or(true);
+    let fluid = |pos: Vec3<i32>| vol.get(pos).map(|b| b.is_fluid()).unwrap_or(false);
+
+    (solid(pos - Vec3::unit_z()) || fluid(pos))
+        && (0..height).all(|z| !solid(pos + Vec3::unit_z() * z))
+}
+
+/// Whether the column of `len` blocks starting at `pos` is free of solid blocks.
+fn is_clear<V: ReadVol<Vox = Block>>(vol: &V, pos: Vec3<i32>, len: i32) -> bool {
+    (0..len).all(|z| {
+        vol.get(pos + Vec3::unit_z() * z)
+            .map(|b| !b.is_solid())
+            .unwrap_or(false)
+    })
+}
+
+/// The cells reachable from `pos` in one move, and what it costs to move there.
+fn neighbours<V: ReadVol<Vox = Block>>(
+    vol: &V,
+    pos: Vec3<i32>,
+    height: i32,
+) -> Vec<(Vec3<i32>, f32)> {
+    const DIRS: [(i32, i32); 8] = [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ];
+
+    let mut neighbours = Vec::new();
+    for &(dx, dy) in DIRS.iter() {
+        let column = pos + Vec3::new(dx, dy, 0);
+        // Don't cut corners
+        if dx != 0
+            && dy != 0
+            && !(is_clear(vol, pos + Vec3::new(dx, 0, 0), height)
+                && is_clear(vol, pos + Vec3::new(0, dy, 0), height))
+        {
+            continue;
+        }
+
+        // The highest cell of the column that can be reached, jumping up or dropping down.
+        let reachable = (-MAX_DROP..=JUMP_HEIGHT).rev().find(|&dz| {
+            let target = column + Vec3::unit_z() * dz;
+            can_stand(vol, target, height)
+                && if dz > 0 {
+                    // Room to jump up
+                    is_clear(vol, pos, height + dz)
+                } else {
+                    // Room to walk off the edge and drop down
+                    is_clear(vol, target, height - dz)
+                }
+        });
+
+        if let Some(dz) = reachable {
+            let dist = ((dx * dx + dy * dy) as f32).sqrt();
+            // Jumping is slow and dropping hurts
+            let cost = dist + dz.max(0) as f32 + (-dz - 1).max(0) as f32 * 0.5;
+            neighbours.push((column + Vec3::unit_z() * dz, cost));
+        }
+    }
+    neighbours
+}
+
+#[derive(PartialEq)]
+struct Node {
+    pos: Vec3<i32>,
+    cost: f32,
+    estimate: f32,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    // Reversed so that `BinaryHeap` pops the most promising node first
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.cost + other.estimate)
+            .partial_cmp(&(self.cost + self.estimate))
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Find a path for a walker `height` blocks tall from the cell `from` to the cell `to`. If `to`
+/// can't be reached, the path leads to the closest cell that can.
+pub fn find_path<V: ReadVol<Vox = Block>>(
+    vol: &V,
+    from: Vec3<i32>,
+    to: Vec3<i32>,
+    height: i32,
+) -> Path {
+    let estimate = |pos: Vec3<i32>| pos.map(|e| e as f32).distance(to.map(|e| e as f32));
+    let bounds = Aabr {
+        min: Vec2::from(from.map2(to, |a, b| a.min(b))) - SEARCH_MARGIN,
+        max: Vec2::from(from.map2(to, |a, b| a.max(b))) + SEARCH_MARGIN,
+    };
+
+    let mut open = BinaryHeap::new();
+    let mut came_from = FxHashMap::<Vec3<i32>, Vec3<i32>>::default();
+    let mut costs = FxHashMap::default();
+    let mut closest = (from, estimate(from));
+
+    open.push(Node {
+        pos: from,
+        cost: 0.0,
+        estimate: closest.1,
+    });
+    costs.insert(from, 0.0);
+
+    let mut iters = 0;
+    while let Some(node) = open.pop() {
+        if node.pos == to || iters >= MAX_ITERS {
+            break;
+        }
+        iters += 1;
+
+        // Skip stale entries of nodes that were reached more cheaply since
+        if node.cost > costs.get(&node.pos).cloned().unwrap_or(std::f32::INFINITY) {
+            continue;
+        }
+
+        for (next, cost) in neighbours(vol, node.pos, height) {
+            if !bounds.contains_point(Vec2::from(next)) {
+                continue;
+            }
+
+            let cost = node.cost + cost;
+            if cost < costs.get(&next).cloned().unwrap_or(std::f32::INFINITY) {
+                costs.insert(next, cost);
+                came_from.insert(next, node.pos);
+
+                let estimate = estimate(next);
+                if estimate < closest.1 {
+                    closest = (next, estimate);
+                }
+                open.push(Node {
+                    pos: next,
+                    cost,
+                    estimate,
+                });
+            }
+        }
+    }
+
+    let mut nodes = vec![closest.0];
+    while let Some(prev) = came_from.get(nodes.last().unwrap()) {
+        nodes.push(*prev);
+    }
+    nodes.reverse();
+
+    Path {
+        nodes,
+        complete: closest.0 == to,
+    }
+}
+
+/// The state of a path requested from the `Pathfinder`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathResult {
+    Pending,
+    Found(Arc<Path>),
+}
+
+enum Search {
+    Pending {
+        ready_at: u64,
+        rx: Mutex<mpsc::Receiver<Path>>,
+    },
+    Done(Arc<Path>),
+}
+
+struct CachedSearch {
+    search: Search,
+    last_used: u64,
+}
+
+/// A resource that runs path searches in the background and caches their results.
+#[derive(Default)]
+pub struct Pathfinder {
+    step: u64,
+    cache: FxHashMap<(Vec3<i32>, Vec3<i32>, i32), CachedSearch>,
+}
+
+impl Pathfinder {
+    /// Advance to the next step, forgetting paths that haven't been used in a while.
+    pub fn maintain(&mut self) {
+        self.step += 1;
+        let step = self.step;
+        self.cache
+            .retain(|_, cached| step - cached.last_used < CACHE_STEPS);
+    }
+
+    /// Request a path for a walker `height` blocks tall from the cell `from` to the cell `to`.
+    /// Keep requesting the same path until it is found.
+    pub fn path(
+        &mut self,
+        terrain: &TerrainMap,
+        from: Vec3<i32>,
+        to: Vec3<i32>,
+        height: i32,
+    ) -> PathResult {
+        let step = self.step;
+        let cached = self
+            .cache
+            .entry((from, to, height))
+            .or_insert_with(|| CachedSearch {
+                search: Self::start(terrain, from, to, height, step),
+                last_used: step,
+            });
+        cached.last_used = step;
+
+        if let Search::Pending { ready_at, rx } = &cached.search {
+            if step < *ready_at {
+                return PathResult::Pending;
+            }
+            // A search that panicked just doesn't go anywhere
+            let path = rx
+                .lock()
+                .ok()
+                .and_then(|rx| rx.recv().ok())
+                .unwrap_or_else(|| Path {
+                    nodes: vec![from],
+                    complete: false,
+                });
+            cached.search = Search::Done(Arc::new(path));
+        }
+
+        match &cached.search {
+            Search::Done(path) => PathResult::Found(path.clone()),
+            Search::Pending { .. } => PathResult::Pending,
+        }
+    }
+
+    fn start(
+        terrain: &TerrainMap,
+        from: Vec3<i32>,
+        to: Vec3<i32>,
+        height: i32,
+        step: u64,
+    ) -> Search {
+        // Copy the chunks the search can reach, so it doesn't need to borrow the terrain.
+        let min = TerrainMap::chunk_key(from.map2(to, |a, b| a.min(b)) - SEARCH_MARGIN);
+        let max = TerrainMap::chunk_key(from.map2(to, |a, b| a.max(b)) + SEARCH_MARGIN);
+        let mut local = TerrainMap::new().unwrap();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let key = Vec2::new(x, y);
+                if let Some(chunk) = terrain.get_key_arc(key) {
+                    local.insert(key, chunk.clone());
+                }
+            }
+        }
+
+        let (tx, rx) = mpsc::channel();
+        rayon::spawn(move || {
+            let _ = tx.send(find_path(&local, from, to, height));
+        });
+
+        Search::Pending {
+            ready_at: step + PATH_LATENCY,
+            rx: Mutex::new(rx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        vol::{Vox, WriteVol},
+        volumes::dyna::Dyna,
+    };
+
+    /// A 16x16x16 volume with a solid floor at z = 0 and the given voxels filled in.
+    fn terrain(solid: &[Vec3<i32>]) -> Dyna<Block, ()> {
+        let mut terrain = Dyna::filled(Vec3::broadcast(16), Block::empty(), ());
+        let stone = Block::new(1, Rgb::broadcast(128));
+        for x in 0..16 {
+            for y in 0..16 {
+                terrain.set(Vec3::new(x, y, 0), stone).unwrap();
+            }
+        }
+        for pos in solid {
+            terrain.set(*pos, stone).unwrap();
+        }
+        terrain
+    }
+
+    /// A wall across the y axis at the given x coordinate, with a gap at y = 12.
+    fn wall(x: i32, height: i32) -> Vec<Vec3<i32>> {
+        (0..16)
+            .filter(|&y| y != 12)
+            .flat_map(|y| (1..=height).map(move |z| Vec3::new(x, y, z)))
+            .collect()
+    }
+
+    #[test]
+    fn jumps_over_low_walls() {
+        let terrain = terrain(&wall(8, 2));
+        let path = find_path(&terrain, Vec3::new(2, 2, 1), Vec3::new(14, 2, 1), 2);
+
+        assert!(path.complete);
+        assert!(path.nodes.contains(&Vec3::new(8, 2, 3)));
+        assert!(path.nodes.iter().all(|node| node.y < 6));
+    }
+
+    #[test]
+    fn walks_around_high_walls() {
+        let terrain = terrain(&wall(8, 4));
+        let path = find_path(&terrain, Vec3::new(2, 2, 1), Vec3::new(14, 2, 1), 2);
+
+        assert!(path.complete);
+        assert!(path.nodes.contains(&Vec3::new(8, 12, 1)));
+        assert_eq!(path.nodes.first(), Some(&Vec3::new(2, 2, 1)));
+        assert_eq!(path.nodes.last(), Some(&Vec3::new(14, 2, 1)));
+    }
+
+    #[test]
+    fn gets_close_to_unreachable_goals() {
+        // A pillar too high to jump onto
+        let pillar = (1..=5).map(|z| Vec3::new(8, 8, z)).collect::<Vec<_>>();
+        let terrain = terrain(&pillar);
+        let path = find_path(&terrain, Vec3::new(2, 2, 1), Vec3::new(8, 8, 6), 2);
+
+        assert!(!path.complete);
+        let last = path.nodes.last().unwrap();
+        assert!(
+            Vec2::from(*last - Vec3::new(8, 8, 6))
+                .map(|e: i32| e.abs())
+                .reduce_max()
+                <= 1
+        );
+    }
+}
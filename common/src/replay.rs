@@ -0,0 +1,355 @@
+//! Recording and replaying the inputs applied to a `State`, and a harness to run scripted
+//! scenarios on a `State` without a client, server or network in between.
+//!
+//! Because the simulation runs in fixed steps with a seeded random number generator, replaying a
+//! recording on a state that was set up the same way reproduces the recorded run exactly.
+
+use crate::{
+    comp::{self, phys},
+    state::State,
+    terrain::{Block, TerrainChunk, TerrainChunkMeta},
+    vol::Vox,
+};
+use log::warn;
+use specs::{Builder, Entity as EcsEntity, Join};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+use vek::*;
+
+/// An input applied to an entity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    Control(comp::Control),
+    Jump,
+    Attack,
+    Glide(bool),
+    /// A chat command, without the leading `/`.
+    Command(String),
+}
+
+/// An input applied to the entity with the given `Uid` before the given step.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    pub step: u64,
+    pub uid: u64,
+    pub input: Input,
+}
+
+/// The inputs applied during a run of the simulation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    /// The total number of steps of the run.
+    pub steps: u64,
+    pub inputs: Vec<RecordedInput>,
+}
+
+impl Recording {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            steps: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), bincode::Error> {
+        bincode::serialize_into(BufWriter::new(File::create(path)?), self)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, bincode::Error> {
+        bincode::deserialize_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// The state of an entity at the end of a run, used to compare runs with each other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntitySnapshot {
+    pub uid: u64,
+    pub pos: Vec3<f32>,
+    pub vel: Vec3<f32>,
+    pub hp: u32,
+}
+
+/// Handles the chat commands of a scenario, since those are implemented outside of `common`.
+pub type CommandHandler = Box<dyn FnMut(&mut State, EcsEntity, &str)>;
+
+/// A `State` that gets driven step by step, recording every input applied to it.
+pub struct Scenario {
+    state: State,
+    recording: Recording,
+    step: u64,
+    command_handler: Option<CommandHandler>,
+}
+
+impl Scenario {
+    /// Create a scenario with an empty world, seeded with the given seed.
+    pub fn new(seed: u64) -> Self {
+        let mut state = State::new();
+        state.set_seed(seed);
+
+        Self {
+            state,
+            recording: Recording::new(seed),
+            step: 0,
+            command_handler: None,
+        }
+    }
+
+    /// Fill the chunks within `radius` chunks of the origin with flat ground, with its surface at
+    /// z = 0.
+    pub fn with_flat_ground(mut self, radius: i32) -> Self {
+        let stone = Block::new(2, Rgb::broadcast(128));
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                self.state.insert_chunk(
+                    Vec2::new(x, y),
+                    TerrainChunk::new(0, stone, Block::empty(), TerrainChunkMeta::void()),
+                );
+            }
+        }
+        self
+    }
+
+    pub fn with_command_handler(
+        mut self,
+        handler: impl FnMut(&mut State, EcsEntity, &str) + 'static,
+    ) -> Self {
+        self.command_handler = Some(Box::new(handler));
+        self
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Create a character with the given body at `pos`.
+    pub fn spawn_character(&mut self, name: &str, body: comp::Body, pos: Vec3<f32>) -> EcsEntity {
+        self.state
+            .ecs_mut()
+            .create_entity_synced()
+            .with(phys::Pos(pos))
+            .with(phys::Vel(Vec3::zero()))
+            .with(phys::Ori(Vec3::unit_y()))
+            .with(comp::Control::default())
+            .with(comp::AnimationInfo::default())
+            .with(comp::Actor::Character {
+                name: name.to_owned(),
+                body,
+            })
+            .with(comp::Stats::default())
+            .build()
+    }
+
+    /// Apply an input to an entity and record it.
+    pub fn input(&mut self, entity: EcsEntity, input: Input) {
+        if let Some(uid) = self.state.ecs().uid_from_entity(entity) {
+            self.recording.inputs.push(RecordedInput {
+                step: self.step,
+                uid: uid.into(),
+                input: input.clone(),
+            });
+        }
+        self.apply(entity, input);
+    }
+
+    fn apply(&mut self, entity: EcsEntity, input: Input) {
+        match input {
+            Input::Control(control) => self.state.write_component(entity, control),
+            Input::Jump => self.state.write_component(entity, comp::Jumping),
+            Input::Attack => {
+                // Like the server, ignore attacks while the previous one is still going on.
+                if self
+                    .state
+                    .read_storage::<comp::Attacking>()
+                    .get(entity)
+                    .is_none()
+                {
+                    self.state.write_component(entity, comp::Attacking::start());
+                }
+            }
+            Input::Glide(true) => self.state.write_component(entity, comp::Gliding),
+            Input::Glide(false) => {
+                self.state
+                    .ecs_mut()
+                    .write_storage::<comp::Gliding>()
+                    .remove(entity);
+            }
+            Input::Command(command) => {
+                if let Some(handler) = &mut self.command_handler {
+                    handler(&mut self.state, entity, &command);
+                }
+            }
+        }
+    }
+
+    /// Run the simulation for the given number of steps.
+    pub fn run(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.state.step();
+            self.state.maintain_projectiles();
+            self.step += 1;
+        }
+        self.recording.steps = self.step;
+    }
+
+    /// Replay a recording on this scenario, which has to be set up the same way as the recorded
+    /// one was.
+    pub fn replay(&mut self, recording: &Recording) {
+        self.state.set_seed(recording.seed);
+        self.recording.seed = recording.seed;
+
+        let mut inputs = recording.inputs.iter().peekable();
+        while self.step < recording.steps {
+            while let Some(recorded) = inputs
+                .peek()
+                .filter(|recorded| recorded.step == self.step)
+                .cloned()
+            {
+                inputs.next();
+                match self.state.ecs().entity_from_uid(recorded.uid) {
+                    Some(entity) => self.input(entity, recorded.input.clone()),
+                    None => warn!("Replayed input for unknown entity {}", recorded.uid),
+                }
+            }
+            self.run(1);
+        }
+    }
+
+    /// The state of every entity with a position, ordered by `Uid`.
+    pub fn snapshot(&self) -> Vec<EntitySnapshot> {
+        let ecs = self.state.ecs();
+        let mut snapshot = (
+            &ecs.entities(),
+            &ecs.read_storage::<phys::Pos>(),
+            &ecs.read_storage::<phys::Vel>(),
+        )
+            .join()
+            .filter_map(|(entity, pos, vel)| {
+                Some(EntitySnapshot {
+                    uid: ecs.uid_from_entity(entity)?.into(),
+                    pos: pos.0,
+                    vel: vel.0,
+                    hp: ecs
+                        .read_storage::<comp::Stats>()
+                        .get(entity)
+                        .map(|stats| stats.hp.get_current())
+                        .unwrap_or(0),
+                })
+            })
+            .collect::<Vec<_>>();
+        snapshot.sort_by_key(|entity| entity.uid);
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The weapon decides how fights go, so tests can't pick bodies at random.
+    fn body() -> comp::Body {
+        comp::Body::Humanoid(comp::HumanoidBody {
+            race: comp::actor::Race::Human,
+            body_type: comp::actor::BodyType::Male,
+            head: comp::actor::Head::Default,
+            chest: comp::actor::Chest::Blue,
+            belt: comp::actor::Belt::Dark,
+            pants: comp::actor::Pants::Green,
+            hand: comp::actor::Hand::Default,
+            foot: comp::actor::Foot::Dark,
+            weapon: comp::actor::Weapon::Sword,
+            shoulder: comp::actor::Shoulder::Default,
+            draw: comp::actor::Draw::Default,
+        })
+    }
+
+    /// An enemy that starts 10 blocks away from a player, who stands still unless a test moves it.
+    fn chase(body: comp::Body) -> (Scenario, EcsEntity) {
+        let mut scenario = Scenario::new(42).with_flat_ground(1);
+        let player = scenario.spawn_character("player", body, Vec3::new(0.0, 0.0, 0.0));
+        let enemy = scenario.spawn_character("enemy", body, Vec3::new(10.0, 0.0, 0.0));
+        scenario
+            .state_mut()
+            .write_component(enemy, comp::Agent::enemy());
+        (scenario, player)
+    }
+
+    /// Run the scenario, counting the hits `entity` takes. Every change to its health resets the
+    /// time of its last change, which tells new changes apart from old ones.
+    fn count_hits(scenario: &mut Scenario, entity: EcsEntity, steps: u64) -> u32 {
+        let last_change = |scenario: &Scenario| {
+            scenario
+                .state()
+                .read_component_cloned::<comp::Stats>(entity)
+                .and_then(|stats| stats.hp.last_change)
+        };
+
+        let mut hits = 0;
+        let mut last = last_change(scenario);
+        for _ in 0..steps {
+            scenario.run(1);
+            let change = last_change(scenario);
+            if let Some((_, time, comp::HealthSource::Attack { .. })) = change {
+                if last.map_or(true, |(_, last_time, _)| time <= last_time) {
+                    hits += 1;
+                }
+            }
+            last = change;
+        }
+        hits
+    }
+
+    #[test]
+    fn enemy_chases_and_attacks_player() {
+        let (mut scenario, player) = chase(body());
+
+        // Twenty seconds
+        let hits = count_hits(&mut scenario, player, 1200);
+        assert!(hits >= 3, "The player was only hit {} times", hits);
+    }
+
+    #[test]
+    fn replay_reproduces_run() {
+        let body = body();
+
+        let (mut recorded, player) = chase(body);
+        recorded.run(30);
+        recorded.input(
+            player,
+            Input::Control(comp::Control {
+                move_dir: Vec2::new(-1.0, 0.0),
+            }),
+        );
+        recorded.run(30);
+        recorded.input(player, Input::Jump);
+        recorded.run(60);
+        recorded.input(player, Input::Control(comp::Control::default()));
+        recorded.input(player, Input::Attack);
+        recorded.run(120);
+
+        // Round trip through a file
+        let path = std::env::temp_dir().join("veloren-replay-test.vrec");
+        recorded.recording().save(&path).unwrap();
+        let recording = Recording::load(&path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(&recording, recorded.recording());
+
+        let (mut replayed, _) = chase(body);
+        replayed.replay(&recording);
+
+        assert_eq!(replayed.recording(), recorded.recording());
+        assert_eq!(replayed.snapshot(), recorded.snapshot());
+    }
+}
@@ -2,17 +2,25 @@
 pub use sphynx::Uid;
 
 use crate::{
-    comp, inventory,
+    comp::{
+        self,
+        projectile::FiredProjectiles,
+        pvp::{BlockedHit, BlockedHits, PvpRules},
+    },
+    inventory,
     msg::{EcsCompPacket, EcsResPacket},
+    pathfinding::Pathfinder,
     sys,
     terrain::{TerrainChunk, TerrainMap},
 };
+use log::warn;
+use rand::{rngs::StdRng, SeedableRng};
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde_derive::{Deserialize, Serialize};
 use specs::{
     shred::{Fetch, FetchMut},
     storage::{MaskedStorage as EcsMaskedStorage, Storage as EcsStorage},
-    Component, DispatcherBuilder, Entity as EcsEntity,
+    Builder, Component, Dispatcher, DispatcherBuilder, Entity as EcsEntity, Join, Resources,
 };
 use sphynx;
 use std::{collections::HashSet, sync::Arc, time::Duration};
@@ -34,11 +42,22 @@ pub struct Time(pub f64);
 #[derive(Default)]
 pub struct DeltaTime(pub f32);
 
-/// At what point should we stop speeding up physics to compensate for lag? If we speed physics up
-/// too fast, we'd skip important physics events like collisions. This constant determines the
-/// upper limit. If delta time exceeds this value, the game's physics will begin to produce time
-/// lag. Ideally, we'd avoid such a situation.
-const MAX_DELTA_TIME: f32 = 0.15;
+/// A resource holding the random number generator that systems must use, so that the simulation
+/// can be reproduced from its seed.
+pub struct SimRng(pub StdRng);
+
+/// The seed of the simulation's random number generator unless another one gets set.
+pub const DEFAULT_SEED: u64 = 0x5eed;
+
+/// The duration (in seconds) of a single simulation step. The simulation always advances in steps
+/// of this size, no matter how often `State::tick` gets called, so that it behaves the same at
+/// every frame rate.
+pub const STEP_TIME: f64 = 1.0 / 60.0;
+
+/// At what point should we stop speeding up physics to compensate for lag? Running many steps in
+/// a single tick makes a slow tick even slower, so beyond this many steps the game's physics will
+/// begin to produce time lag instead. Ideally, we'd avoid such a situation.
+const MAX_STEPS_PER_TICK: u32 = 10;
 
 pub struct Changes {
     pub new_chunks: HashSet<Vec2<i32>>,
@@ -62,21 +81,37 @@ impl Changes {
     }
 }
 
+/// Owns the dispatcher that runs the systems of the state.
+struct SendDispatcher(Dispatcher<'static, 'static>);
+
+// SAFETY: The only part of a `Dispatcher` that isn't `Send` is its list of thread-local systems
+// (added with `DispatcherBuilder::add_thread_local`), which may hold non-`Send` data and always run
+// on the thread calling `dispatch`. Everything else is `Send`: `DispatcherBuilder::add` only takes
+// `Send` systems, and the thread pool is an `Arc<ThreadPool>`. A `SendDispatcher` is only built in
+// `build_dispatcher`, from the systems of `sys::add_local_systems`, which never adds thread-local
+// ones. So no part of the dispatcher is bound to the thread it was built on, and `State` (and with
+// it `Client` and `Server`) can move between threads. Adding a thread-local system there would
+// make this unsound.
+unsafe impl Send for SendDispatcher {}
+
 /// A type used to represent game state stored on both the client and the server. This includes
 /// things like entity components, terrain data, and global states like weather, time of day, etc.
 pub struct State {
     ecs: sphynx::World<EcsCompPacket, EcsResPacket>,
-    // Avoid lifetime annotation by storing a thread pool instead of the whole dispatcher
-    thread_pool: Arc<ThreadPool>,
+    dispatcher: SendDispatcher,
+    // Simulation time that hasn't been stepped through yet
+    accumulator: f64,
     changes: Changes,
 }
 
 impl State {
     /// Create a new `State`.
     pub fn new() -> Self {
+        let mut ecs = sphynx::World::new(specs::World::new(), Self::setup_sphynx_world);
         Self {
-            ecs: sphynx::World::new(specs::World::new(), Self::setup_sphynx_world),
-            thread_pool: Arc::new(ThreadPoolBuilder::new().build().unwrap()),
+            dispatcher: Self::build_dispatcher(&mut ecs.res),
+            ecs,
+            accumulator: 0.0,
             changes: Changes::default(),
         }
     }
@@ -85,17 +120,29 @@ impl State {
     pub fn from_state_package(
         state_package: sphynx::StatePackage<EcsCompPacket, EcsResPacket>,
     ) -> Self {
+        let mut ecs = sphynx::World::from_state_package(
+            specs::World::new(),
+            Self::setup_sphynx_world,
+            state_package,
+        );
         Self {
-            ecs: sphynx::World::from_state_package(
-                specs::World::new(),
-                Self::setup_sphynx_world,
-                state_package,
-            ),
-            thread_pool: Arc::new(ThreadPoolBuilder::new().build().unwrap()),
+            dispatcher: Self::build_dispatcher(&mut ecs.res),
+            ecs,
+            accumulator: 0.0,
             changes: Changes::default(),
         }
     }
 
+    // Create the dispatcher that runs the ECS systems every step, and set its systems up.
+    fn build_dispatcher(res: &mut Resources) -> SendDispatcher {
+        let thread_pool: Arc<ThreadPool> = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        let mut dispatch_builder = DispatcherBuilder::new().with_pool(thread_pool);
+        sys::add_local_systems(&mut dispatch_builder);
+        let mut dispatcher = dispatch_builder.build();
+        dispatcher.setup(res);
+        SendDispatcher(dispatcher)
+    }
+
     // Create a new Sphynx ECS world.
     fn setup_sphynx_world(ecs: &mut sphynx::World<EcsCompPacket, EcsResPacket>) {
         // Register synced components.
@@ -103,6 +150,13 @@ impl State {
         ecs.register_synced::<comp::Player>();
         ecs.register_synced::<comp::Stats>();
         ecs.register_synced::<comp::Attacking>(); // TODO: Don't send this to the client?
+        ecs.register_synced::<comp::Projectile>();
+        ecs.register_synced::<comp::Equipment>();
+        ecs.register_synced::<comp::Effects>();
+        ecs.register_synced::<comp::LifeState>();
+        ecs.register_synced::<comp::Group>();
+        ecs.register_synced::<comp::PvpFlag>();
+        ecs.register_synced::<inventory::item::Item>();
         ecs.register::<comp::phys::ForceUpdate>();
 
         // Register components synced by other means
@@ -111,6 +165,13 @@ impl State {
         ecs.register::<comp::phys::Ori>();
         ecs.register::<comp::AnimationInfo>();
 
+        // Register components computed by both sides
+        ecs.register::<comp::phys::Collider>();
+        ecs.register::<comp::phys::Mass>();
+        ecs.register::<comp::phys::Friction>();
+        ecs.register::<comp::phys::OnGround>();
+        ecs.register::<comp::phys::InFluid>();
+
         // Register client-local components
         ecs.register::<comp::Control>();
         ecs.register::<comp::Jumping>();
@@ -120,15 +181,24 @@ impl State {
         ecs.register::<comp::Respawning>();
         ecs.register::<comp::Gliding>();
         ecs.register::<comp::Dying>();
+        ecs.register::<comp::player::RespawnPoint>();
+        ecs.register::<comp::group::GroupInvite>();
         ecs.register::<inventory::Inventory>();
+        ecs.register::<inventory::InventoryUpdate>();
+        ecs.register::<inventory::Dropped>();
 
         // Register synced resources used by the ECS.
         ecs.add_resource_synced(TimeOfDay(0.0));
+        ecs.add_resource_synced(PvpRules::default());
 
         // Register unsynced resources used by the ECS.
         ecs.add_resource(Time(0.0));
         ecs.add_resource(DeltaTime(0.0));
+        ecs.add_resource(SimRng(StdRng::seed_from_u64(DEFAULT_SEED)));
         ecs.add_resource(TerrainMap::new().unwrap());
+        ecs.add_resource(Pathfinder::default());
+        ecs.add_resource(FiredProjectiles::default());
+        ecs.add_resource(BlockedHits::default());
     }
 
     /// Register a component with the state's ECS.
@@ -185,6 +255,11 @@ impl State {
         self.ecs.read_resource::<Time>().0
     }
 
+    /// Reseed the random number generator of the simulation.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.ecs.write_resource::<SimRng>().0 = StdRng::seed_from_u64(seed);
+    }
+
     /// Get a reference to this state's terrain.
     pub fn terrain(&self) -> Fetch<TerrainMap> {
         self.ecs.read_resource::<TerrainMap>()
@@ -234,29 +309,104 @@ impl State {
         }
     }
 
-    /// Execute a single tick, simulating the game state by the given duration.
+    /// Execute a single tick, simulating the game state by the given duration. The simulation
+    /// advances in fixed steps of `STEP_TIME`; time left over is carried over to the next tick.
     pub fn tick(&mut self, dt: Duration) {
-        // Change the time accordingly.
-        self.ecs.write_resource::<TimeOfDay>().0 += dt.as_secs_f64() * DAY_CYCLE_FACTOR;
-        self.ecs.write_resource::<Time>().0 += dt.as_secs_f64();
+        self.accumulator += dt.as_secs_f64();
+
+        // Beyond MAX_STEPS_PER_TICK, start lagging instead of trying to catch up.
+        let mut steps = 0;
+        while self.accumulator >= STEP_TIME && steps < MAX_STEPS_PER_TICK {
+            self.step();
+            self.accumulator -= STEP_TIME;
+            steps += 1;
+        }
+        if steps == MAX_STEPS_PER_TICK {
+            self.accumulator = self.accumulator.min(STEP_TIME);
+        }
+    }
 
-        // Update delta time.
-        // Beyond a delta time of MAX_DELTA_TIME, start lagging to avoid skipping important physics events.
-        self.ecs.write_resource::<DeltaTime>().0 = dt.as_secs_f32().min(MAX_DELTA_TIME);
+    /// Advance the simulation by a single step of `STEP_TIME`.
+    pub fn step(&mut self) {
+        // Change the time accordingly.
+        self.ecs.write_resource::<TimeOfDay>().0 += STEP_TIME * DAY_CYCLE_FACTOR;
+        self.ecs.write_resource::<Time>().0 += STEP_TIME;
+        self.ecs.write_resource::<DeltaTime>().0 = STEP_TIME as f32;
 
         // Run systems to update the world.
-        // Create and run a dispatcher for ecs systems.
-        let mut dispatch_builder = DispatcherBuilder::new().with_pool(self.thread_pool.clone());
-        sys::add_local_systems(&mut dispatch_builder);
         // This dispatches all the systems in parallel.
-        dispatch_builder.build().dispatch(&self.ecs.res);
+        self.dispatcher.0.dispatch(&self.ecs.res);
 
         self.ecs.maintain();
     }
 
+    /// Create entities for the projectiles fired since the last call, and remove the ones that
+    /// hit something or flew for too long. Only the server should do this.
+    pub fn maintain_projectiles(&mut self) {
+        let fired = std::mem::replace(
+            &mut self.ecs.write_resource::<FiredProjectiles>().0,
+            Vec::new(),
+        );
+        for fired in fired {
+            self.ecs
+                .create_entity_synced()
+                .with(comp::phys::Pos(fired.pos))
+                .with(comp::phys::Vel(fired.vel))
+                .with(comp::phys::Ori(fired.vel.normalized()))
+                .with(fired.projectile)
+                .with(comp::phys::ForceUpdate)
+                .build();
+        }
+
+        let done = (
+            &self.ecs.entities(),
+            &self.ecs.read_storage::<comp::Projectile>(),
+        )
+            .join()
+            .filter(|(_, projectile)| projectile.done)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in done {
+            if let Err(err) = self.ecs.delete_entity_synced(entity) {
+                warn!("Failed to delete projectile: {:?}", err);
+            }
+        }
+    }
+
+    /// Forget the projectiles fired since the last call. Clients leave creating projectiles to
+    /// the server.
+    pub fn discard_fired_projectiles(&mut self) {
+        self.ecs.write_resource::<FiredProjectiles>().0.clear();
+    }
+
+    /// Take the hits between players that the PvP rules prevented since the last call.
+    pub fn take_blocked_hits(&mut self) -> Vec<BlockedHit> {
+        std::mem::replace(&mut self.ecs.write_resource::<BlockedHits>().0, Vec::new())
+    }
+
     /// Clean up the state after a tick.
     pub fn cleanup(&mut self) {
         // Clean up data structures from the last tick.
         self.changes.cleanup();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_advance_in_fixed_steps() {
+        let mut state = State::new();
+
+        state.tick(Duration::from_millis(10));
+        assert_eq!(state.get_time(), 0.0);
+        state.tick(Duration::from_millis(10));
+        assert_eq!(state.get_time(), STEP_TIME);
+
+        // Long ticks lag behind instead of running an unbounded number of steps.
+        state.tick(Duration::from_secs(5));
+        let expected = STEP_TIME * (1 + MAX_STEPS_PER_TICK) as f64;
+        assert!((state.get_time() - expected).abs() < 1e-9);
+    }
+}
@@ -1,5 +1,9 @@
-use crate::{comp::Attacking, state::DeltaTime};
-use specs::{Entities, Join, Read, System, WriteStorage};
+use crate::{
+    comp::{Attacking, Equipment},
+    inventory::item::WeaponStats,
+    state::DeltaTime,
+};
+use specs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
 
 // Basic ECS AI agent system
 pub struct Sys;
@@ -8,17 +12,25 @@ impl<'a> System<'a> for Sys {
     type SystemData = (
         Entities<'a>,
         Read<'a, DeltaTime>,
+        ReadStorage<'a, Equipment>,
         WriteStorage<'a, Attacking>,
     );
 
-    fn run(&mut self, (entities, dt, mut attacks): Self::SystemData) {
+    fn run(&mut self, (entities, dt, equipments, mut attacks): Self::SystemData) {
         for attack in (&mut attacks).join() {
             attack.time += dt.0;
         }
 
         let finished_attacks = (&entities, &mut attacks)
             .join()
-            .filter(|(_, a)| a.time > 0.25) // TODO: constant
+            .filter(|(e, a)| {
+                let cooldown = equipments
+                    .get(*e)
+                    .map(|equipment| equipment.weapon_stats())
+                    .unwrap_or(WeaponStats::UNARMED)
+                    .cooldown;
+                a.time > cooldown
+            })
             .map(|(e, _)| e)
             .collect::<Vec<_>>();
 
@@ -1,17 +1,113 @@
-use crate::comp::{phys::Pos, Agent, Attacking, Control, Jumping};
+use crate::{
+    ai::{self, Action, Situation},
+    comp::{
+        agent::{Route, Threat},
+        phys::{Collider, Pos},
+        Agent, AgentKind, Attacking, Control, HealthSource, Jumping, LifeState, PetCommand, Stats,
+    },
+    pathfinding::{PathResult, Pathfinder},
+    ray::Ray,
+    state::{SimRng, Time, Uid},
+    terrain::{Block, TerrainMap},
+};
+use fxhash::FxHashMap;
 use log::warn;
-use rand::{seq::SliceRandom, thread_rng};
-use specs::{Entities, Join, ReadStorage, System, WriteStorage};
+use rand::Rng;
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
 use vek::*;
 
-// Basic ECS AI agent system
+// Agents remember whoever hit them within this many seconds
+const ATTACKED_RECENTLY: f64 = 1.0;
+// Eye height for entities without a collider
+const DEFAULT_EYE_HEIGHT: f32 = 1.5;
+// Agents look for a new path when their target moves this many blocks away from the end of the
+// current one, or when they get this far away from the path
+const REPATH_DIST: i32 = 3;
+// How close (in blocks) agents need to get to a node of their path to move on to the next one
+const WAYPOINT_RADIUS: f32 = 0.5;
+
+/// Where to steer towards to follow a path through the terrain to `tgt_pos`. Agents head straight
+/// for their target until the path has been found, and once they reach the end of it.
+fn navigate(
+    pathfinder: &mut Pathfinder,
+    terrain: &TerrainMap,
+    route: &mut Option<Route>,
+    pos: Vec3<f32>,
+    tgt_pos: Vec3<f32>,
+    height: i32,
+) -> Vec3<f32> {
+    let cell = pos.map(|e| e.floor() as i32);
+    let goal = tgt_pos.map(|e| e.floor() as i32);
+
+    let stale = route.as_ref().map_or(true, |route| {
+        (route.to - goal).map(|e| e.abs()).reduce_max() > REPATH_DIST
+    });
+    if stale {
+        *route = Some(Route {
+            from: cell,
+            to: goal,
+            path: None,
+            next: 0,
+        });
+    }
+    let route = match route {
+        Some(route) => route,
+        None => return tgt_pos,
+    };
+
+    if route.path.is_none() {
+        if let PathResult::Found(path) = pathfinder.path(terrain, route.from, route.to, height) {
+            route.path = Some(path);
+            route.next = 0;
+        }
+    }
+
+    let path = match &route.path {
+        Some(path) => path.clone(),
+        None => return tgt_pos,
+    };
+
+    // Move on past the nodes that have been reached.
+    while let Some(node) = path.nodes.get(route.next) {
+        let node_pos = node.map(|e| e as f32) + Vec3::new(0.5, 0.5, 0.0);
+        if Vec2::<f32>::from(node_pos - pos).magnitude() < WAYPOINT_RADIUS
+            && (node.z - cell.z).abs() <= 1
+        {
+            route.next += 1;
+        } else {
+            break;
+        }
+    }
+
+    match path.nodes.get(route.next) {
+        Some(node) => {
+            // Agents that got pushed or fell off their path look for a new one.
+            if (*node - cell).map(|e| e.abs()).reduce_max() > REPATH_DIST {
+                route.path = None;
+                route.from = cell;
+            }
+            node.map(|e| e as f32) + Vec3::new(0.5, 0.5, 0.0)
+        }
+        None => tgt_pos,
+    }
+}
+
+/// Runs the perception and decision making of AI agents, see `ai`.
 pub struct Sys;
 
 impl<'a> System<'a> for Sys {
     type SystemData = (
         Entities<'a>,
+        Read<'a, Time>,
+        ReadExpect<'a, TerrainMap>,
+        WriteExpect<'a, SimRng>,
+        WriteExpect<'a, Pathfinder>,
         WriteStorage<'a, Agent>,
+        ReadStorage<'a, Uid>,
         ReadStorage<'a, Pos>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Stats>,
+        ReadStorage<'a, LifeState>,
         WriteStorage<'a, Control>,
         WriteStorage<'a, Jumping>,
         WriteStorage<'a, Attacking>,
@@ -19,96 +115,305 @@ impl<'a> System<'a> for Sys {
 
     fn run(
         &mut self,
-        (entities, mut agents, positions, mut controls, mut jumps, mut attacks): Self::SystemData,
+        (
+            entities,
+            time,
+            terrain,
+            mut rng,
+            mut pathfinder,
+            mut agents,
+            uids,
+            positions,
+            colliders,
+            stats,
+            life_states,
+            mut controls,
+            mut jumps,
+            mut attacks,
+        ): Self::SystemData,
     ) {
+        let rng = &mut rng.0;
+        let time = time.0;
+        pathfinder.maintain();
+
+        let eye = |entity, pos: Vec3<f32>| {
+            pos + Vec3::unit_z()
+                * colliders
+                    .get(entity)
+                    .map(|collider| collider.size.z * 0.9)
+                    .unwrap_or(DEFAULT_EYE_HEIGHT)
+        };
+        let can_see = |from: Vec3<f32>, to: Vec3<f32>| {
+            if let (_, Ok(None)) = Ray::new(&*terrain, from, to, |block: &Block| block.is_solid())
+                .ignore_error()
+                .cast()
+            {
+                true
+            } else {
+                false
+            }
+        };
+
+        let entity_from_uid = (&entities, &uids)
+            .join()
+            .map(|(entity, &uid)| (u64::from(uid), entity))
+            .collect::<FxHashMap<_, _>>();
+
+        // Packs stick together and share what they know about threats.
+        let mut agent_kinds = FxHashMap::default();
+        let mut packs = FxHashMap::<u32, (Vec3<f32>, f32, Vec<Threat>)>::default();
+        for (entity, agent, pos) in (&entities, &agents, &positions).join() {
+            agent_kinds.insert(entity, agent.kind);
+            if let Some(pack) = agent.pack {
+                let (centre, count, threats) =
+                    packs.entry(pack).or_insert((Vec3::zero(), 0.0, Vec::new()));
+                *centre += pos.0;
+                *count += 1.0;
+                threats.extend(agent.threats.iter().cloned());
+            }
+        }
+
         for (entity, agent, pos, control) in
             (&entities, &mut agents, &positions, &mut controls).join()
         {
-            match agent {
-                Agent::Wanderer(bearing) => {
-                    *bearing += Vec2::new(rand::random::<f32>() - 0.5, rand::random::<f32>() - 0.5)
-                        * 0.1
-                        - *bearing * 0.01
-                        - pos.0 * 0.0002;
+            let behaviour = ai::behaviour(&agent.behaviour);
+            // Pets of players that left have nobody to follow.
+            let owner = agent
+                .owner()
+                .and_then(|owner| entity_from_uid.get(&owner).cloned());
+            let health = stats
+                .get(entity)
+                .map(|stats| stats.hp.get_current() as f32 / stats.hp.get_maximum().max(1) as f32)
+                .unwrap_or(1.0);
 
-                    if bearing.magnitude_squared() != 0.0 {
-                        control.move_dir = bearing.normalized();
+            // Perception: aggressive agents are wary of every character they can see, except for
+            // their owner. Other agents only count if they are pets.
+            if behaviour.aggressive {
+                let eye_pos = eye(entity, pos.0);
+                for (other, other_pos, _) in (&entities, &positions, &stats).join() {
+                    let is_hostile = other != entity
+                        && Some(other) != owner
+                        && match agent_kinds.get(&other) {
+                            Some(AgentKind::Pet { .. }) | None => true,
+                            Some(_) => false,
+                        };
+
+                    if is_hostile
+                        && life_states.get(other).map_or(true, LifeState::is_alive)
+                        && other_pos.0.distance(pos.0) <= behaviour.sight_range
+                        && can_see(eye_pos, eye(other, other_pos.0))
+                    {
+                        agent.remember(Threat {
+                            entity: other,
+                            last_seen_pos: other_pos.0,
+                            last_seen: time,
+                        });
                     }
                 }
-                Agent::Pet { target, offset } => {
-                    // Run towards target.
-                    match positions.get(*target) {
-                        Some(tgt_pos) => {
-                            let tgt_pos = tgt_pos.0 + *offset;
-
-                            if tgt_pos.z > pos.0.z + 1.0 {
-                                if let Err(err) = jumps.insert(entity, Jumping) {
-                                    warn!("Inserting Jumping for an entity failed: {:?}", err,);
-                                }
-                            }
+            }
 
-                            // Move towards the target.
-                            let dist: f32 = Vec2::from(tgt_pos - pos.0).magnitude();
-                            control.move_dir = if dist > 5.0 {
-                                Vec2::from(tgt_pos - pos.0).normalized()
-                            } else if dist < 1.5 && dist > 0.0 {
-                                Vec2::from(pos.0 - tgt_pos).normalized()
-                            } else {
-                                Vec2::zero()
-                            };
+            // Whoever attacked the agent is a threat, no matter where it is.
+            if let Some((amount, since, HealthSource::Attack { by })) =
+                stats.get(entity).and_then(|stats| stats.hp.last_change)
+            {
+                if amount < 0 && since < ATTACKED_RECENTLY {
+                    if let Some((attacker, attacker_pos)) = entity_from_uid
+                        .get(&u64::from(by))
+                        .filter(|&&attacker| attacker != entity && Some(attacker) != owner)
+                        .and_then(|&attacker| Some((attacker, positions.get(attacker)?)))
+                    {
+                        agent.remember(Threat {
+                            entity: attacker,
+                            last_seen_pos: attacker_pos.0,
+                            last_seen: time,
+                        });
+                    }
+                }
+            }
+
+            // Pets defend their owner from enemies, and go after whatever their owner points at.
+            if let Some((_, _, HealthSource::Attack { by })) = owner
+                .and_then(|owner| stats.get(owner))
+                .and_then(|stats| stats.hp.last_change)
+                .filter(|(amount, since, _)| *amount < 0 && *since < ATTACKED_RECENTLY)
+            {
+                if let Some((attacker, attacker_pos)) = entity_from_uid
+                    .get(&u64::from(by))
+                    .filter(|&&attacker| agent_kinds.get(&attacker) == Some(&AgentKind::Enemy))
+                    .and_then(|&attacker| Some((attacker, positions.get(attacker)?)))
+                {
+                    agent.remember(Threat {
+                        entity: attacker,
+                        last_seen_pos: attacker_pos.0,
+                        last_seen: time,
+                    });
+                }
+            }
+            if let AgentKind::Pet {
+                owner: owner_uid,
+                command: PetCommand::Attack(target),
+            } = agent.kind
+            {
+                match entity_from_uid
+                    .get(&target)
+                    .filter(|&&target| life_states.get(target).map_or(true, LifeState::is_alive))
+                    .and_then(|&target| Some((target, positions.get(target)?)))
+                {
+                    Some((target, target_pos)) => agent.remember(Threat {
+                        entity: target,
+                        last_seen_pos: target_pos.0,
+                        last_seen: time,
+                    }),
+                    // Pets come back once their target is dead or gone.
+                    None => {
+                        agent.kind = AgentKind::Pet {
+                            owner: owner_uid,
+                            command: PetCommand::Follow,
                         }
-                        _ => control.move_dir = Vec2::zero(),
                     }
+                }
+            }
+
+            let mut pack_centre = None;
+            if let Some((centre, count, threats)) = agent.pack.and_then(|pack| packs.get(&pack)) {
+                pack_centre = Some(*centre / *count);
+                for threat in threats {
+                    agent.remember(*threat);
+                }
+            }
+
+            // Memory
+            agent.forget_before(time - behaviour.memory);
+            agent.threats.retain(|threat| {
+                entities.is_alive(threat.entity)
+                    && stats.get(threat.entity).is_some()
+                    && life_states
+                        .get(threat.entity)
+                        .map_or(true, LifeState::is_alive)
+            });
+
+            // Decision making
+            let owner_pos = owner
+                .and_then(|owner| positions.get(owner))
+                .map(|pos| pos.0);
+            let threat = agent.nearest_threat(pos.0).cloned();
+            let height = colliders
+                .get(entity)
+                .map(|collider| collider.size.z.ceil() as i32)
+                .unwrap_or(2);
+            let staying = agent.command() == Some(PetCommand::Stay);
+            let situation = Situation {
+                health,
+                owner_dist: owner_pos
+                    .filter(|_| !staying)
+                    .map(|owner_pos| Vec2::from(owner_pos - pos.0).magnitude()),
+                threat_dist: threat
+                    .map(|threat| Vec2::from(threat.last_seen_pos - pos.0).magnitude()),
+                pack_dist: pack_centre
+                    .map(|centre: Vec3<f32>| Vec2::from(centre - pos.0).magnitude()),
+            };
 
-                    // Change offset occasionally.
-                    if rand::random::<f32>() < 0.003 {
-                        *offset =
-                            Vec2::new(rand::random::<f32>() - 0.5, rand::random::<f32>() - 0.5)
-                                * 10.0;
+            let mut move_to = |control: &mut Control, tgt_pos: Vec3<f32>, speed: f32| {
+                let dir = Vec2::<f32>::from(tgt_pos - pos.0);
+                control.move_dir = if dir.magnitude_squared() > 0.0 {
+                    dir.normalized() * speed
+                } else {
+                    Vec2::zero()
+                };
+                if tgt_pos.z > pos.0.z + 1.0 {
+                    if let Err(err) = jumps.insert(entity, Jumping) {
+                        warn!("Inserting Jumping for an entity failed: {:?}", err);
                     }
                 }
-                Agent::Enemy { target } => {
-                    let choose_new = match target.map(|tgt| positions.get(tgt)).flatten() {
-                        Some(tgt_pos) => {
-                            let dist = Vec2::<f32>::from(tgt_pos.0 - pos.0).magnitude();
-                            if dist < 2.0 {
-                                control.move_dir = Vec2::zero();
-
-                                if rand::random::<f32>() < 0.2 {
-                                    attacks
-                                        .insert(entity, Attacking::start())
-                                        .expect("Inserting attacking for an entity failed!");
-                                }
+            };
 
-                                false
-                            } else if dist < 60.0 {
-                                control.move_dir =
-                                    Vec2::<f32>::from(tgt_pos.0 - pos.0).normalized() * 0.96;
+            // Pets told to stay put only move to fight.
+            let action = match behaviour.choose(&situation) {
+                Action::Wander if staying => Action::Idle,
+                action => action,
+            };
 
-                                false
-                            } else {
-                                true
-                            }
-                        }
-                        None => {
-                            control.move_dir = Vec2::one();
-                            true
-                        }
+            match action {
+                Action::Idle => control.move_dir = Vec2::zero(),
+                Action::Wander => {
+                    agent.bearing += Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5)
+                        * 0.1
+                        - agent.bearing * 0.01;
+                    // Pets wander around their owner, everyone else around the origin.
+                    agent.bearing -= match owner_pos {
+                        Some(owner_pos) => Vec2::from(pos.0 - owner_pos) * 0.01,
+                        None => Vec2::from(pos.0) * 0.0002,
                     };
 
-                    if choose_new {
-                        let entities = (&entities, &positions)
-                            .join()
-                            .filter(|(_, e_pos)| {
-                                Vec2::<f32>::from(e_pos.0 - pos.0).magnitude() < 30.0
-                            })
-                            .map(|(e, _)| e)
-                            .collect::<Vec<_>>();
-
-                        let mut rng = thread_rng();
-                        *target = (&entities).choose(&mut rng).cloned();
+                    if agent.bearing.magnitude_squared() != 0.0 {
+                        control.move_dir = agent.bearing.normalized();
                     }
                 }
+                Action::Follow => match owner_pos {
+                    Some(owner_pos) => {
+                        let waypoint = navigate(
+                            &mut pathfinder,
+                            &terrain,
+                            &mut agent.route,
+                            pos.0,
+                            owner_pos + agent.offset,
+                            height,
+                        );
+                        move_to(control, waypoint, 1.0);
+
+                        // Change offset occasionally.
+                        if rng.gen::<f32>() < 0.003 {
+                            agent.offset =
+                                Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * 10.0;
+                        }
+                    }
+                    None => control.move_dir = Vec2::zero(),
+                },
+                Action::Attack => match threat {
+                    Some(threat) => {
+                        // Chase the target where it is if it's visible, otherwise where it was
+                        // last seen.
+                        let tgt_pos = positions
+                            .get(threat.entity)
+                            .map(|tgt_pos| tgt_pos.0)
+                            .filter(|_| threat.last_seen == time)
+                            .unwrap_or(threat.last_seen_pos);
+                        let dist = Vec2::<f32>::from(tgt_pos - pos.0).magnitude();
+
+                        if dist < behaviour.attack_range {
+                            control.move_dir = Vec2::zero();
+
+                            if attacks.get(entity).is_none() && rng.gen::<f32>() < 0.2 {
+                                if let Err(err) = attacks.insert(entity, Attacking::start()) {
+                                    warn!("Inserting Attacking for an entity failed: {:?}", err);
+                                }
+                            }
+                        } else {
+                            let waypoint = navigate(
+                                &mut pathfinder,
+                                &terrain,
+                                &mut agent.route,
+                                pos.0,
+                                tgt_pos,
+                                height,
+                            );
+                            move_to(control, waypoint, 0.96);
+                        }
+                    }
+                    None => control.move_dir = Vec2::zero(),
+                },
+                Action::Flee => match threat {
+                    Some(threat) => move_to(
+                        control,
+                        pos.0 + Vec3::from(Vec2::from(pos.0 - threat.last_seen_pos)),
+                        1.0,
+                    ),
+                    None => control.move_dir = Vec2::zero(),
+                },
+                Action::Regroup => match pack_centre {
+                    Some(centre) => move_to(control, centre, 1.0),
+                    None => control.move_dir = Vec2::zero(),
+                },
             }
         }
     }
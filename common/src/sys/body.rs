@@ -0,0 +1,60 @@
+use crate::comp::{
+    phys::{Collider, Friction, Mass},
+    Actor,
+};
+use log::warn;
+use specs::{
+    BitSet, Entities, Join, ModifiedFlag, ReadStorage, ReaderId, Resources, System, SystemData,
+    WriteStorage,
+};
+
+/// Derives the physical properties of entities from their bodies. This happens when an entity
+/// gets a body and whenever its body changes, rather than every tick.
+#[derive(Default)]
+pub struct Sys {
+    modified: Option<ReaderId<ModifiedFlag>>,
+    changed: BitSet,
+}
+
+impl<'a> System<'a> for Sys {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Actor>,
+        WriteStorage<'a, Collider>,
+        WriteStorage<'a, Mass>,
+        WriteStorage<'a, Friction>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.modified = Some(WriteStorage::<Actor>::fetch(res).track_modified());
+    }
+
+    fn run(
+        &mut self,
+        (entities, actors, mut colliders, mut masses, mut frictions): Self::SystemData,
+    ) {
+        self.changed.clear();
+        if let Some(modified) = self.modified.as_mut() {
+            actors.populate_modified(modified, &mut self.changed);
+        }
+        // Entities without a collider just got their body.
+        for (entity, _, _) in (&entities, &actors, !&colliders).join() {
+            self.changed.add(entity.id());
+        }
+
+        for (entity, actor, _) in (&entities, &actors, &self.changed).join() {
+            let body = match actor {
+                Actor::Character { body, .. } => body,
+            };
+
+            if let Err(err) = colliders
+                .insert(entity, Collider::for_body(body))
+                .and(masses.insert(entity, Mass::for_body(body)))
+                .and(frictions.insert(entity, Friction::for_body(body)))
+            {
+                warn!("Inserting body properties for an entity failed: {:?}", err);
+            }
+        }
+    }
+}
@@ -1,14 +1,18 @@
 use crate::{
     comp::{
-        phys::{ForceUpdate, Ori, Pos, Vel},
-        Animation, AnimationInfo, Attacking, Control, Gliding, HealthSource, Jumping, Stats,
+        phys::{ForceUpdate, InFluid, Mass, OnGround, Ori, Pos, Vel},
+        projectile::{FiredProjectile, FiredProjectiles},
+        pvp::{player_behind, BlockedHit, BlockedHits, PvpRules},
+        stats::{ATTACK_ENERGY, ENERGY_REGEN, GLIDE_ENERGY},
+        Agent, Animation, AnimationInfo, Attacking, Control, Effects, Equipment, Gliding, Group,
+        HealthSource, Jumping, LifeState, Player, Projectile, PvpFlag, Stats,
     },
+    inventory::item::WeaponStats,
     state::{DeltaTime, Uid},
-    terrain::TerrainMap,
-    vol::{ReadVol, Vox},
 };
+use fxhash::FxHashMap;
 use log::warn;
-use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, WriteStorage};
+use specs::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage};
 use vek::*;
 
 // Basic ECS AI agent system
@@ -19,22 +23,49 @@ const HUMANOID_SPEED: f32 = 500.0;
 const HUMANOID_AIR_ACCEL: f32 = 10.0;
 const HUMANOID_AIR_SPEED: f32 = 100.0;
 const HUMANOID_JUMP_ACCEL: f32 = 16.0;
+// The knock-back impulse of an attack with 100 strength. A humanoid (60 kg) gets pushed away at
+// 10 m/s.
+const KNOCKBACK_IMPULSE: f32 = 600.0;
 const GLIDE_ACCEL: f32 = 15.0;
 const GLIDE_SPEED: f32 = 45.0;
 // Gravity is 9.81 * 4, so this makes gravity equal to .15
 const GLIDE_ANTIGRAV: f32 = 9.81 * 3.95;
+const SWIM_ACCEL: f32 = 20.0;
+const SWIM_SPEED: f32 = 6.0;
+// Pressing jump while swimming gives an upward stroke of this velocity
+const SWIM_STROKE: f32 = 5.0;
+// Entities with more than this fraction of their body in fluid swim
+const SWIM_SUBMERSION: f32 = 0.5;
+// Projectiles leave ranged weapons at this height above the feet of the attacker...
+const MUZZLE_HEIGHT: f32 = 1.4;
+// ...this far in front of them...
+const MUZZLE_DIST: f32 = 0.6;
+// ...and aim slightly upwards.
+const MUZZLE_LIFT: f32 = 0.05;
 
 impl<'a> System<'a> for Sys {
     type SystemData = (
         Entities<'a>,
         ReadStorage<'a, Uid>,
         Read<'a, DeltaTime>,
-        ReadExpect<'a, TerrainMap>,
+        Write<'a, FiredProjectiles>,
+        Read<'a, PvpRules>,
+        Write<'a, BlockedHits>,
         ReadStorage<'a, Pos>,
+        ReadStorage<'a, OnGround>,
+        ReadStorage<'a, InFluid>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Equipment>,
         WriteStorage<'a, Vel>,
         WriteStorage<'a, Ori>,
         WriteStorage<'a, AnimationInfo>,
         WriteStorage<'a, Stats>,
+        WriteStorage<'a, Effects>,
+        ReadStorage<'a, LifeState>,
+        ReadStorage<'a, Group>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, PvpFlag>,
+        ReadStorage<'a, Agent>,
         ReadStorage<'a, Control>,
         WriteStorage<'a, Jumping>,
         WriteStorage<'a, Gliding>,
@@ -48,12 +79,24 @@ impl<'a> System<'a> for Sys {
             entities,
             uids,
             dt,
-            terrain,
+            mut fired_projectiles,
+            pvp_rules,
+            mut blocked_hits,
             positions,
+            on_grounds,
+            in_fluids,
+            masses,
+            equipments,
             mut velocities,
             mut orientations,
             mut animation_infos,
             mut stats,
+            mut effects,
+            life_states,
+            groups,
+            players,
+            pvp_flags,
+            agents,
             controls,
             mut jumps,
             glides,
@@ -61,35 +104,52 @@ impl<'a> System<'a> for Sys {
             mut force_updates,
         ): Self::SystemData,
     ) {
-        for (entity, pos, control, stats, mut ori, mut vel) in (
+        for (entity, control, stats, mut ori, mut vel) in (
             &entities,
-            &positions,
             &controls,
-            &stats,
+            &mut stats,
             &mut orientations,
             &mut velocities,
         )
             .join()
         {
-            // Disable while dead TODO: Replace with client states
-            if stats.is_dead {
+            // The dead don't move
+            if !life_states.get(entity).map_or(true, LifeState::is_alive) {
                 continue;
             }
 
-            let on_ground = terrain
-                .get((pos.0 - Vec3::unit_z() * 0.1).map(|e| e.floor() as i32))
-                .map(|vox| !vox.is_empty())
-                .unwrap_or(false)
-                && vel.0.z <= 0.0;
+            let on_ground = on_grounds.get(entity).is_some();
+            let swimming = in_fluids
+                .get(entity)
+                .map(|in_fluid| in_fluid.submersion > SWIM_SUBMERSION)
+                .unwrap_or(false);
+
+            // Gliding takes energy
+            let gliding = glides.get(entity).is_some() && vel.0.z < 0.0 && stats.energy > 0.0;
+            if gliding && !on_ground && !swimming {
+                stats.energy = (stats.energy - (GLIDE_ENERGY + ENERGY_REGEN) * dt.0).max(0.0);
+            }
 
-            let gliding = glides.get(entity).is_some() && vel.0.z < 0.0;
             let move_dir = if control.move_dir.magnitude() > 1.0 {
                 control.move_dir.normalized()
             } else {
                 control.move_dir
-            };
+            } * effects
+                .get(entity)
+                .map(|effects| effects.speed_factor())
+                .unwrap_or(1.0);
+
+            if swimming {
+                if Vec2::<f32>::from(vel.0).magnitude() < SWIM_SPEED {
+                    vel.0 += Vec2::broadcast(dt.0) * move_dir * SWIM_ACCEL;
+                }
 
-            if on_ground {
+                // Swim upwards
+                if jumps.get(entity).is_some() {
+                    vel.0.z = vel.0.z.max(SWIM_STROKE);
+                    jumps.remove(entity);
+                }
+            } else if on_ground {
                 // Move player according to move_dir
                 if vel.0.magnitude() < HUMANOID_SPEED {
                     vel.0 += Vec2::broadcast(dt.0) * move_dir * HUMANOID_ACCEL;
@@ -113,7 +173,9 @@ impl<'a> System<'a> for Sys {
                 ori.0 = vel.0.normalized() * Vec3::new(1.0, 1.0, 0.0);
             }
 
-            let animation = if on_ground {
+            let animation = if swimming {
+                Animation::Swim
+            } else if on_ground {
                 if control.move_dir.magnitude() > 0.01 {
                     Animation::Run
                 } else if attacks.get(entity).is_some() {
@@ -145,30 +207,187 @@ impl<'a> System<'a> for Sys {
             }
         }
 
+        // Where players are and whether they want to fight, by `Uid`, to judge attacks on players
+        let fighters = (&entities, &uids, &positions, &players)
+            .join()
+            .map(|(entity, &uid, pos, _)| {
+                (u64::from(uid), (pos.0, pvp_flags.get(entity).is_some()))
+            })
+            .collect::<FxHashMap<_, _>>();
+
         for (entity, &uid, pos, ori, attacking) in
             (&entities, &uids, &positions, &orientations, &mut attacks).join()
         {
             if !attacking.applied {
+                // Attacks take energy, and tired attackers just swing at the air
+                match stats.get_mut(entity) {
+                    Some(stats) if stats.energy < ATTACK_ENERGY => {
+                        attacking.applied = true;
+                        continue;
+                    }
+                    Some(stats) => stats.energy -= ATTACK_ENERGY,
+                    None => {}
+                }
+
+                let mut weapon = equipments
+                    .get(entity)
+                    .map(|equipment| equipment.weapon_stats())
+                    .unwrap_or(WeaponStats::UNARMED);
+                // Experienced fighters hit harder
+                if let Some(stats) = stats.get(entity) {
+                    weapon.damage = (weapon.damage as f32 * stats.damage_scale()).round() as i32;
+                }
+
+                // Ranged weapons fire a projectile, which hits whatever it runs into.
+                if let Some(kind) = weapon.projectile {
+                    let dir = Vec2::<f32>::from(ori.0)
+                        .try_normalized()
+                        .unwrap_or(Vec2::unit_y());
+                    let aim = (Vec3::from(dir) + Vec3::unit_z() * MUZZLE_LIFT).normalized();
+                    let from_player = player_behind(entity, &uids, &players, &agents).is_some();
+                    fired_projectiles.0.push(FiredProjectile {
+                        pos: pos.0 + Vec3::unit_z() * MUZZLE_HEIGHT + Vec3::from(dir) * MUZZLE_DIST,
+                        vel: aim * kind.speed(),
+                        projectile: Projectile::new(
+                            kind,
+                            uid,
+                            from_player,
+                            weapon.damage,
+                            weapon.strength,
+                        ),
+                    });
+                    attacking.applied = true;
+                    continue;
+                }
+
+                let mut blocked = None;
                 for (b, pos_b, stat_b, mut vel_b) in
                     (&entities, &positions, &mut stats, &mut velocities).join()
                 {
-                    // Check if it is a hit
+                    // Check if it is a hit. Members of a group can't hurt each other.
                     if entity != b
-                        && !stat_b.is_dead
-                        && pos.0.distance_squared(pos_b.0) < 50.0
-                        && ori.0.angle_between(pos_b.0 - pos.0).to_degrees() < 70.0
+                        && !Group::same(groups.get(entity), groups.get(b))
+                        && life_states.get(b).map_or(true, LifeState::is_alive)
+                        && pos.0.distance(pos_b.0) < weapon.reach
+                        && ori.0.angle_between(pos_b.0 - pos.0).to_degrees() < weapon.arc
                     {
-                        // Deal damage
-                        stat_b.hp.change_by(-10, HealthSource::Attack { by: uid }); // TODO: variable damage and weapon
-                        vel_b.0 += (pos_b.0 - pos.0).normalized() * 10.0;
-                        vel_b.0.z = 15.0;
+                        // Players, and their pets, only hurt players where the rules allow it.
+                        // Pets whose owner is gone are judged like a player without a flag.
+                        let player = player_behind(entity, &uids, &players, &agents);
+                        if let (Some(player), Some(_)) = (player, players.get(b)) {
+                            let (player_pos, player_flag) =
+                                fighters.get(&player).cloned().unwrap_or((pos.0, false));
+                            if let Err(reason) = pvp_rules.check(
+                                player_pos,
+                                player_flag,
+                                pos_b.0,
+                                pvp_flags.get(b).is_some(),
+                            ) {
+                                // Players only get told about their own attacks
+                                if players.get(entity).is_some() {
+                                    blocked = Some(reason);
+                                }
+                                continue;
+                            }
+                        }
+
+                        // Deal damage, mitigated by the target's armor
+                        let defense = equipments
+                            .get(b)
+                            .map(|equipment| equipment.defense())
+                            .unwrap_or(0);
+                        stat_b.hp.change_by(
+                            -weapon.damage_against(defense),
+                            HealthSource::Attack { by: uid },
+                        );
+
+                        // Stronger weapons push further, and lighter entities get pushed further
+                        let push = KNOCKBACK_IMPULSE * weapon.strength as f32
+                            / 100.0
+                            / masses.get(b).cloned().unwrap_or_default().0;
+                        vel_b.0 += (pos_b.0 - pos.0).normalized() * push;
+                        vel_b.0.z = (push * 1.5).min(25.0);
                         if let Err(err) = force_updates.insert(b, ForceUpdate) {
                             warn!("Inserting ForceUpdate for an entity failed: {:?}", err);
                         }
+
+                        if let Some(effect) = weapon.effect {
+                            let effect = effect.inflicted_by(uid);
+                            match effects.get_mut(b) {
+                                Some(effects) => effects.add(effect),
+                                None => {
+                                    if let Err(err) = effects.insert(b, Effects(vec![effect])) {
+                                        warn!("Inserting Effects for an entity failed: {:?}", err);
+                                    }
+                                }
+                            }
+                        }
                     }
                 }
+                if let Some(reason) = blocked {
+                    blocked_hits.0.push(BlockedHit {
+                        attacker: uid,
+                        reason,
+                    });
+                }
                 attacking.applied = true;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        comp::{self, pvp::PvpMode, AgentKind, PetCommand},
+        replay::Scenario,
+    };
+    use vek::*;
+
+    /// The health a player has left after a pet of another player bit it once.
+    fn bitten(mode: PvpMode) -> u32 {
+        let mut scenario = Scenario::new(0).with_flat_ground(1);
+        let body = comp::Body::Quadruped(comp::QuadrupedBody {
+            race: comp::actor::Race::Human,
+            body_type: comp::actor::BodyType::Male,
+            pig_head: comp::actor::PigHead::Default,
+            pig_chest: comp::actor::PigChest::Default,
+            pig_leg_l: comp::actor::PigLegL::Default,
+            pig_leg_r: comp::actor::PigLegR::Default,
+        });
+        let owner = scenario.spawn_character("owner", body, Vec3::new(-5.0, 0.0, 0.0));
+        let pet = scenario.spawn_character("pet", body, Vec3::new(0.0, 0.0, 0.0));
+        let victim = scenario.spawn_character("victim", body, Vec3::new(1.0, 0.0, 0.0));
+
+        let state = scenario.state_mut();
+        state.ecs_mut().write_resource::<comp::pvp::PvpRules>().mode = mode;
+        let owner_uid = state.ecs().uid_from_entity(owner).unwrap().into();
+        for player in &[owner, victim] {
+            state.write_component(*player, comp::Player::new("test".to_owned(), None));
+        }
+        let mut agent = comp::Agent::pet(owner_uid);
+        agent.kind = AgentKind::Pet {
+            owner: owner_uid,
+            command: PetCommand::Stay,
+        };
+        state.write_component(pet, agent);
+        state.write_component(pet, comp::phys::Ori(Vec3::unit_x()));
+        state.write_component(pet, comp::Attacking::start());
+
+        scenario.run(1);
+        assert!(scenario.state_mut().take_blocked_hits().is_empty());
+        scenario
+            .state()
+            .read_component_cloned::<comp::Stats>(victim)
+            .unwrap()
+            .hp
+            .get_current()
+    }
+
+    #[test]
+    fn pets_follow_the_pvp_rules() {
+        let full = comp::Stats::default().hp.get_current();
+        assert_eq!(bitten(PvpMode::Off), full);
+        assert!(bitten(PvpMode::On) < full);
+    }
+}
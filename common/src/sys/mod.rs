@@ -1,8 +1,10 @@
 pub mod actions;
 pub mod agent;
 pub mod animation;
+pub mod body;
 pub mod inputs;
 pub mod phys;
+pub mod projectile;
 mod stats;
 
 // External
@@ -12,13 +14,19 @@ use specs::DispatcherBuilder;
 const AGENT_SYS: &str = "agent_sys";
 const INPUTS_SYS: &str = "inputs_sys";
 const ACTIONS_SYS: &str = "actions_sys";
+const BODY_SYS: &str = "body_sys";
 const PHYS_SYS: &str = "phys_sys";
+const PROJECTILE_SYS: &str = "projectile_sys";
 const ANIMATION_SYS: &str = "animation_sys";
 const STATS_SYS: &str = "stats_sys";
 
+/// Add the systems every `State` runs. Never add thread-local systems here, `State` relies on
+/// there being none to move between threads.
 pub fn add_local_systems(dispatch_builder: &mut DispatcherBuilder) {
     dispatch_builder.add(agent::Sys, AGENT_SYS, &[]);
-    dispatch_builder.add(phys::Sys, PHYS_SYS, &[]);
+    dispatch_builder.add(body::Sys::default(), BODY_SYS, &[]);
+    dispatch_builder.add(phys::Sys, PHYS_SYS, &[BODY_SYS]);
+    dispatch_builder.add(projectile::Sys, PROJECTILE_SYS, &[PHYS_SYS]);
     dispatch_builder.add(actions::Sys, ACTIONS_SYS, &[]);
     dispatch_builder.add(inputs::Sys, INPUTS_SYS, &[]);
     dispatch_builder.add(animation::Sys, ANIMATION_SYS, &[]);
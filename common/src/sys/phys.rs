@@ -1,29 +1,41 @@
 use crate::{
     comp::{
-        phys::{Pos, Vel},
-        Stats,
+        phys::{Collider, Friction, InFluid, Mass, OnGround, Pos, Vel},
+        HealthSource, LifeState, Stats,
     },
     state::DeltaTime,
-    terrain::TerrainMap,
-    vol::{ReadVol, Vox},
+    terrain::{Block, TerrainMap},
+    util::SpatialHash,
+    vol::ReadVol,
 };
-use specs::{Join, Read, ReadExpect, ReadStorage, System, WriteStorage};
+use log::warn;
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, WriteStorage};
 use vek::*;
 
 // Basic ECS physics system
 pub struct Sys;
 
-const GRAVITY: f32 = 9.81 * 4.0;
+pub const GRAVITY: f32 = 9.81 * 4.0;
 const FRIC_GROUND: f32 = 0.15;
 const FRIC_AIR: f32 = 0.015;
+const FRIC_FLUID: f32 = 0.04;
+// Slightly stronger than gravity, so that swimmers float with their heads above the surface
+const BUOYANCY: f32 = GRAVITY * 1.15;
+// Landing faster than this hurts. A fall of about 8 blocks reaches this speed.
+const FALL_DAMAGE_SPEED: f32 = 25.0;
+const FALL_DAMAGE_PER_SPEED: f32 = 4.0;
+// Entities falling below this height have fallen out of the world
+const VOID_Z: f32 = -64.0;
 
 // Integrates forces, calculates the new velocity based off of the old velocity
 // dt = delta time
 // lv = linear velocity
 // damp = linear damping
+// submersion = fraction of the body in fluid
 // Friction is a type of damping.
-fn integrate_forces(dt: f32, mut lv: Vec3<f32>, damp: f32) -> Vec3<f32> {
+fn integrate_forces(dt: f32, mut lv: Vec3<f32>, damp: f32, submersion: f32) -> Vec3<f32> {
     lv.z -= (GRAVITY * dt).max(-50.0);
+    lv.z += BUOYANCY * submersion * dt;
 
     let mut linear_damp = 1.0 - dt * damp;
 
@@ -38,48 +50,592 @@ fn integrate_forces(dt: f32, mut lv: Vec3<f32>, damp: f32) -> Vec3<f32> {
     lv
 }
 
+/// The highest ledge (in blocks) that an entity on the ground walks up without jumping.
+const STEP_HEIGHT: f32 = 1.0;
+/// The furthest an entity moves along an axis before collisions get checked again. Keeping this
+/// below one block stops fast entities from passing through walls.
+const MAX_STEP_DIST: f32 = 0.4;
+/// The gap left between an entity and a wall it was stopped by, to avoid rounding errors pushing
+/// the entity into the wall.
+const SKIN: f32 = 0.001;
+
+/// The outcome of moving an entity through the terrain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Movement {
+    pub pos: Vec3<f32>,
+    pub vel: Vec3<f32>,
+    pub on_ground: bool,
+    /// The downward speed with which the entity hit the ground, if it landed.
+    pub impact: f32,
+}
+
+/// The bounding box of a collider of the given size placed at `pos`.
+pub fn collider_aabb(pos: Vec3<f32>, size: Vec3<f32>) -> Aabb<f32> {
+    Aabb {
+        min: pos - Vec3::new(size.x / 2.0, size.y / 2.0, 0.0),
+        max: pos + Vec3::new(size.x / 2.0, size.y / 2.0, size.z),
+    }
+}
+
+/// Whether the box overlaps any solid voxel. Voxels that can't be read count as empty.
+fn collides<V: ReadVol<Vox = Block>>(terrain: &V, aabb: Aabb<f32>) -> bool {
+    let min = aabb.min.map(|e| e.floor() as i32);
+    let max = aabb.max.map(|e| e.ceil() as i32 - 1);
+
+    (min.x..=max.x).any(|x| {
+        (min.y..=max.y).any(|y| {
+            (min.z..=max.z).any(|z| {
+                terrain
+                    .get(Vec3::new(x, y, z))
+                    .map(|vox| vox.is_solid())
+                    .unwrap_or(false)
+            })
+        })
+    })
+}
+
+/// How deep a collider is in fluid, sampled along the column of voxels at its centre. Returns
+/// `None` if it isn't in any fluid.
+pub fn in_fluid<V: ReadVol<Vox = Block>>(
+    terrain: &V,
+    pos: Vec3<f32>,
+    size: Vec3<f32>,
+) -> Option<InFluid> {
+    let (bottom, top) = (pos.z, pos.z + size.z);
+    let mut kind = None;
+    let mut depth = 0.0;
+
+    for z in bottom.floor() as i32..top.ceil() as i32 {
+        let fluid = terrain
+            .get(Vec3::new(pos.x.floor() as i32, pos.y.floor() as i32, z))
+            .ok()
+            .and_then(|vox| vox.get_fluid());
+
+        if let Some(fluid) = fluid {
+            // The lowest fluid decides what kind of fluid the entity is in.
+            kind.get_or_insert(fluid);
+            depth += (top.min(z as f32 + 1.0) - bottom.max(z as f32)).max(0.0);
+        }
+    }
+
+    kind.map(|kind| InFluid {
+        submersion: depth / size.z,
+        kind,
+    })
+}
+
+/// Move a collider of the given size through the terrain with velocity `vel` for `dt` seconds.
+/// The movement is split into steps shorter than a block and stopped at solid voxels, one axis at
+/// a time. Entities that started on the ground step up ledges of up to `STEP_HEIGHT`.
+pub fn move_through<V: ReadVol<Vox = Block>>(
+    terrain: &V,
+    mut pos: Vec3<f32>,
+    mut vel: Vec3<f32>,
+    size: Vec3<f32>,
+    was_on_ground: bool,
+    dt: f32,
+) -> Movement {
+    // Entities stuck in the terrain (e.g. because a chunk was loaded around them) get pushed out
+    // upwards.
+    if collides(terrain, collider_aabb(pos, size)) {
+        return Movement {
+            pos: Vec3::new(pos.x, pos.y, pos.z.floor() + 1.0),
+            vel: Vec3::zero(),
+            on_ground: false,
+            impact: 0.0,
+        };
+    }
+
+    let delta = vel * dt;
+    let steps = (delta.map(|e| e.abs()).reduce_partial_max() / MAX_STEP_DIST)
+        .ceil()
+        .max(1.0) as usize;
+    let mut step = delta / steps as f32;
+    let mut on_ground = false;
+    let mut impact = 0.0f32;
+    let mut can_step_up = was_on_ground;
+
+    for _ in 0..steps {
+        // Horizontal movement first, so that entities can step up ledges before falling.
+        for axis in 0..2 {
+            if step[axis] == 0.0 {
+                continue;
+            }
+
+            let mut new_pos = pos;
+            new_pos[axis] += step[axis];
+            if !collides(terrain, collider_aabb(new_pos, size)) {
+                pos = new_pos;
+                continue;
+            }
+
+            // Step up onto the ledge if there's room for the entity above it.
+            if can_step_up {
+                let step_z = (pos.z + 0.01).floor() + STEP_HEIGHT;
+                let lifted = Vec3::new(new_pos.x, new_pos.y, step_z);
+                if !collides(
+                    terrain,
+                    collider_aabb(Vec3::new(pos.x, pos.y, step_z), size),
+                ) && !collides(terrain, collider_aabb(lifted, size))
+                {
+                    pos = lifted;
+                    continue;
+                }
+            }
+
+            // Stop against the face of the voxel in the way.
+            let half = size[axis] / 2.0;
+            pos[axis] = if step[axis] > 0.0 {
+                (new_pos[axis] + half).floor() - half - SKIN
+            } else {
+                (new_pos[axis] - half).floor() + 1.0 + half + SKIN
+            };
+            step[axis] = 0.0;
+            vel[axis] = 0.0;
+        }
+
+        if step.z != 0.0 {
+            let mut new_pos = pos;
+            new_pos.z += step.z;
+            if collides(terrain, collider_aabb(new_pos, size)) {
+                if step.z < 0.0 {
+                    // Land on top of the voxel below.
+                    pos.z = new_pos.z.floor() + 1.0;
+                    on_ground = true;
+                    can_step_up = true;
+                    impact = impact.max(-vel.z);
+                } else {
+                    // Bump into the ceiling.
+                    pos.z = (new_pos.z + size.z).floor() - size.z - SKIN;
+                }
+                step.z = 0.0;
+                vel.z = 0.0;
+            } else {
+                pos = new_pos;
+            }
+        }
+    }
+
+    // Entities resting on the ground without falling are on the ground as well.
+    let on_ground = on_ground
+        || (vel.z <= 0.0 && collides(terrain, collider_aabb(pos - Vec3::unit_z() * 0.05, size)));
+
+    Movement {
+        pos,
+        vel,
+        on_ground,
+        impact,
+    }
+}
+
+/// How hard overlapping entities push each other apart, in blocks per second per block of
+/// overlap.
+const PUSH_STRENGTH: f32 = 10.0;
+/// The cell size of the spatial hash used to find entities that might overlap.
+const ENTITY_CELL_SIZE: f32 = 4.0;
+
+/// An entity taking part in entity-entity collision.
+#[derive(Copy, Clone, Debug)]
+pub struct Solid {
+    pub pos: Vec3<f32>,
+    pub size: Vec3<f32>,
+    pub mass: f32,
+}
+
+impl Solid {
+    fn radius(&self) -> f32 {
+        self.size.x.max(self.size.y) / 2.0
+    }
+}
+
+/// Find overlapping entities and work out how far each of them has to move horizontally to
+/// separate them. Overlaps are resolved in proportion to mass, so lighter entities move further.
+pub fn separate(solids: &[Solid]) -> Vec<Vec2<f32>> {
+    let mut grid = SpatialHash::new(ENTITY_CELL_SIZE);
+    let mut max_radius = 0.0f32;
+    for (i, solid) in solids.iter().enumerate() {
+        grid.insert(Vec2::from(solid.pos), i);
+        max_radius = max_radius.max(solid.radius());
+    }
+
+    let mut pushes = vec![Vec2::zero(); solids.len()];
+    for (i, a) in solids.iter().enumerate() {
+        for &j in grid.query(Vec2::from(a.pos), a.radius() + max_radius) {
+            // Handle each pair only once
+            if j <= i {
+                continue;
+            }
+            let b = &solids[j];
+
+            if a.pos.z >= b.pos.z + b.size.z || b.pos.z >= a.pos.z + a.size.z {
+                continue;
+            }
+
+            let diff = Vec2::from(a.pos - b.pos);
+            let dist = diff.magnitude();
+            let overlap = a.radius() + b.radius() - dist;
+            if overlap <= 0.0 {
+                continue;
+            }
+
+            // Entities at the very same spot get pushed apart along an arbitrary (but
+            // deterministic) direction.
+            let dir = if dist > 0.001 {
+                diff / dist
+            } else {
+                Vec2::unit_x()
+            };
+            let total_mass = a.mass + b.mass;
+            pushes[i] += dir * overlap * b.mass / total_mass;
+            pushes[j] -= dir * overlap * a.mass / total_mass;
+        }
+    }
+
+    pushes
+}
+
 impl<'a> System<'a> for Sys {
     type SystemData = (
+        Entities<'a>,
         ReadExpect<'a, TerrainMap>,
         Read<'a, DeltaTime>,
-        ReadStorage<'a, Stats>,
+        WriteStorage<'a, Stats>,
+        ReadStorage<'a, LifeState>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Friction>,
+        ReadStorage<'a, Mass>,
         WriteStorage<'a, Pos>,
         WriteStorage<'a, Vel>,
+        WriteStorage<'a, OnGround>,
+        WriteStorage<'a, InFluid>,
     );
 
-    fn run(&mut self, (terrain, dt, stats, mut positions, mut velocities): Self::SystemData) {
-        for (stats, pos, vel) in (&stats, &mut positions, &mut velocities).join() {
-            // Disable while dead TODO: Replace with client states
-            if stats.is_dead {
+    fn run(
+        &mut self,
+        (
+            entities,
+            terrain,
+            dt,
+            mut stats,
+            life_states,
+            colliders,
+            frictions,
+            masses,
+            mut positions,
+            mut velocities,
+            mut on_grounds,
+            mut in_fluids,
+        ): Self::SystemData,
+    ) {
+        for (entity, pos, vel) in (&entities, &mut positions, &mut velocities).join() {
+            // Only living things move
+            let alive = life_states.get(entity).map_or(true, LifeState::is_alive);
+            if stats.get(entity).is_none() || !alive {
                 continue;
             }
 
-            let on_ground = terrain
-                .get((pos.0 - Vec3::unit_z() * 0.1).map(|e| e.floor() as i32))
-                .map(|vox| !vox.is_empty())
-                .unwrap_or(false)
-                && vel.0.z <= 0.0;
+            let size = colliders.get(entity).cloned().unwrap_or_default().size;
+
+            // Movement and collision with terrain
+            let movement = move_through(
+                &*terrain,
+                pos.0,
+                vel.0,
+                size,
+                on_grounds.get(entity).is_some(),
+                dt.0,
+            );
+            pos.0 = movement.pos;
+
+            // Fall damage
+            if movement.impact > FALL_DAMAGE_SPEED {
+                let damage = ((movement.impact - FALL_DAMAGE_SPEED) * FALL_DAMAGE_PER_SPEED) as i32;
+                if let Some(stats) = stats.get_mut(entity) {
+                    stats.hp.change_by(-damage, HealthSource::Fall);
+                }
+            }
+
+            // Falling out of the world is fatal
+            if pos.0.z < VOID_Z {
+                if let Some(stats) = stats.get_mut(entity) {
+                    stats.hp.set_to(0, HealthSource::Void);
+                }
+            }
 
-            // Movement
-            pos.0 += vel.0 * dt.0;
+            let fluid = in_fluid(&*terrain, pos.0, size);
+            let submersion = fluid.map(|fluid| fluid.submersion).unwrap_or(0.0);
 
             // Integrate forces
-            // Friction is assumed to be a constant dependent on location
-            let friction = 50.0 * if on_ground { FRIC_GROUND } else { FRIC_AIR };
-            vel.0 = integrate_forces(dt.0, vel.0, friction);
-
-            // Basic collision with terrain
-            let mut i = 0.0;
-            while terrain
-                .get(pos.0.map(|e| e.floor() as i32))
-                .map(|vox| !vox.is_empty())
-                .unwrap_or(false)
-                && i < 6000.0 * dt.0
-            {
-                pos.0.z += 0.0025;
-                vel.0.z = 0.0;
-                i += 1.0;
+            // Friction is assumed to be a constant dependent on location, scaled by how well the
+            // entity grips the ground. Fluids add drag on top of that.
+            let friction =
+                50.0 * if movement.on_ground {
+                    FRIC_GROUND * frictions.get(entity).cloned().unwrap_or_default().0
+                } else {
+                    FRIC_AIR
+                } + 50.0 * FRIC_FLUID * submersion;
+            vel.0 = integrate_forces(dt.0, movement.vel, friction, submersion);
+
+            if let Some(fluid) = fluid {
+                if let Err(err) = in_fluids.insert(entity, fluid) {
+                    warn!("Inserting InFluid for an entity failed: {:?}", err);
+                }
+            } else {
+                in_fluids.remove(entity);
+            }
+
+            if movement.on_ground {
+                if let Err(err) = on_grounds.insert(entity, OnGround) {
+                    warn!("Inserting OnGround for an entity failed: {:?}", err);
+                }
+            } else {
+                on_grounds.remove(entity);
+            }
+        }
+
+        // Push overlapping entities apart
+        let (solid_entities, solids): (Vec<_>, Vec<_>) =
+            (&entities, &stats, &positions, &velocities)
+                .join()
+                .filter(|(entity, _, _, _)| {
+                    life_states.get(*entity).map_or(true, LifeState::is_alive)
+                })
+                .map(|(entity, _, pos, _)| {
+                    let solid = Solid {
+                        pos: pos.0,
+                        size: colliders.get(entity).cloned().unwrap_or_default().size,
+                        mass: masses.get(entity).cloned().unwrap_or_default().0,
+                    };
+                    (entity, solid)
+                })
+                .unzip();
+
+        for (entity, push) in solid_entities.into_iter().zip(separate(&solids)) {
+            if push != Vec2::zero() {
+                if let Some(vel) = velocities.get_mut(entity) {
+                    vel.0 += Vec3::from(push) * PUSH_STRENGTH;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        terrain::{FluidKind, WATER_KIND},
+        vol::WriteVol,
+        volumes::dyna::Dyna,
+    };
+
+    const SIZE: Vec3<f32> = Vec3 {
+        x: 0.9,
+        y: 0.9,
+        z: 1.9,
+    };
+
+    /// A 16x16x16 volume with a solid floor at z = 0 and the given voxels filled in.
+    fn terrain(solid: &[Vec3<i32>]) -> Dyna<Block, ()> {
+        let mut terrain = Dyna::filled(Vec3::broadcast(16), Block::empty(), ());
+        let stone = Block::new(1, Rgb::broadcast(128));
+        for x in 0..16 {
+            for y in 0..16 {
+                terrain.set(Vec3::new(x, y, 0), stone).unwrap();
             }
         }
+        for pos in solid {
+            terrain.set(*pos, stone).unwrap();
+        }
+        terrain
+    }
+
+    /// A wall across the y axis at the given x coordinate, `height` blocks high.
+    fn wall(x: i32, height: i32) -> Vec<Vec3<i32>> {
+        (0..16)
+            .flat_map(|y| (1..=height).map(move |z| Vec3::new(x, y, z)))
+            .collect()
+    }
+
+    fn solid(x: f32, y: f32, mass: f32) -> Solid {
+        Solid {
+            pos: Vec3::new(x, y, 1.0),
+            size: SIZE,
+            mass,
+        }
+    }
+
+    #[test]
+    fn separates_overlapping_entities() {
+        let pushes = separate(&[
+            solid(0.0, 0.0, 60.0),
+            solid(0.5, 0.0, 20.0),
+            solid(10.0, 0.0, 60.0),
+        ]);
+
+        // The lighter entity gets pushed three times as far
+        assert!(pushes[0].x < 0.0 && pushes[1].x > 0.0);
+        assert!((pushes[1].x + pushes[0].x * 3.0).abs() < 0.0001);
+        assert!((pushes[1].x - pushes[0].x - 0.4).abs() < 0.0001);
+        assert_eq!(pushes[2], Vec2::zero());
+    }
+
+    #[test]
+    fn stacked_entities_do_not_push() {
+        let mut above = solid(0.0, 0.0, 60.0);
+        above.pos.z += SIZE.z;
+        let pushes = separate(&[solid(0.0, 0.0, 60.0), above]);
+        assert_eq!(pushes, vec![Vec2::zero(); 2]);
+    }
+
+    #[test]
+    fn fluid_is_not_solid() {
+        let water = Block::new(WATER_KIND, Rgb::new(100, 150, 255));
+        let mut terrain = terrain(&[]);
+        for z in 1..4 {
+            terrain.set(Vec3::new(8, 8, z), water).unwrap();
+        }
+
+        let m = move_through(
+            &terrain,
+            Vec3::new(8.5, 8.5, 5.0),
+            Vec3::new(0.0, 0.0, -2.0),
+            SIZE,
+            false,
+            1.0,
+        );
+        assert_eq!(m.pos.z, 3.0);
+        assert!(!m.on_ground);
+
+        // Water up to z = 4, so 1 of the 1.9 blocks of height are submerged
+        let fluid = in_fluid(&terrain, m.pos, SIZE).unwrap();
+        assert_eq!(fluid.kind, FluidKind::Water);
+        assert!((fluid.submersion - 1.0 / 1.9).abs() < 0.0001);
+        assert!(in_fluid(&terrain, Vec3::new(2.5, 2.5, 1.0), SIZE).is_none());
+    }
+
+    #[test]
+    fn lands_on_ground() {
+        let terrain = terrain(&[]);
+        let m = move_through(
+            &terrain,
+            Vec3::new(8.0, 8.0, 5.0),
+            Vec3::new(0.0, 0.0, -20.0),
+            SIZE,
+            false,
+            1.0,
+        );
+        assert_eq!(m.pos, Vec3::new(8.0, 8.0, 1.0));
+        assert_eq!(m.vel, Vec3::zero());
+        assert!(m.on_ground);
+    }
+
+    #[test]
+    fn landing_reports_impact() {
+        let terrain = terrain(&[]);
+        let m = move_through(
+            &terrain,
+            Vec3::new(8.0, 8.0, 1.5),
+            Vec3::new(0.0, 0.0, -30.0),
+            SIZE,
+            false,
+            0.1,
+        );
+        assert_eq!(m.impact, 30.0);
+
+        let m = move_through(&terrain, m.pos, m.vel, SIZE, m.on_ground, 0.1);
+        assert_eq!(m.impact, 0.0);
+    }
+
+    #[test]
+    fn airborne_is_not_on_ground() {
+        let terrain = terrain(&[]);
+        let m = move_through(
+            &terrain,
+            Vec3::new(8.0, 8.0, 5.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            SIZE,
+            false,
+            0.1,
+        );
+        assert!(!m.on_ground);
+        assert!((m.pos.z - 4.9).abs() < 0.0001);
+    }
+
+    #[test]
+    fn no_tunneling_through_walls() {
+        let terrain = terrain(&wall(8, 3));
+        let m = move_through(
+            &terrain,
+            Vec3::new(3.0, 8.0, 1.0),
+            Vec3::new(500.0, 0.0, 0.0),
+            SIZE,
+            true,
+            1.0,
+        );
+        assert!(m.pos.x + SIZE.x / 2.0 <= 8.0);
+        assert!(m.pos.x > 7.0);
+        assert_eq!(m.vel.x, 0.0);
+    }
+
+    #[test]
+    fn bumps_into_ceiling() {
+        let ceiling = (0..16)
+            .flat_map(|x| (0..16).map(move |y| Vec3::new(x, y, 4)))
+            .collect::<Vec<_>>();
+        let terrain = terrain(&ceiling);
+        let m = move_through(
+            &terrain,
+            Vec3::new(8.0, 8.0, 1.0),
+            Vec3::new(0.0, 0.0, 30.0),
+            SIZE,
+            true,
+            1.0,
+        );
+        assert!(m.pos.z + SIZE.z <= 4.0);
+        assert!(m.pos.z > 2.0);
+        assert_eq!(m.vel.z, 0.0);
+    }
+
+    #[test]
+    fn steps_up_ledges() {
+        let terrain = terrain(&wall(8, 1));
+        let m = move_through(
+            &terrain,
+            Vec3::new(6.5, 8.0, 1.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            SIZE,
+            true,
+            0.5,
+        );
+        assert_eq!(m.pos.z, 2.0);
+        assert!((m.pos.x - 8.5).abs() < 0.0001);
+        assert!(m.on_ground);
+    }
+
+    #[test]
+    fn no_step_up_while_airborne_or_onto_high_walls() {
+        let low = terrain(&wall(8, 1));
+        let m = move_through(
+            &low,
+            Vec3::new(6.5, 8.0, 1.5),
+            Vec3::new(4.0, 0.0, 0.0),
+            SIZE,
+            false,
+            0.5,
+        );
+        assert!(m.pos.x + SIZE.x / 2.0 <= 8.0);
+
+        let high = terrain(&wall(8, 2));
+        let m = move_through(
+            &high,
+            Vec3::new(6.5, 8.0, 1.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            SIZE,
+            true,
+            0.5,
+        );
+        assert!(m.pos.x + SIZE.x / 2.0 <= 8.0);
+        assert_eq!(m.pos.z, 1.0);
     }
 }
@@ -0,0 +1,367 @@
+use crate::{
+    comp::{
+        phys::{Collider, ForceUpdate, Mass, Ori, Pos, Vel},
+        pvp::{player_behind, BlockedHit, BlockedHits, PvpRules},
+        Agent, Equipment, Group, HealthSource, LifeState, Player, Projectile, PvpFlag, Stats,
+    },
+    inventory::item::mitigate,
+    ray::Ray,
+    state::{DeltaTime, Uid},
+    sys::phys::{collider_aabb, GRAVITY},
+    terrain::{Block, TerrainMap},
+};
+use fxhash::FxHashMap;
+use log::warn;
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, Write, WriteStorage};
+use vek::*;
+
+// Projectiles that haven't hit anything after this many seconds get removed
+const LIFETIME: f32 = 10.0;
+// How far (in blocks) a projectile can pass by an entity and still hit it
+const PROJECTILE_RADIUS: f32 = 0.2;
+// The knock-back impulse of a projectile with 100 strength
+const KNOCKBACK_IMPULSE: f32 = 300.0;
+
+/// Where the segment from `from` to `to` enters the box, as a fraction of its length, if it
+/// passes through it at all. Segments starting inside the box enter it at 0.
+pub fn segment_hits(from: Vec3<f32>, to: Vec3<f32>, aabb: Aabb<f32>) -> Option<f32> {
+    let dir = to - from;
+    let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+    for i in 0..3 {
+        if dir[i].abs() < 1e-6 {
+            if from[i] < aabb.min[i] || from[i] > aabb.max[i] {
+                return None;
+            }
+        } else {
+            let t0 = (aabb.min[i] - from[i]) / dir[i];
+            let t1 = (aabb.max[i] - from[i]) / dir[i];
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+            if t_min > t_max {
+                return None;
+            }
+        }
+    }
+    Some(t_min)
+}
+
+/// Moves projectiles and resolves what they hit.
+pub struct Sys;
+
+impl<'a> System<'a> for Sys {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        ReadExpect<'a, TerrainMap>,
+        Read<'a, PvpRules>,
+        Write<'a, BlockedHits>,
+        ReadStorage<'a, Uid>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Equipment>,
+        WriteStorage<'a, Projectile>,
+        WriteStorage<'a, Pos>,
+        WriteStorage<'a, Vel>,
+        WriteStorage<'a, Ori>,
+        WriteStorage<'a, Stats>,
+        ReadStorage<'a, LifeState>,
+        ReadStorage<'a, Group>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, PvpFlag>,
+        ReadStorage<'a, Agent>,
+        WriteStorage<'a, ForceUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            dt,
+            terrain,
+            pvp_rules,
+            mut blocked_hits,
+            uids,
+            colliders,
+            masses,
+            equipments,
+            mut projectiles,
+            mut positions,
+            mut velocities,
+            mut orientations,
+            mut stats,
+            life_states,
+            groups,
+            players,
+            pvp_flags,
+            agents,
+            mut force_updates,
+        ): Self::SystemData,
+    ) {
+        let targets = (&entities, &uids, &positions, &stats)
+            .join()
+            .filter(|(entity, _, _, _)| life_states.get(*entity).map_or(true, LifeState::is_alive))
+            .map(|(entity, &uid, pos, _)| {
+                let aabb = collider_aabb(
+                    pos.0,
+                    colliders.get(entity).cloned().unwrap_or_default().size,
+                );
+                let aabb = Aabb {
+                    min: aabb.min - PROJECTILE_RADIUS,
+                    max: aabb.max + PROJECTILE_RADIUS,
+                };
+                (entity, u64::from(uid), pos.0, aabb, groups.get(entity))
+            })
+            .collect::<Vec<_>>();
+
+        // Every entity by `Uid`, since shooters may have died since they fired
+        let entity_from_uid = (&entities, &uids)
+            .join()
+            .map(|(entity, &uid)| (u64::from(uid), entity))
+            .collect::<FxHashMap<_, _>>();
+        // Where players are and whether they want to fight, by `Uid`, to judge hits on players
+        let fighters = (&entities, &uids, &positions, &players)
+            .join()
+            .map(|(entity, &uid, pos, _)| {
+                (u64::from(uid), (pos.0, pvp_flags.get(entity).is_some()))
+            })
+            .collect::<FxHashMap<_, _>>();
+
+        let mut hits = Vec::new();
+        for (projectile, pos, vel, ori) in (
+            &mut projectiles,
+            &mut positions,
+            &mut velocities,
+            &mut orientations,
+        )
+            .join()
+        {
+            if projectile.done {
+                continue;
+            }
+
+            projectile.age += dt.0;
+            if projectile.age > LIFETIME {
+                projectile.done = true;
+                continue;
+            }
+
+            vel.0.z -= GRAVITY * projectile.kind.gravity() * dt.0;
+            let next = pos.0 + vel.0 * dt.0;
+            let travel = (next - pos.0).magnitude();
+            if travel == 0.0 {
+                continue;
+            }
+            let dir = (next - pos.0) / travel;
+
+            // Stop at the first solid block in the way...
+            let (dist, block) = Ray::new(&*terrain, pos.0, next, |block: &Block| block.is_solid())
+                .ignore_error()
+                .cast();
+            let hit_terrain = match block {
+                Ok(Some(_)) => dist <= travel,
+                _ => false,
+            };
+            let end = if hit_terrain {
+                pos.0 + dir * dist
+            } else {
+                next
+            };
+
+            // ...unless the projectile hits an entity first. Projectiles fly past the owner and
+            // the members of its group.
+            let owner = u64::from(projectile.owner);
+            let owner_entity = entity_from_uid.get(&owner).cloned();
+            let owner_group = owner_entity.and_then(|owner| groups.get(owner));
+            // The projectile hits whatever it runs into first.
+            let target = targets
+                .iter()
+                .filter(|(_, uid, _, _, group)| *uid != owner && !Group::same(owner_group, *group))
+                .filter_map(|target| Some((target, segment_hits(pos.0, end, target.3)?)))
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(target, _)| target);
+
+            if let Some((target, _, target_pos, _, _)) = target {
+                // Projectiles of players and their pets stop harmlessly at players the rules
+                // forbid them to fight, even if the shooter died since. Projectiles of players
+                // that are gone are judged like those of a player without a flag.
+                let shooter = match owner_entity {
+                    Some(owner) => player_behind(owner, &uids, &players, &agents)
+                        .map(|player| fighters.get(&player).cloned().unwrap_or((pos.0, false))),
+                    None if projectile.from_player => Some((pos.0, false)),
+                    None => None,
+                };
+                let blocked = match shooter {
+                    Some((shooter_pos, shooter_flag)) if players.get(*target).is_some() => {
+                        pvp_rules
+                            .check(
+                                shooter_pos,
+                                shooter_flag,
+                                *target_pos,
+                                pvp_flags.get(*target).is_some(),
+                            )
+                            .err()
+                    }
+                    _ => None,
+                };
+                match blocked {
+                    Some(reason) => blocked_hits.0.push(BlockedHit {
+                        attacker: projectile.owner,
+                        reason,
+                    }),
+                    None => hits.push((*target, projectile.clone(), dir)),
+                }
+                projectile.done = true;
+            } else if hit_terrain {
+                pos.0 = end;
+                vel.0 = Vec3::zero();
+                projectile.done = true;
+            } else {
+                pos.0 = next;
+                ori.0 = dir;
+            }
+        }
+
+        for (target, projectile, dir) in hits {
+            let defense = equipments
+                .get(target)
+                .map(|equipment| equipment.defense())
+                .unwrap_or(0);
+            if let Some(stats) = stats.get_mut(target) {
+                stats.hp.change_by(
+                    -mitigate(projectile.damage, defense),
+                    HealthSource::Attack {
+                        by: projectile.owner,
+                    },
+                );
+            }
+
+            if let Some(vel) = velocities.get_mut(target) {
+                vel.0 += dir * KNOCKBACK_IMPULSE * projectile.strength as f32
+                    / 100.0
+                    / masses.get(target).cloned().unwrap_or_default().0;
+                if let Err(err) = force_updates.insert(target, ForceUpdate) {
+                    warn!("Inserting ForceUpdate for an entity failed: {:?}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        comp::{
+            self,
+            projectile::{FiredProjectile, FiredProjectiles, ProjectileKind},
+            pvp::PvpMode,
+        },
+        replay::Scenario,
+    };
+
+    #[test]
+    fn segments_hit_boxes() {
+        let aabb = Aabb {
+            min: Vec3::new(4.0, -0.5, 0.0),
+            max: Vec3::new(5.0, 0.5, 2.0),
+        };
+        let from = Vec3::new(0.0, 0.0, 1.0);
+
+        assert_eq!(
+            segment_hits(from, Vec3::new(10.0, 0.0, 1.0), aabb),
+            Some(0.4)
+        );
+        // Too short
+        assert_eq!(segment_hits(from, Vec3::new(3.0, 0.0, 1.0), aabb), None);
+        // Passes over it
+        assert_eq!(segment_hits(from, Vec3::new(10.0, 0.0, 3.0), aabb), None);
+        // Starts inside
+        assert_eq!(
+            segment_hits(Vec3::new(4.5, 0.0, 1.0), Vec3::new(4.6, 0.0, 1.0), aabb),
+            Some(0.0)
+        );
+
+        // Boxes behind others get entered later, however big they are
+        let big = Aabb {
+            min: Vec3::new(5.5, -10.0, -10.0),
+            max: Vec3::new(6.5, 10.0, 10.0),
+        };
+        let to = Vec3::new(10.0, 0.0, 1.0);
+        assert!(segment_hits(from, to, aabb) < segment_hits(from, to, big));
+    }
+
+    /// The health a player has left after getting shot by a character that died right after
+    /// firing, and the number of hits the rules prevented. The shooter is a player if `player` is
+    /// set, and its entity is deleted entirely if `gone` is set.
+    fn shot_by_the_dead(mode: PvpMode, player: bool, gone: bool) -> (u32, usize) {
+        let mut scenario = Scenario::new(0).with_flat_ground(1);
+        let body = comp::Body::Humanoid(comp::HumanoidBody {
+            race: comp::actor::Race::Human,
+            body_type: comp::actor::BodyType::Female,
+            head: comp::actor::Head::Default,
+            chest: comp::actor::Chest::Blue,
+            belt: comp::actor::Belt::Dark,
+            pants: comp::actor::Pants::Green,
+            hand: comp::actor::Hand::Default,
+            foot: comp::actor::Foot::Dark,
+            weapon: comp::actor::Weapon::Bow,
+            shoulder: comp::actor::Shoulder::Default,
+            draw: comp::actor::Draw::Default,
+        });
+        let shooter = scenario.spawn_character("shooter", body, Vec3::new(-10.0, 0.0, 0.0));
+        let victim = scenario.spawn_character("victim", body, Vec3::new(0.0, 0.0, 0.0));
+
+        let state = scenario.state_mut();
+        state.ecs_mut().write_resource::<PvpRules>().mode = mode;
+        state.write_component(victim, Player::new("test".to_owned(), None));
+        if player {
+            state.write_component(shooter, Player::new("test".to_owned(), None));
+        }
+        state.write_component(shooter, LifeState::Dead);
+        let owner = state.ecs().uid_from_entity(shooter).unwrap();
+        if gone {
+            state.ecs_mut().delete_entity_synced(shooter).unwrap();
+        }
+        state
+            .ecs_mut()
+            .write_resource::<FiredProjectiles>()
+            .0
+            .push(FiredProjectile {
+                pos: Vec3::new(-0.5, 0.0, 1.0),
+                vel: Vec3::new(20.0, 0.0, 0.0),
+                projectile: Projectile::new(ProjectileKind::Arrow, owner, player, 10, 100),
+            });
+
+        // The projectile gets created after the first step, and hits during the second one
+        scenario.run(2);
+        let blocked = scenario.state_mut().take_blocked_hits().len();
+        let hp = scenario
+            .state()
+            .read_component_cloned::<Stats>(victim)
+            .unwrap()
+            .hp
+            .get_current();
+        (hp, blocked)
+    }
+
+    #[test]
+    fn projectiles_of_dead_players_follow_the_pvp_rules() {
+        let full = Stats::default().hp.get_current();
+        for &gone in &[false, true] {
+            assert_eq!(shot_by_the_dead(PvpMode::Off, true, gone), (full, 1));
+            let (hp, blocked) = shot_by_the_dead(PvpMode::On, true, gone);
+            assert!(hp < full);
+            assert_eq!(blocked, 0);
+        }
+    }
+
+    #[test]
+    fn projectiles_of_dead_npcs_hit_players() {
+        let full = Stats::default().hp.get_current();
+        for &gone in &[false, true] {
+            let (hp, blocked) = shot_by_the_dead(PvpMode::Off, false, gone);
+            assert!(hp < full);
+            assert_eq!(blocked, 0);
+        }
+    }
+}
@@ -1,25 +1,134 @@
 use crate::{
-    comp::{Dying, HealthSource, Stats},
+    comp::{
+        phys::InFluid, stats::ENERGY_REGEN, Dying, Effects, HealthSource, LifeState, Stats,
+        MAX_BREATH, MAX_ENERGY,
+    },
     state::DeltaTime,
+    terrain::FluidKind,
 };
 use log::warn;
-use specs::{Entities, Join, Read, System, WriteStorage};
+use specs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
 
 // Basic ECS AI agent system
 pub struct Sys;
 
+// Entities with more than this fraction of their body in fluid can't breathe
+const HEAD_SUBMERSION: f32 = 0.9;
+// How much breath is regained per second above the surface
+const BREATH_REGEN: f32 = 2.0;
+// Damage taken every `DROWN_INTERVAL` seconds while out of breath
+const DROWN_DAMAGE: i32 = 10;
+const DROWN_INTERVAL: f32 = 1.0;
+// Damage taken every `LAVA_INTERVAL` seconds while in lava
+const LAVA_DAMAGE: i32 = 20;
+const LAVA_INTERVAL: f32 = 0.5;
+// Health regained every `REGEN_INTERVAL` seconds, once `REGEN_DELAY` seconds passed since the
+// last time the entity got hurt
+const REGEN_HEALTH: i32 = 1;
+const REGEN_INTERVAL: f64 = 1.0;
+const REGEN_DELAY: f64 = 5.0;
+
+// Hurt entities in lava every `LAVA_INTERVAL` seconds. The timer keeps running outside of lava,
+// so that hopping in and out doesn't burn any faster.
+fn burn(stat: &mut Stats, in_lava: bool, dt: f32) {
+    stat.burn = (stat.burn - dt).max(0.0);
+    if in_lava && stat.burn == 0.0 {
+        stat.hp.change_by(-LAVA_DAMAGE, HealthSource::Lava);
+        stat.burn = LAVA_INTERVAL;
+    }
+}
+
 impl<'a> System<'a> for Sys {
     type SystemData = (
         Entities<'a>,
         Read<'a, DeltaTime>,
+        ReadStorage<'a, InFluid>,
         WriteStorage<'a, Stats>,
+        WriteStorage<'a, Effects>,
+        WriteStorage<'a, LifeState>,
         WriteStorage<'a, Dying>,
     );
 
-    fn run(&mut self, (entities, dt, mut stats, mut dyings): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            entities,
+            dt,
+            in_fluids,
+            mut stats,
+            mut effects,
+            mut life_states,
+            mut dyings,
+        ): Self::SystemData,
+    ) {
         for (entity, mut stat) in (&entities, &mut stats).join() {
-            if stat.should_die() && !stat.is_dead {
-                // TODO: Replace is_dead with client states
+            let alive = life_states.get(entity).map_or(true, LifeState::is_alive);
+
+            if alive {
+                // Burning in lava
+                let in_lava = in_fluids
+                    .get(entity)
+                    .map(|in_fluid| in_fluid.kind == FluidKind::Lava)
+                    .unwrap_or(false);
+                burn(&mut stat, in_lava, dt.0);
+
+                // Drowning
+                let under_water = in_fluids
+                    .get(entity)
+                    .map(|in_fluid| in_fluid.submersion > HEAD_SUBMERSION)
+                    .unwrap_or(false);
+
+                if under_water {
+                    stat.breath -= dt.0;
+                    if stat.breath <= 0.0 {
+                        stat.hp.change_by(-DROWN_DAMAGE, HealthSource::Drowning);
+                        stat.breath += DROWN_INTERVAL;
+                    }
+                } else if stat.breath < MAX_BREATH {
+                    stat.breath = (stat.breath + BREATH_REGEN * dt.0).min(MAX_BREATH);
+                }
+
+                stat.energy = (stat.energy + ENERGY_REGEN * dt.0).min(MAX_ENERGY);
+
+                // Status effects (only touched while there are any, to keep them from being
+                // synced every tick)
+                if effects
+                    .get(entity)
+                    .map(|e| !e.0.is_empty())
+                    .unwrap_or(false)
+                {
+                    if let Some(effects) = effects.get_mut(entity) {
+                        let (damage, healing, poisoner) = effects.tick(dt.0);
+                        if damage > 0 {
+                            stat.hp
+                                .change_by(-damage, HealthSource::Poison { by: poisoner });
+                        }
+                        if healing > 0
+                            && stat.hp.get_current() > 0
+                            && stat.hp.get_current() < stat.hp.get_maximum()
+                        {
+                            let healed =
+                                healing.min((stat.hp.get_maximum() - stat.hp.get_current()) as i32);
+                            stat.hp.change_by(healed, HealthSource::Heal);
+                        }
+                    }
+                }
+
+                // Passive regeneration
+                let may_regen = match stat.hp.last_change {
+                    Some((amount, time, _)) if amount < 0 => time >= REGEN_DELAY,
+                    Some((_, time, _)) => time >= REGEN_INTERVAL,
+                    None => true,
+                };
+                if may_regen
+                    && stat.hp.get_current() > 0
+                    && stat.hp.get_current() < stat.hp.get_maximum()
+                {
+                    stat.hp.change_by(REGEN_HEALTH, HealthSource::Heal);
+                }
+            }
+
+            if stat.should_die() && alive {
                 if let Err(err) = dyings.insert(
                     entity,
                     Dying {
@@ -34,7 +143,11 @@ impl<'a> System<'a> for Sys {
                 ) {
                     warn!("Inserting Dying for an entity failed: {:?}", err);
                 }
-                stat.is_dead = true;
+                if let Err(err) = life_states.insert(entity, LifeState::Dead) {
+                    warn!("Inserting LifeState for an entity failed: {:?}", err);
+                }
+                // Death cures everything
+                effects.remove(entity);
             }
             if let Some(change) = &mut stat.hp.last_change {
                 change.1 += dt.0 as f64;
@@ -42,3 +155,30 @@ impl<'a> System<'a> for Sys {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lava_burns_at_its_own_pace() {
+        let mut stat = Stats::default();
+        let max = stat.hp.get_current();
+
+        burn(&mut stat, true, 0.1);
+        assert_eq!(stat.hp.get_current(), max - LAVA_DAMAGE as u32);
+
+        // Other changes to health don't reset the burn timer
+        stat.hp.change_by(-1, HealthSource::Poison { by: None });
+        burn(&mut stat, true, 0.1);
+        assert_eq!(stat.hp.get_current(), max - LAVA_DAMAGE as u32 - 1);
+
+        // Leaving the lava for a moment doesn't either
+        burn(&mut stat, false, 0.1);
+        burn(&mut stat, true, 0.1);
+        assert_eq!(stat.hp.get_current(), max - LAVA_DAMAGE as u32 - 1);
+
+        burn(&mut stat, true, 0.2);
+        assert_eq!(stat.hp.get_current(), max - 2 * LAVA_DAMAGE as u32 - 1);
+    }
+}
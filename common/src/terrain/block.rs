@@ -8,6 +8,17 @@ pub struct Block {
     color: [u8; 3],
 }
 
+/// The kind of the blocks that water is made of.
+pub const WATER_KIND: u8 = 3;
+/// The kind of the blocks that lava is made of.
+pub const LAVA_KIND: u8 = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FluidKind {
+    Water,
+    Lava,
+}
+
 impl Block {
     pub fn new(kind: u8, color: Rgb<u8>) -> Self {
         Self {
@@ -29,9 +40,30 @@ impl Block {
             0 => None,
             1 => Some(0.85),
             2 => Some(1.0),
+            WATER_KIND => Some(0.4),
+            LAVA_KIND => Some(0.4),
             _ => unimplemented!(),
         }
     }
+
+    /// The fluid this block is made of, if any.
+    pub fn get_fluid(&self) -> Option<FluidKind> {
+        match self.kind {
+            WATER_KIND => Some(FluidKind::Water),
+            LAVA_KIND => Some(FluidKind::Lava),
+            _ => None,
+        }
+    }
+
+    /// Whether entities can swim through this block.
+    pub fn is_fluid(&self) -> bool {
+        self.get_fluid().is_some()
+    }
+
+    /// Whether entities collide with this block.
+    pub fn is_solid(&self) -> bool {
+        !self.is_empty() && !self.is_fluid()
+    }
 }
 
 impl Vox for Block {
@@ -44,6 +44,10 @@ impl Chonk {
         self.z_offset + (self.sub_chunks.len() as u32 * SUB_CHUNK_HEIGHT) as i32
     }
 
+    pub fn meta(&self) -> &TerrainChunkMeta {
+        &self.meta
+    }
+
     pub fn get_metrics(&self) -> ChonkMetrics {
         ChonkMetrics {
             chonks: 1,
@@ -4,7 +4,11 @@ pub mod chonk;
 pub mod structure;
 
 // Reexports
-pub use self::{biome::BiomeKind, block::Block, structure::Structure};
+pub use self::{
+    biome::BiomeKind,
+    block::{Block, FluidKind, LAVA_KIND, WATER_KIND},
+    structure::Structure,
+};
 
 use crate::{vol::VolSize, volumes::vol_map_2d::VolMap2d};
 use serde_derive::{Deserialize, Serialize};
@@ -31,11 +35,19 @@ pub struct TerrainChunkMeta {
 }
 
 impl TerrainChunkMeta {
+    pub fn new(biome: BiomeKind) -> Self {
+        Self { biome }
+    }
+
     pub fn void() -> Self {
         Self {
             biome: BiomeKind::Void,
         }
     }
+
+    pub fn biome(&self) -> BiomeKind {
+        self.biome
+    }
 }
 
 // Terrain type aliases
@@ -1 +1,3 @@
+pub mod spatial_hash;
 
+pub use spatial_hash::SpatialHash;
@@ -0,0 +1,64 @@
+use fxhash::FxHashMap;
+use vek::*;
+
+/// Buckets items by their horizontal position so that items close to a point can be found
+/// without looking at every item.
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    cells: FxHashMap<Vec2<i32>, Vec<T>>,
+}
+
+impl<T> SpatialHash<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: FxHashMap::default(),
+        }
+    }
+
+    fn cell_key(&self, pos: Vec2<f32>) -> Vec2<i32> {
+        pos.map(|e| (e / self.cell_size).floor() as i32)
+    }
+
+    pub fn insert(&mut self, pos: Vec2<f32>, item: T) {
+        let key = self.cell_key(pos);
+        self.cells.entry(key).or_default().push(item);
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Iterate through all items that might be within `radius` of `pos`. Items further away may be
+    /// returned as well.
+    pub fn query<'a>(&'a self, pos: Vec2<f32>, radius: f32) -> impl Iterator<Item = &'a T> + 'a {
+        let min = self.cell_key(pos - radius);
+        let max = self.cell_key(pos + radius);
+
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| Vec2::new(x, y)))
+            .filter_map(move |key| self.cells.get(&key))
+            .flat_map(|cell| cell.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_finds_nearby_items() {
+        let mut hash = SpatialHash::new(4.0);
+        hash.insert(Vec2::new(1.0, 1.0), 'a');
+        hash.insert(Vec2::new(-1.0, 3.5), 'b');
+        hash.insert(Vec2::new(20.0, 1.0), 'c');
+
+        let mut found = hash.query(Vec2::new(0.0, 0.0), 2.0).collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, vec![&'a', &'b']);
+        assert_eq!(
+            hash.query(Vec2::new(20.0, 2.0), 1.0).collect::<Vec<_>>(),
+            vec![&'c']
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use common::{
+    msg::{ClientMsg, ServerMsg, PROTOCOL_VERSION},
+    net::{
+        capture::{read_capture, CaptureEntry, Direction},
+        PostBox,
+    },
+};
+use log::{error, info, warn};
+use std::{
+    env,
+    net::{SocketAddr, ToSocketAddrs},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:59003";
+
+const USAGE: &str = "\
+Usage:
+    netcap-cli print <capture> [--server]   Pretty-print a capture. Pass --server for captures
+                                            recorded by the server.
+    netcap-cli replay <capture> [address]   Replay the messages of a client capture against a
+                                            server (default: 127.0.0.1:59003).";
+
+fn main() {
+    // Init logging
+    pretty_env_logger::init();
+
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        ["print", path] => print(path, false),
+        ["print", path, "--server"] => print(path, true),
+        ["replay", path] => replay(path, DEFAULT_ADDR),
+        ["replay", path, addr] => replay(path, addr),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+fn load(path: &str) -> Vec<CaptureEntry> {
+    match read_capture(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("Failed to read capture '{}': {:?}", path, err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Describe a client message, leaving out bulky data.
+fn describe_client_msg(msg: &ClientMsg) -> String {
+    format!("[{:>2}] {:?}", msg.id(), msg)
+}
+
+/// Describe a server message, leaving out bulky data.
+fn describe_server_msg(msg: &ServerMsg) -> String {
+    let desc = match msg {
+        ServerMsg::InitialSync {
+            entity_uid,
+            server_info,
+            session_token,
+            ..
+        } => format!(
+            "InitialSync {{ entity_uid: {}, server_info: {:?}, session_token: {} }}",
+            entity_uid, server_info, session_token
+        ),
+        ServerMsg::TerrainChunkUpdate { key, .. } => {
+            format!("TerrainChunkUpdate {{ key: {:?} }}", key)
+        }
+        msg => format!("{:?}", msg),
+    };
+    format!("[{:>2}] {}", msg.id(), desc)
+}
+
+fn print(path: &str, recorded_by_server: bool) {
+    for entry in load(path) {
+        let (arrow, client_msg) = match (entry.direction, recorded_by_server) {
+            (Direction::Sent, false) | (Direction::Received, true) => ("C -> S", true),
+            (Direction::Received, false) | (Direction::Sent, true) => ("S -> C", false),
+        };
+
+        let desc = if client_msg {
+            entry
+                .msg::<ClientMsg>()
+                .map(|msg| describe_client_msg(&msg))
+        } else {
+            entry
+                .msg::<ServerMsg>()
+                .map(|msg| describe_server_msg(&msg))
+        }
+        .unwrap_or_else(|err| format!("<undecodable message: {:?}>", err));
+
+        println!("{:>10.3}s  {}  {}", entry.time, arrow, desc);
+    }
+}
+
+fn replay(path: &str, addr: &str) {
+    let addr: SocketAddr = match addr.to_socket_addrs().ok().and_then(|mut a| a.next()) {
+        Some(addr) => addr,
+        None => {
+            error!("Invalid server address '{}'", addr);
+            process::exit(1);
+        }
+    };
+
+    let msgs = load(path)
+        .into_iter()
+        .filter(|entry| entry.direction == Direction::Sent)
+        .filter_map(|entry| match entry.msg::<ClientMsg>() {
+            Ok(msg) => Some((entry.time, msg)),
+            Err(err) => {
+                warn!("Skipping undecodable message: {:?}", err);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut postbox = match PostBox::<ClientMsg, ServerMsg>::to(addr) {
+        Ok(postbox) => postbox,
+        Err(err) => {
+            error!("Failed to connect to {}: {:?}", addr, err);
+            process::exit(1);
+        }
+    };
+    info!("Replaying {} messages against {}", msgs.len(), addr);
+
+    postbox.send_message(ClientMsg::VersionInfo {
+        protocol_version: PROTOCOL_VERSION,
+    });
+
+    let start = Instant::now();
+    let handle_incoming = |postbox: &mut PostBox<ClientMsg, ServerMsg>| {
+        for msg in postbox.new_messages() {
+            println!(
+                "{:>10.3}s  S -> C  {}",
+                start.elapsed().as_secs_f64(),
+                describe_server_msg(&msg)
+            );
+        }
+        if let Some(err) = postbox.error() {
+            error!("Connection failed: {:?}", err);
+            process::exit(1);
+        }
+    };
+
+    for (time, msg) in msgs {
+        // Keep the original timing between messages.
+        while start.elapsed().as_secs_f64() < time {
+            handle_incoming(&mut postbox);
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        println!("{:>10.3}s  C -> S  {}", time, describe_client_msg(&msg));
+        postbox.send_message(msg);
+    }
+
+    // Give the server a moment to answer the last messages.
+    let end = Instant::now();
+    while end.elapsed() < Duration::from_secs(1) {
+        handle_incoming(&mut postbox);
+        thread::sleep(Duration::from_millis(5));
+    }
+}
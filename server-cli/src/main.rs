@@ -1,10 +1,32 @@
-use common::clock::Clock;
+use common::{clock::Clock, comp::pvp::PvpRules};
 use log::info;
-use server::{Event, Input, Server};
-use std::time::Duration;
+use server::{ChatSettings, DeathSettings, Event, Input, Server};
+use std::{env, path::Path, time::Duration};
 
 const TPS: u64 = 30;
 
+/// Set this environment variable to a directory to capture the network traffic of every client.
+const CAPTURE_VAR: &str = "VELOREN_NET_CAPTURE";
+
+const CHAT_LOG: &str = "chat.log";
+/// A list of words to censor in chat, one per line.
+const CHAT_FILTER: &str = "chat_filter.txt";
+
+/// Set this environment variable to `off`, `on` or `opt-in` to choose whether players can hurt each
+/// other.
+const PVP_VAR: &str = "VELOREN_PVP";
+/// A list of regions where players can't hurt each other, one per line as
+/// `<min x> <min y> <max x> <max y> <name>`.
+const SAFE_ZONES: &str = "safe_zones.txt";
+
+/// Set this environment variable to `<x> <y> <z>` to choose where characters spawn.
+const SPAWN_POINT_VAR: &str = "VELOREN_SPAWN_POINT";
+/// Set this environment variable to the fraction (from 0 to 1) of the experience towards their next
+/// level that characters lose when they die.
+const XP_LOSS_VAR: &str = "VELOREN_XP_LOSS";
+/// Set this environment variable to `true` to make characters drop their inventory where they die.
+const DROP_INVENTORY_VAR: &str = "VELOREN_DROP_INVENTORY";
+
 fn main() {
     // Init logging
     pretty_env_logger::init();
@@ -15,7 +37,63 @@ fn main() {
     let mut clock = Clock::new();
 
     // Create server
-    let mut server = Server::new().expect("Failed to create server instance!");
+    let mut chat_settings = ChatSettings {
+        log_path: Some(CHAT_LOG.into()),
+        ..ChatSettings::default()
+    };
+    if Path::new(CHAT_FILTER).exists() {
+        chat_settings = chat_settings
+            .load_banned_words(CHAT_FILTER)
+            .expect("Failed to load the chat filter!");
+    }
+
+    let mut pvp_rules = PvpRules::default();
+    if let Ok(mode) = env::var(PVP_VAR) {
+        pvp_rules.mode = server::pvp::parse_mode(&mode)
+            .unwrap_or_else(|| panic!("Invalid PvP mode '{}'!", mode));
+    }
+    if Path::new(SAFE_ZONES).exists() {
+        pvp_rules.safe_zones =
+            server::pvp::load_safe_zones(SAFE_ZONES).expect("Failed to load the safe zones!");
+    }
+    info!(
+        "PvP mode: {:?}, {} safe zones",
+        pvp_rules.mode,
+        pvp_rules.safe_zones.len()
+    );
+
+    let mut death_settings = DeathSettings::default();
+    if let Ok(point) = env::var(SPAWN_POINT_VAR) {
+        death_settings.spawn_point = server::death::parse_spawn_point(&point)
+            .unwrap_or_else(|| panic!("Invalid spawn point '{}'!", point));
+    }
+    if let Ok(xp_loss) = env::var(XP_LOSS_VAR) {
+        death_settings.xp_loss = xp_loss
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|xp_loss| (0.0..=1.0).contains(xp_loss))
+            .unwrap_or_else(|| panic!("Invalid experience loss '{}'!", xp_loss));
+    }
+    if let Ok(drop_inventory) = env::var(DROP_INVENTORY_VAR) {
+        death_settings.drop_inventory = drop_inventory
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid inventory drop setting '{}'!", drop_inventory));
+    }
+
+    let mut server = Server::new()
+        .expect("Failed to create server instance!")
+        .with_chat_settings(chat_settings)
+        .with_pvp_rules(pvp_rules)
+        .with_death_settings(death_settings);
+
+    if let Ok(dir) = env::var(CAPTURE_VAR) {
+        info!("Capturing network traffic into '{}'", dir);
+        server
+            .record_network(dir)
+            .expect("Failed to start network capture!");
+    }
 
     loop {
         let events = server
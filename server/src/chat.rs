@@ -0,0 +1,217 @@
+use log::warn;
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Moderation settings for the server chat.
+#[derive(Clone, Debug)]
+pub struct ChatSettings {
+    /// The maximum number of characters in a single message.
+    pub max_length: usize,
+    /// How many messages a client may send within `rate_period`.
+    pub rate_limit: usize,
+    /// The period (in seconds) over which `rate_limit` is enforced.
+    pub rate_period: f64,
+    /// Words that get censored in chat messages. Matching ignores case.
+    pub banned_words: Vec<String>,
+    /// A file that every chat message gets appended to.
+    pub log_path: Option<PathBuf>,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            max_length: 256,
+            rate_limit: 5,
+            rate_period: 5.0,
+            banned_words: Vec::new(),
+            log_path: None,
+        }
+    }
+}
+
+impl ChatSettings {
+    /// Add the banned words listed in a file, one per line.
+    pub fn load_banned_words<P: AsRef<Path>>(mut self, path: P) -> Result<Self, io::Error> {
+        self.banned_words.extend(
+            fs::read_to_string(path)?
+                .lines()
+                .map(|line| line.trim())
+                .filter(|word| !word.is_empty())
+                .map(|word| word.to_owned()),
+        );
+        Ok(self)
+    }
+}
+
+/// Keeps track of when a client sent its recent chat messages.
+pub struct RateLimiter {
+    times: VecDeque<f64>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            times: VecDeque::new(),
+        }
+    }
+
+    /// Record a message sent at `time`, unless more than `limit` messages were sent during the
+    /// preceding `period` seconds.
+    pub fn allow(&mut self, time: f64, limit: usize, period: f64) -> bool {
+        while self
+            .times
+            .front()
+            .map(|t| time - t > period)
+            .unwrap_or(false)
+        {
+            self.times.pop_front();
+        }
+
+        if self.times.len() < limit {
+            self.times.push_back(time);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Checks chat messages against the server's chat settings.
+pub struct ChatFilter {
+    settings: ChatSettings,
+    banned_words: HashSet<String>,
+}
+
+impl ChatFilter {
+    pub fn new(settings: ChatSettings) -> Self {
+        Self {
+            banned_words: settings
+                .banned_words
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect(),
+            settings,
+        }
+    }
+
+    /// Check a message sent by a client at `time`. Returns the censored message if it may be
+    /// sent, or the reason it was rejected.
+    pub fn check(
+        &self,
+        limiter: &mut RateLimiter,
+        time: f64,
+        msg: String,
+    ) -> Result<String, String> {
+        if msg.chars().count() > self.settings.max_length {
+            return Err(format!(
+                "Your message is too long (at most {} characters).",
+                self.settings.max_length
+            ));
+        }
+
+        if !limiter.allow(time, self.settings.rate_limit, self.settings.rate_period) {
+            return Err(String::from(
+                "You are sending messages too quickly, slow down.",
+            ));
+        }
+
+        Ok(self.censor(&msg))
+    }
+
+    /// Replace every banned word in the message with asterisks.
+    fn censor(&self, msg: &str) -> String {
+        msg.split(' ')
+            .map(|word| {
+                let bare = word
+                    .trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase();
+                if self.banned_words.contains(&bare) {
+                    "*".repeat(word.chars().count())
+                } else {
+                    word.to_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// An append-only log of all chat messages.
+pub struct ChatLog {
+    file: Option<File>,
+}
+
+impl ChatLog {
+    pub fn none() -> Self {
+        Self { file: None }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Ok(Self {
+            file: Some(OpenOptions::new().create(true).append(true).open(path)?),
+        })
+    }
+
+    pub fn log(&mut self, alias: &str, msg: &str) {
+        if let Some(file) = &mut self.file {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0);
+
+            if let Err(err) = writeln!(file, "[{:.3}] [{}] {}", timestamp, alias, msg) {
+                warn!("Failed to write to the chat log, disabling it: {:?}", err);
+                self.file = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> ChatFilter {
+        ChatFilter::new(ChatSettings {
+            max_length: 16,
+            rate_limit: 2,
+            rate_period: 1.0,
+            banned_words: vec![String::from("Heck")],
+            log_path: None,
+        })
+    }
+
+    #[test]
+    fn censor() {
+        let filter = filter();
+        let mut limiter = RateLimiter::new();
+
+        assert_eq!(
+            filter.check(&mut limiter, 0.0, String::from("oh heck!")),
+            Ok(String::from("oh *****"))
+        );
+        assert!(filter
+            .check(
+                &mut limiter,
+                10.0,
+                String::from("this message is far too long")
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn rate_limit() {
+        let filter = filter();
+        let mut limiter = RateLimiter::new();
+
+        assert!(filter.check(&mut limiter, 0.0, String::from("a")).is_ok());
+        assert!(filter.check(&mut limiter, 0.5, String::from("b")).is_ok());
+        assert!(filter.check(&mut limiter, 0.9, String::from("c")).is_err());
+        assert!(filter.check(&mut limiter, 1.6, String::from("d")).is_ok());
+    }
+}
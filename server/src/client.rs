@@ -1,3 +1,4 @@
+use crate::chat::RateLimiter;
 use common::{
     msg::{ClientMsg, ClientState, RequestStateError, ServerMsg},
     net::PostBox,
@@ -7,8 +8,18 @@ use std::collections::HashMap;
 
 pub struct Client {
     pub client_state: ClientState,
+    /// Whether the client still has to tell its protocol version, which it must do before
+    /// anything else.
+    pub version_pending: bool,
     pub postbox: PostBox<ServerMsg, ClientMsg>,
     pub last_ping: f64,
+    pub session_token: u64,
+    /// The token of the session the client had before reconnecting, if that session couldn't be
+    /// resumed. The pets saved with it come back to the client's character.
+    pub previous_session_token: Option<u64>,
+    /// The player that most recently whispered to this client, used to reply.
+    pub last_whisper_from: Option<EcsEntity>,
+    pub chat_limiter: RateLimiter,
 }
 
 impl Client {
@@ -49,6 +60,10 @@ impl Clients {
         self.clients.get(entity)
     }
 
+    pub fn remove(&mut self, entity: &EcsEntity) -> Option<Client> {
+        self.clients.remove(entity)
+    }
+
     pub fn get_mut<'a>(&'a mut self, entity: &EcsEntity) -> Option<&'a mut Client> {
         self.clients.get_mut(entity)
     }
@@ -71,6 +86,14 @@ impl Clients {
         }
     }
 
+    pub fn notify_registered_if<F: FnMut(EcsEntity) -> bool>(&mut self, msg: ServerMsg, mut f: F) {
+        for (_entity, client) in self.clients.iter_mut().filter(|(e, _)| f(**e)) {
+            if client.client_state != ClientState::Connected {
+                client.notify(msg.clone());
+            }
+        }
+    }
+
     pub fn notify_ingame(&mut self, msg: ServerMsg) {
         for client in self.clients.values_mut() {
             if client.client_state == ClientState::Spectator
@@ -2,17 +2,22 @@
 //! To implement a new command, add an instance of `ChatCommand` to `CHAT_COMMANDS`
 //! and provide a handler function.
 
-use crate::Server;
+use crate::{death, group::GroupError, pets, Server};
 use common::{
     comp,
-    msg::ServerMsg,
+    msg::{ChatMsg, ClientMsg, ServerMsg},
     npc::{get_npc_name, NpcKind},
 };
 use specs::{Builder, Entity as EcsEntity, Join};
 use vek::*;
 
 use lazy_static::lazy_static;
+use log::warn;
 use scan_fmt::scan_fmt;
+
+// How far away (in blocks) pets can be sent to attack something
+const PET_ATTACK_RANGE: f32 = 20.0;
+
 /// Struct representing a command that a user can run from server chat.
 pub struct ChatCommand {
     /// The keyword used to invoke the command, omitting the leading '/'.
@@ -85,6 +90,12 @@ lazy_static! {
             "/kill : Kill yourself",
             handle_kill
         ),
+        ChatCommand::new(
+            "setspawn",
+            "",
+            "/setspawn : Respawn where you are standing after dying",
+            handle_setspawn
+        ),
         ChatCommand::new(
             "pig",
             "{}",
@@ -103,6 +114,72 @@ lazy_static! {
             "/enemy : Spawn a test enemy NPC",
             handle_enemy
         ),
+        ChatCommand::new(
+            "effect",
+            "{} {d} {d}",
+            "/effect <poison|slow|regeneration> <strength> <seconds> : Put a status effect on yourself",
+            handle_effect
+        ),
+        ChatCommand::new(
+            "tell",
+            "{} {}",
+            "/tell <alias> <message> : Send a private message to another player",
+            handle_tell
+        ),
+        ChatCommand::new(
+            "r",
+            "{}",
+            "/r <message> : Reply to the last private message you received",
+            handle_reply
+        ),
+        ChatCommand::new(
+            "invite",
+            "{}",
+            "/invite <alias> : Invite another player to your group",
+            handle_invite
+        ),
+        ChatCommand::new(
+            "accept",
+            "",
+            "/accept : Join the group you were last invited to",
+            handle_accept
+        ),
+        ChatCommand::new(
+            "leave",
+            "",
+            "/leave : Leave your group",
+            handle_leave
+        ),
+        ChatCommand::new(
+            "kick",
+            "{}",
+            "/kick <alias> : Remove a player from the group you lead",
+            handle_kick
+        ),
+        ChatCommand::new(
+            "g",
+            "{}",
+            "/g <message> : Send a message to your group",
+            handle_group_chat
+        ),
+        ChatCommand::new(
+            "tame",
+            "",
+            "/tame : Tame the closest wild animal next to you",
+            handle_tame
+        ),
+        ChatCommand::new(
+            "pet",
+            "{}",
+            "/pet <follow|stay|attack> : Order your pets to follow you, stay put or attack whatever is closest to you",
+            handle_pet
+        ),
+        ChatCommand::new(
+            "pvp",
+            "",
+            "/pvp : Choose whether you want to fight other players, if the server lets you",
+            handle_pvp
+        ),
         ChatCommand::new(
             "help", "", "/help: Display this message", handle_help)
     ];
@@ -127,13 +204,16 @@ fn handle_jump(server: &mut Server, entity: EcsEntity, args: String, action: &Ch
                 }
                 None => server.clients.notify(
                     entity,
-                    ServerMsg::Chat(String::from("Command 'jump' invalid in current state.")),
+                    ServerMsg::Chat(ChatMsg::command(String::from(
+                        "Command 'jump' invalid in current state.",
+                    ))),
                 ),
             }
         }
-        _ => server
-            .clients
-            .notify(entity, ServerMsg::Chat(String::from(action.help_string))),
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
     }
 }
 
@@ -148,9 +228,10 @@ fn handle_goto(server: &mut Server, entity: EcsEntity, args: String, action: &Ch
                 .state
                 .write_component(entity, comp::phys::ForceUpdate);
         }
-        _ => server
-            .clients
-            .notify(entity, ServerMsg::Chat(String::from(action.help_string))),
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
     }
 }
 
@@ -174,9 +255,10 @@ fn handle_alias(server: &mut Server, entity: EcsEntity, args: String, action: &C
                 .get_mut(entity)
                 .map(|player| player.alias = alias);
         }
-        None => server
-            .clients
-            .notify(entity, ServerMsg::Chat(String::from(action.help_string))),
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
     }
 }
 
@@ -202,32 +284,64 @@ fn handle_tp(server: &mut Server, entity: EcsEntity, args: String, action: &Chat
                     }
                     None => server.clients.notify(
                         entity,
-                        ServerMsg::Chat(format!("Unable to teleport to player '{}'!", alias)),
+                        ServerMsg::Chat(ChatMsg::command(format!(
+                            "Unable to teleport to player '{}'!",
+                            alias
+                        ))),
                     ),
                 },
                 None => {
                     server.clients.notify(
                         entity,
-                        ServerMsg::Chat(format!("Player '{}' not found!", alias)),
+                        ServerMsg::Chat(ChatMsg::command(format!("Player '{}' not found!", alias))),
+                    );
+                    server.clients.notify(
+                        entity,
+                        ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
                     );
-                    server
-                        .clients
-                        .notify(entity, ServerMsg::Chat(String::from(action.help_string)));
                 }
             }
         }
-        None => server
-            .clients
-            .notify(entity, ServerMsg::Chat(String::from(action.help_string))),
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
     }
 }
 
-fn handle_pet_pig(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
-    match server
+fn handle_setspawn(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
+    let pos = server
         .state
-        .read_component_cloned::<comp::phys::Pos>(entity)
-    {
-        Some(mut pos) => {
+        .read_component_cloned::<comp::phys::Pos>(entity);
+    match pos {
+        Some(pos) if death::is_alive(&server.state, entity) => {
+            server
+                .state
+                .write_component(entity, comp::player::RespawnPoint(pos.0));
+            server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command(
+                    "You will respawn here from now on.".to_owned(),
+                )),
+            );
+        }
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(
+                "You can't set a respawn point right now!".to_owned(),
+            )),
+        ),
+    }
+}
+
+fn handle_pet_pig(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
+    match (
+        server
+            .state
+            .read_component_cloned::<comp::phys::Pos>(entity),
+        server.state.ecs().uid_from_entity(entity),
+    ) {
+        (Some(mut pos), Some(owner)) => {
             pos.0.x += 1.0; // Temp fix TODO: Solve NaN issue with positions of pets
             server
                 .create_npc(
@@ -235,27 +349,28 @@ fn handle_pet_pig(server: &mut Server, entity: EcsEntity, _args: String, _action
                     get_npc_name(NpcKind::Pig),
                     comp::Body::Quadruped(comp::QuadrupedBody::random()),
                 )
-                .with(comp::Agent::Pet {
-                    target: entity,
-                    offset: Vec2::zero(),
-                })
+                .with(comp::Agent::pet(owner.into()))
                 .build();
-            server
-                .clients
-                .notify(entity, ServerMsg::Chat("Spawned pet!".to_owned()));
+            server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command("Spawned pet!".to_owned())),
+            );
         }
-        None => server
-            .clients
-            .notify(entity, ServerMsg::Chat("You have no position!".to_owned())),
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command("You have no position!".to_owned())),
+        ),
     }
 }
 
 fn handle_pet_wolf(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
-    match server
-        .state
-        .read_component_cloned::<comp::phys::Pos>(entity)
-    {
-        Some(mut pos) => {
+    match (
+        server
+            .state
+            .read_component_cloned::<comp::phys::Pos>(entity),
+        server.state.ecs().uid_from_entity(entity),
+    ) {
+        (Some(mut pos), Some(owner)) => {
             pos.0.x += 1.0; // Temp fix TODO: Solve NaN issue with positions of pets
             server
                 .create_npc(
@@ -263,18 +378,17 @@ fn handle_pet_wolf(server: &mut Server, entity: EcsEntity, _args: String, _actio
                     get_npc_name(NpcKind::Wolf),
                     comp::Body::QuadrupedMedium(comp::QuadrupedMediumBody::random()),
                 )
-                .with(comp::Agent::Pet {
-                    target: entity,
-                    offset: Vec2::zero(),
-                })
+                .with(comp::Agent::pet(owner.into()))
                 .build();
-            server
-                .clients
-                .notify(entity, ServerMsg::Chat("Spawned pet!".to_owned()));
+            server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command("Spawned pet!".to_owned())),
+            );
         }
-        None => server
-            .clients
-            .notify(entity, ServerMsg::Chat("You have no position!".to_owned())),
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command("You have no position!".to_owned())),
+        ),
     }
 }
 
@@ -291,22 +405,330 @@ fn handle_enemy(server: &mut Server, entity: EcsEntity, _args: String, _action:
                     get_npc_name(NpcKind::Humanoid),
                     comp::Body::Humanoid(comp::HumanoidBody::random()),
                 )
-                .with(comp::Agent::Enemy { target: None })
+                .with(comp::Agent::enemy())
                 .build();
-            server
-                .clients
-                .notify(entity, ServerMsg::Chat("Spawned enemy!".to_owned()));
+            server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command("Spawned enemy!".to_owned())),
+            );
         }
-        None => server
-            .clients
-            .notify(entity, ServerMsg::Chat("You have no position!".to_owned())),
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command("You have no position!".to_owned())),
+        ),
+    }
+}
+
+fn handle_effect(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    let (opt_kind, opt_strength, opt_duration) = scan_fmt!(&args, action.arg_fmt, String, i32, f32);
+    let kind = opt_kind.and_then(|name| {
+        comp::EffectKind::ALL
+            .iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(&name))
+            .cloned()
+    });
+    match (kind, opt_strength, opt_duration) {
+        (Some(kind), Some(strength), Some(duration)) => {
+            let effect = comp::Effect::new(kind, strength, duration);
+            let mut effects = server.state.ecs().write_storage::<comp::Effects>();
+            match effects.get_mut(entity) {
+                Some(effects) => effects.add(effect),
+                None => {
+                    if let Err(err) = effects.insert(entity, comp::Effects(vec![effect])) {
+                        warn!("Inserting Effects for an entity failed: {:?}", err);
+                    }
+                }
+            }
+        }
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
     }
 }
 
 fn handle_help(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
     for cmd in CHAT_COMMANDS.iter() {
-        server
-            .clients
-            .notify(entity, ServerMsg::Chat(String::from(cmd.help_string)));
+        server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(cmd.help_string))),
+        );
+    }
+}
+
+fn handle_tell(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    // The message may contain spaces, so only split off the alias.
+    let (alias, text) = match args.find(' ') {
+        Some(i) => (&args[..i], args[i + 1..].trim()),
+        None => (args.as_str(), ""),
+    };
+    if alias.is_empty() || text.is_empty() {
+        server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        );
+        return;
+    }
+
+    let ecs = server.state.ecs();
+    let opt_player = (&ecs.entities(), &ecs.read_storage::<comp::Player>())
+        .join()
+        .find(|(_, player)| player.alias == alias)
+        .map(|(entity, _)| entity);
+    match opt_player {
+        Some(player) => whisper(server, entity, player, text.to_owned()),
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(format!("Player '{}' not found!", alias))),
+        ),
+    }
+}
+
+fn handle_reply(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    let text = args.trim();
+    if text.is_empty() {
+        server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        );
+        return;
+    }
+
+    match server
+        .clients
+        .get(&entity)
+        .and_then(|client| client.last_whisper_from)
+    {
+        Some(player) if server.clients.get(&player).is_some() => {
+            whisper(server, entity, player, text.to_owned())
+        }
+        Some(_) => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(
+                "That player is no longer online.",
+            ))),
+        ),
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(
+                "Nobody has sent you a private message yet.",
+            ))),
+        ),
+    }
+}
+
+/// Send a private message from one player to another.
+fn whisper(server: &mut Server, from: EcsEntity, to: EcsEntity, text: String) {
+    let players = server.state.ecs().read_storage::<comp::Player>();
+    let msg = match (players.get(from), players.get(to)) {
+        (Some(sender), Some(receiver)) => ServerMsg::Chat(ChatMsg::whisper(
+            sender.alias.clone(),
+            receiver.alias.clone(),
+            text,
+        )),
+        _ => return,
+    };
+
+    server.clients.notify(to, msg.clone());
+    if from != to {
+        server.clients.notify(from, msg);
+    }
+    if let Some(client) = server.clients.get_mut(&to) {
+        client.last_whisper_from = Some(from);
+    }
+}
+
+/// The `Uid` of the player with the given alias, if there is one.
+fn find_player_uid(server: &Server, alias: &str) -> Option<u64> {
+    let ecs = server.state.ecs();
+    (&ecs.entities(), &ecs.read_storage::<comp::Player>())
+        .join()
+        .find(|(_, player)| player.alias == alias)
+        .and_then(|(entity, _)| ecs.uid_from_entity(entity))
+        .map(u64::from)
+}
+
+fn handle_invite(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    match scan_fmt!(&args, action.arg_fmt, String) {
+        Some(alias) => match find_player_uid(server, &alias) {
+            Some(uid) => server.handle_group_msg(entity, ClientMsg::GroupInvite(uid)),
+            None => server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command(format!("Player '{}' not found!", alias))),
+            ),
+        },
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
+    }
+}
+
+fn handle_accept(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
+    server.handle_group_msg(entity, ClientMsg::GroupAccept);
+}
+
+fn handle_leave(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
+    server.handle_group_msg(entity, ClientMsg::GroupLeave);
+}
+
+fn handle_kick(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    match scan_fmt!(&args, action.arg_fmt, String) {
+        Some(alias) => match find_player_uid(server, &alias) {
+            Some(uid) => server.handle_group_msg(entity, ClientMsg::GroupKick(uid)),
+            None => server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command(format!("Player '{}' not found!", alias))),
+            ),
+        },
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        ),
+    }
+}
+
+fn handle_group_chat(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    let text = args.trim();
+    if text.is_empty() {
+        server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+        );
+        return;
+    }
+
+    let sender = server
+        .state
+        .read_component_cloned::<comp::Player>(entity)
+        .map(|player| player.alias);
+    match (
+        server.state.read_component_cloned::<comp::Group>(entity),
+        sender,
+    ) {
+        (Some(group), Some(sender)) => server.notify_group(
+            group.id,
+            ServerMsg::Chat(ChatMsg::group(sender, text.to_owned())),
+        ),
+        _ => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(GroupError::NotInGroup.to_string())),
+        ),
     }
 }
+
+/// The `Uid` of the closest entity with stats around a player, if it's not further away than
+/// `max_dist` and passes the filter.
+fn closest_uid(
+    server: &Server,
+    entity: EcsEntity,
+    max_dist: f32,
+    filter: impl Fn(EcsEntity) -> bool,
+) -> Option<u64> {
+    let ecs = server.state.ecs();
+    let pos = ecs.read_storage::<comp::phys::Pos>().get(entity)?.0;
+    (
+        &ecs.entities(),
+        &ecs.read_storage::<comp::phys::Pos>(),
+        &ecs.read_storage::<comp::Stats>(),
+    )
+        .join()
+        .filter(|(other, other_pos, _)| {
+            *other != entity && other_pos.0.distance(pos) <= max_dist && filter(*other)
+        })
+        .min_by(|(_, a, _), (_, b, _)| {
+            a.0.distance(pos)
+                .partial_cmp(&b.0.distance(pos))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .and_then(|(other, _, _)| ecs.uid_from_entity(other))
+        .map(u64::from)
+}
+
+fn handle_tame(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
+    let target = {
+        let agents = server.state.ecs().read_storage::<comp::Agent>();
+        closest_uid(server, entity, pets::TAME_RANGE, |other| {
+            agents
+                .get(other)
+                .map_or(false, |agent| agent.owner().is_none())
+        })
+    };
+    match target {
+        Some(uid) => server.handle_pet_msg(entity, ClientMsg::Tame(uid)),
+        None => server.clients.notify(
+            entity,
+            ServerMsg::Chat(ChatMsg::command(
+                "There's no animal close enough to tame!".to_owned(),
+            )),
+        ),
+    }
+}
+
+fn handle_pet(server: &mut Server, entity: EcsEntity, args: String, action: &ChatCommand) {
+    let command = match scan_fmt!(&args, action.arg_fmt, String)
+        .as_ref()
+        .map(|s| s.as_str())
+    {
+        Some("follow") => comp::PetCommand::Follow,
+        Some("stay") => comp::PetCommand::Stay,
+        Some("attack") => {
+            let target = {
+                let own_pets = pets::pets(&server.state, entity);
+                let players = server.state.ecs().read_storage::<comp::Player>();
+                closest_uid(server, entity, PET_ATTACK_RANGE, |other| {
+                    !own_pets.contains(&other) && players.get(other).is_none()
+                })
+            };
+            match target {
+                Some(uid) => comp::PetCommand::Attack(uid),
+                None => {
+                    server.clients.notify(
+                        entity,
+                        ServerMsg::Chat(ChatMsg::command(
+                            "There's nothing for your pets to attack!".to_owned(),
+                        )),
+                    );
+                    return;
+                }
+            }
+        }
+        _ => {
+            server.clients.notify(
+                entity,
+                ServerMsg::Chat(ChatMsg::command(String::from(action.help_string))),
+            );
+            return;
+        }
+    };
+    server.handle_pet_msg(entity, ClientMsg::PetCommand(command));
+}
+
+fn handle_pvp(server: &mut Server, entity: EcsEntity, _args: String, _action: &ChatCommand) {
+    let mode = server
+        .state
+        .ecs()
+        .read_resource::<comp::pvp::PvpRules>()
+        .mode;
+    let text = match mode {
+        comp::pvp::PvpMode::Off => "Players can't fight each other on this server.",
+        comp::pvp::PvpMode::On => "Players can always fight each other on this server.",
+        comp::pvp::PvpMode::OptIn => {
+            let was_flagged = server
+                .state
+                .ecs()
+                .write_storage::<comp::PvpFlag>()
+                .remove(entity)
+                .is_some();
+            if was_flagged {
+                "You no longer fight other players."
+            } else {
+                server.state.write_component(entity, comp::PvpFlag);
+                "You can fight other players that typed /pvp now, outside of safe zones."
+            }
+        }
+    };
+    server
+        .clients
+        .notify(entity, ServerMsg::Chat(ChatMsg::command(text.to_owned())));
+}
@@ -0,0 +1,258 @@
+//! What dying costs characters, and where they come back to life.
+
+use crate::{equipment, items};
+use common::{
+    comp::{self, phys::Pos, player::RespawnPoint},
+    inventory::{Inventory, InventoryUpdate},
+    state::State,
+};
+use specs::Entity as EcsEntity;
+use std::fmt;
+use vek::*;
+
+// Respawned characters appear this far (in blocks) above their respawn point, and fall down to it
+const RESPAWN_HEIGHT: f32 = 2.0;
+
+/// Where characters start out, and what happens when they die.
+#[derive(Clone, Debug)]
+pub struct DeathSettings {
+    /// Where new characters spawn, and where dead ones respawn unless they set their own
+    /// respawn point.
+    pub spawn_point: Vec3<f32>,
+    /// The fraction (from 0 to 1) of the experience towards their next level that characters
+    /// lose when they die. Nobody loses levels.
+    pub xp_loss: f32,
+    /// Whether characters drop everything in their inventory where they die. Equipped items are
+    /// kept.
+    pub drop_inventory: bool,
+}
+
+impl Default for DeathSettings {
+    fn default() -> Self {
+        Self {
+            spawn_point: Vec3::new(16_384.0, 16_384.0, 280.0),
+            xp_loss: 0.1,
+            drop_inventory: false,
+        }
+    }
+}
+
+/// Parse a spawn point from its coordinates, like `16384 16384 280`.
+pub fn parse_spawn_point(point: &str) -> Option<Vec3<f32>> {
+    let mut coords = point
+        .split_whitespace()
+        .map(|coord| coord.parse::<f32>().ok());
+    let point = Vec3::new(coords.next()??, coords.next()??, coords.next()??);
+    match coords.next() {
+        Some(_) => None,
+        None => Some(point),
+    }
+}
+
+/// The world spawn point, see `DeathSettings::spawn_point`.
+#[derive(Copy, Clone)]
+pub struct SpawnPoint(pub Vec3<f32>);
+
+/// What a character lost by dying.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Penalty {
+    pub xp: u32,
+    pub items: usize,
+}
+
+impl fmt::Display for Penalty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.xp, self.items) {
+            (0, 0) => write!(f, "You lost nothing."),
+            (xp, 0) => write!(f, "You lost {} experience.", xp),
+            (0, items) => write!(f, "You dropped {} items where you died.", items),
+            (xp, items) => write!(
+                f,
+                "You lost {} experience and dropped {} items where you died.",
+                xp, items
+            ),
+        }
+    }
+}
+
+/// Whether an entity has stats and isn't dead.
+pub fn is_alive(state: &State, entity: EcsEntity) -> bool {
+    state
+        .ecs()
+        .read_storage::<comp::Stats>()
+        .get(entity)
+        .is_some()
+        && state
+            .ecs()
+            .read_storage::<comp::LifeState>()
+            .get(entity)
+            .map_or(true, comp::LifeState::is_alive)
+}
+
+/// Make a character that just died pay for it, according to the settings.
+pub fn apply_penalty(state: &mut State, entity: EcsEntity, settings: &DeathSettings) -> Penalty {
+    let mut penalty = Penalty::default();
+
+    if let Some(stats) = state.ecs().write_storage::<comp::Stats>().get_mut(entity) {
+        penalty.xp = (stats.xp as f32 * settings.xp_loss.max(0.0).min(1.0)) as u32;
+        stats.xp -= penalty.xp;
+    }
+
+    let pos = state.read_component_cloned::<Pos>(entity);
+    if let (true, Some(pos)) = (settings.drop_inventory, pos) {
+        let dropped = state
+            .ecs()
+            .write_storage::<Inventory>()
+            .get_mut(entity)
+            .map(|inventory| {
+                inventory
+                    .slots
+                    .iter_mut()
+                    .filter_map(|slot| slot.take())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        penalty.items = dropped.len();
+        for item in dropped {
+            items::drop_item(state, pos.0, item);
+        }
+        state.write_component(entity, InventoryUpdate);
+    }
+
+    penalty
+}
+
+/// Bring a dead character back to life at its respawn point, or the world spawn point if it has
+/// none. Characters come back with everything their level and equipment allow.
+pub fn respawn(state: &mut State, entity: EcsEntity) {
+    // Characters keep their level and experience
+    let old_stats = state
+        .read_component_cloned::<comp::Stats>(entity)
+        .unwrap_or_default();
+    state.write_component(
+        entity,
+        comp::Stats {
+            level: old_stats.level,
+            xp: old_stats.xp,
+            ..comp::Stats::default()
+        },
+    );
+    state.write_component(entity, comp::LifeState::Alive);
+    equipment::refresh(state, entity);
+    if let Some(stats) = state
+        .ecs_mut()
+        .write_storage::<comp::Stats>()
+        .get_mut(entity)
+    {
+        let maximum = stats.hp.get_maximum();
+        stats.hp.set_to(maximum, comp::HealthSource::Unknown);
+    }
+
+    let respawn_point = match state.read_component_cloned::<RespawnPoint>(entity) {
+        Some(point) => point.0,
+        None => state.ecs().read_resource::<SpawnPoint>().0,
+    };
+    state.write_component(entity, Pos(respawn_point + Vec3::unit_z() * RESPAWN_HEIGHT));
+    state.write_component(entity, comp::phys::Vel(Vec3::zero()));
+    state.write_component(entity, comp::phys::ForceUpdate);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::inventory::item::{Ingredient, Item};
+    use specs::{Builder, Join};
+
+    fn state() -> State {
+        let mut state = State::new();
+        state
+            .ecs_mut()
+            .add_resource(SpawnPoint(Vec3::new(100.0, 100.0, 50.0)));
+        state
+    }
+
+    fn character(state: &mut State) -> EcsEntity {
+        let body = comp::Body::Humanoid(comp::HumanoidBody::random());
+        let mut stats = comp::Stats::default();
+        stats.gain_xp(150);
+        state
+            .ecs_mut()
+            .create_entity_synced()
+            .with(Pos(Vec3::zero()))
+            .with(comp::Actor::Character {
+                name: "test".to_owned(),
+                body,
+            })
+            .with(comp::Equipment::for_body(&body))
+            .with(Inventory::new())
+            .with(stats)
+            .with(comp::LifeState::Dead)
+            .build()
+    }
+
+    #[test]
+    fn penalty() {
+        let mut state = state();
+        let entity = character(&mut state);
+        let meat = Item::Ingredient {
+            kind: Ingredient::Meat,
+            amount: 3,
+        };
+        state
+            .ecs()
+            .write_storage::<Inventory>()
+            .get_mut(entity)
+            .unwrap()
+            .push(meat);
+
+        let settings = DeathSettings {
+            xp_loss: 0.5,
+            drop_inventory: true,
+            ..DeathSettings::default()
+        };
+        assert_eq!(
+            apply_penalty(&mut state, entity, &settings),
+            Penalty { xp: 25, items: 1 }
+        );
+        let stats = state.read_component_cloned::<comp::Stats>(entity).unwrap();
+        assert_eq!((stats.level, stats.xp), (2, 25));
+        let inventory = state.read_component_cloned::<Inventory>(entity).unwrap();
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(
+            (&state.ecs().read_storage::<Item>()).join().next(),
+            Some(&meat)
+        );
+    }
+
+    #[test]
+    fn spawn_points() {
+        assert_eq!(
+            parse_spawn_point(" 100 -20.5  280\n"),
+            Some(Vec3::new(100.0, -20.5, 280.0))
+        );
+        assert_eq!(parse_spawn_point("100 200"), None);
+        assert_eq!(parse_spawn_point("100 200 300 400"), None);
+        assert_eq!(parse_spawn_point("100 200 high"), None);
+    }
+
+    #[test]
+    fn respawn_at_respawn_point() {
+        let mut state = state();
+        let entity = character(&mut state);
+        assert!(!is_alive(&state, entity));
+
+        respawn(&mut state, entity);
+        assert!(is_alive(&state, entity));
+        let pos = state.read_component_cloned::<Pos>(entity).unwrap();
+        assert_eq!(pos.0.z, 50.0 + RESPAWN_HEIGHT);
+        let stats = state.read_component_cloned::<comp::Stats>(entity).unwrap();
+        assert_eq!(stats.level, 2);
+        assert_eq!(stats.hp.get_current(), stats.hp.get_maximum());
+
+        state.write_component(entity, RespawnPoint(Vec3::new(1.0, 2.0, 3.0)));
+        respawn(&mut state, entity);
+        let pos = state.read_component_cloned::<Pos>(entity).unwrap();
+        assert_eq!(pos.0, Vec3::new(1.0, 2.0, 3.0 + RESPAWN_HEIGHT));
+    }
+}
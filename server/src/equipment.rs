@@ -0,0 +1,184 @@
+//! Moving items between the inventories and equipment of characters, on behalf of their players.
+
+use crate::death;
+use common::{
+    comp,
+    inventory::{item::EquipSlot, Inventory, InventoryUpdate},
+    state::State,
+};
+use specs::Entity as EcsEntity;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EquipError {
+    Dead,
+    NoItem,
+    NotEquippable,
+    InventoryFull,
+}
+
+impl fmt::Display for EquipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            EquipError::Dead => "You can't change your equipment while dead!",
+            EquipError::NoItem => "There is no item there!",
+            EquipError::NotEquippable => "That item can't be equipped!",
+            EquipError::InventoryFull => "Your inventory is full!",
+        })
+    }
+}
+
+fn check_alive(state: &State, entity: EcsEntity) -> Result<(), EquipError> {
+    if death::is_alive(state, entity) {
+        Ok(())
+    } else {
+        Err(EquipError::Dead)
+    }
+}
+
+/// Equip the item in the given inventory slot. Whatever was in its equipment slot takes its place
+/// in the inventory.
+pub fn equip(state: &mut State, entity: EcsEntity, cell: usize) -> Result<(), EquipError> {
+    check_alive(state, entity)?;
+    {
+        let ecs = state.ecs();
+        let mut inventories = ecs.write_storage::<Inventory>();
+        let mut equipments = ecs.write_storage::<comp::Equipment>();
+        let (inventory, equipment) = match (inventories.get_mut(entity), equipments.get_mut(entity))
+        {
+            (Some(inventory), Some(equipment)) => (inventory, equipment),
+            _ => return Err(EquipError::NoItem),
+        };
+
+        let item = inventory.remove(cell).ok_or(EquipError::NoItem)?;
+        match equipment.equip(item) {
+            Ok(Some(old)) => {
+                inventory.swap(cell, old);
+            }
+            Ok(None) => {}
+            Err(item) => {
+                inventory.swap(cell, item);
+                return Err(EquipError::NotEquippable);
+            }
+        }
+    }
+    refresh(state, entity);
+    state.write_component(entity, InventoryUpdate);
+    Ok(())
+}
+
+/// Put the item in the given equipment slot into the inventory.
+pub fn unequip(state: &mut State, entity: EcsEntity, slot: EquipSlot) -> Result<(), EquipError> {
+    check_alive(state, entity)?;
+    {
+        let ecs = state.ecs();
+        let mut inventories = ecs.write_storage::<Inventory>();
+        let mut equipments = ecs.write_storage::<comp::Equipment>();
+        let (inventory, equipment) = match (inventories.get_mut(entity), equipments.get_mut(entity))
+        {
+            (Some(inventory), Some(equipment)) => (inventory, equipment),
+            _ => return Err(EquipError::NoItem),
+        };
+
+        if equipment.get(slot).is_none() {
+            return Err(EquipError::NoItem);
+        }
+        if inventory.is_full() {
+            return Err(EquipError::InventoryFull);
+        }
+        if let Some(item) = equipment.unequip(slot) {
+            inventory.push(item);
+        }
+    }
+    refresh(state, entity);
+    state.write_component(entity, InventoryUpdate);
+    Ok(())
+}
+
+/// Show the equipment of a character on its body and apply its bonuses to its stats. Call this
+/// whenever the equipment or level of a character changes.
+pub fn refresh(state: &mut State, entity: EcsEntity) {
+    let ecs = state.ecs();
+    let equipment = match ecs.read_storage::<comp::Equipment>().get(entity) {
+        Some(equipment) => equipment.clone(),
+        None => return,
+    };
+
+    if let Some(comp::Actor::Character {
+        body: comp::Body::Humanoid(body),
+        ..
+    }) = ecs.write_storage::<comp::Actor>().get_mut(entity)
+    {
+        equipment.dress(body);
+    }
+
+    if let Some(stats) = ecs.write_storage::<comp::Stats>().get_mut(entity) {
+        let maximum = stats.base_health() as i32 + equipment.health_bonus();
+        stats.hp.set_maximum(maximum.max(1) as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{
+        comp::{actor, stats::BASE_HEALTH},
+        inventory::item::{Armor, Item, Rarity},
+    };
+    use specs::Builder;
+
+    fn character(state: &mut State) -> EcsEntity {
+        let body = comp::Body::Humanoid(comp::HumanoidBody::random());
+        state
+            .ecs_mut()
+            .create_entity_synced()
+            .with(comp::Actor::Character {
+                name: "test".to_owned(),
+                body,
+            })
+            .with(comp::Equipment::for_body(&body))
+            .with(Inventory::new())
+            .with(comp::Stats::default())
+            .build()
+    }
+
+    #[test]
+    fn equip_and_unequip() {
+        let mut state = State::new();
+        let entity = character(&mut state);
+        let chestplate = Item::Armor {
+            defense: 10,
+            health_bonus: 50,
+            rarity: Rarity::Rare,
+            variant: Armor::Chestplate(actor::Chest::Orange),
+        };
+        state
+            .ecs()
+            .write_storage::<Inventory>()
+            .get_mut(entity)
+            .unwrap()
+            .swap(2, chestplate);
+
+        assert_eq!(equip(&mut state, entity, 0), Err(EquipError::NoItem));
+        assert_eq!(equip(&mut state, entity, 2), Ok(()));
+        let stats = state.read_component_cloned::<comp::Stats>(entity).unwrap();
+        assert_eq!(stats.hp.get_maximum(), BASE_HEALTH + 50);
+        match state.read_component_cloned::<comp::Actor>(entity) {
+            Some(comp::Actor::Character {
+                body: comp::Body::Humanoid(body),
+                ..
+            }) => assert_eq!(body.chest, actor::Chest::Orange),
+            _ => panic!("character lost its body"),
+        }
+
+        assert_eq!(unequip(&mut state, entity, EquipSlot::Chest), Ok(()));
+        assert_eq!(
+            unequip(&mut state, entity, EquipSlot::Chest),
+            Err(EquipError::NoItem)
+        );
+        let inventory = state.read_component_cloned::<Inventory>(entity).unwrap();
+        assert_eq!(inventory.get(0), Some(chestplate));
+        let stats = state.read_component_cloned::<comp::Stats>(entity).unwrap();
+        assert_eq!(stats.hp.get_maximum(), BASE_HEALTH);
+    }
+}
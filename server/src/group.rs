@@ -0,0 +1,270 @@
+//! Players teaming up: inviting each other into groups, leaving them and kicking members out.
+
+use crate::death;
+use common::{
+    comp::{
+        self,
+        group::{GroupInvite, MAX_GROUP_SIZE},
+        phys::Pos,
+        Group,
+    },
+    state::State,
+};
+use log::warn;
+use specs::{Entity as EcsEntity, Join};
+use std::fmt;
+
+/// How long (in seconds) invitations can be accepted.
+pub const INVITE_TIMEOUT: f64 = 60.0;
+// Members further than this (in blocks) from a kill don't get a share of its experience
+const XP_SHARE_RANGE: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupError {
+    NoPlayer,
+    Yourself,
+    AlreadyInGroup,
+    NotLeader,
+    NotInGroup,
+    NotMember,
+    Full,
+    NoInvite,
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GroupError::NoPlayer => "That player doesn't exist!",
+            GroupError::Yourself => "You can't do that to yourself!",
+            GroupError::AlreadyInGroup => "That player is already in a group!",
+            GroupError::NotLeader => "Only the leader of your group can do that!",
+            GroupError::NotInGroup => "You aren't in a group!",
+            GroupError::NotMember => "That player isn't in your group!",
+            GroupError::Full => "The group is full!",
+            GroupError::NoInvite => "Nobody invited you to a group recently!",
+        })
+    }
+}
+
+fn uid(state: &State, entity: EcsEntity) -> Option<u64> {
+    state.ecs().uid_from_entity(entity).map(u64::from)
+}
+
+/// The members of the group with the given id.
+pub fn members(state: &State, id: u64) -> Vec<EcsEntity> {
+    (
+        &state.ecs().entities(),
+        &state.ecs().read_storage::<Group>(),
+    )
+        .join()
+        .filter(|(_, group)| group.id == id)
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Invite a player into the group of `entity`. Players without a group can invite others too,
+/// which founds a new group once the invitation is accepted.
+pub fn invite(state: &mut State, entity: EcsEntity, target: EcsEntity) -> Result<(), GroupError> {
+    if entity == target {
+        return Err(GroupError::Yourself);
+    }
+    if state
+        .read_component_cloned::<comp::Player>(target)
+        .is_none()
+    {
+        return Err(GroupError::NoPlayer);
+    }
+    if state.read_component_cloned::<Group>(target).is_some() {
+        return Err(GroupError::AlreadyInGroup);
+    }
+    let from = uid(state, entity).ok_or(GroupError::NoPlayer)?;
+    if let Some(group) = state.read_component_cloned::<Group>(entity) {
+        if group.leader != from {
+            return Err(GroupError::NotLeader);
+        }
+        if members(state, group.id).len() >= MAX_GROUP_SIZE {
+            return Err(GroupError::Full);
+        }
+    }
+
+    let time = state.get_time();
+    state.write_component(target, GroupInvite { from, time });
+    Ok(())
+}
+
+/// Accept the last invitation `entity` got, returning the group it joined.
+pub fn accept(state: &mut State, entity: EcsEntity) -> Result<Group, GroupError> {
+    let invite = state
+        .ecs()
+        .write_storage::<GroupInvite>()
+        .remove(entity)
+        .filter(|invite| state.get_time() - invite.time <= INVITE_TIMEOUT)
+        .ok_or(GroupError::NoInvite)?;
+    if state.read_component_cloned::<Group>(entity).is_some() {
+        return Err(GroupError::AlreadyInGroup);
+    }
+    let inviter = state
+        .ecs()
+        .entity_from_uid(invite.from)
+        .ok_or(GroupError::NoInvite)?;
+
+    // Invitations of players that lost the lead or joined another group since are void.
+    let group = match state.read_component_cloned::<Group>(inviter) {
+        Some(group) if group.leader != invite.from => return Err(GroupError::NoInvite),
+        Some(group) if members(state, group.id).len() >= MAX_GROUP_SIZE => {
+            return Err(GroupError::Full)
+        }
+        Some(group) => group,
+        None => {
+            // Ids of groups that no longer exist may be reused, since nobody refers to them.
+            let id = (&state.ecs().read_storage::<Group>())
+                .join()
+                .map(|group| group.id + 1)
+                .max()
+                .unwrap_or(1);
+            let group = Group {
+                id,
+                leader: invite.from,
+            };
+            state.write_component(inviter, group);
+            group
+        }
+    };
+    state.write_component(entity, group);
+    Ok(group)
+}
+
+/// Take `entity` out of its group, returning the group it left. Leaders hand their role to
+/// another member, and groups with a single member left are disbanded.
+pub fn leave(state: &mut State, entity: EcsEntity) -> Result<Group, GroupError> {
+    let group = state
+        .ecs()
+        .write_storage::<Group>()
+        .remove(entity)
+        .ok_or(GroupError::NotInGroup)?;
+
+    let rest = members(state, group.id);
+    match rest.as_slice() {
+        [] => {}
+        [last] => {
+            state.ecs().write_storage::<Group>().remove(*last);
+        }
+        [next, ..] if uid(state, entity) == Some(group.leader) => {
+            let leader = match uid(state, *next) {
+                Some(leader) => leader,
+                None => {
+                    warn!("Group member without a Uid!");
+                    return Ok(group);
+                }
+            };
+            for member in rest {
+                state.write_component(member, Group { leader, ..group });
+            }
+        }
+        _ => {}
+    }
+    Ok(group)
+}
+
+/// Remove `target` from the group `entity` leads.
+pub fn kick(state: &mut State, entity: EcsEntity, target: EcsEntity) -> Result<Group, GroupError> {
+    if entity == target {
+        return Err(GroupError::Yourself);
+    }
+    let group = state
+        .read_component_cloned::<Group>(entity)
+        .ok_or(GroupError::NotInGroup)?;
+    if uid(state, entity) != Some(group.leader) {
+        return Err(GroupError::NotLeader);
+    }
+    match state.read_component_cloned::<Group>(target) {
+        Some(target_group) if target_group.id == group.id => leave(state, target),
+        _ => Err(GroupError::NotMember),
+    }
+}
+
+/// Split the experience of a kill among the living members of the killer's group that are near
+/// it. Everybody gets an equal share, but at least 1.
+pub fn share_xp(state: &State, killer: EcsEntity, xp: u32) -> Vec<(EcsEntity, u32)> {
+    let group = match state.read_component_cloned::<Group>(killer) {
+        Some(group) => group,
+        None => return vec![(killer, xp)],
+    };
+    let killer_pos = state.read_component_cloned::<Pos>(killer);
+    let positions = state.ecs().read_storage::<Pos>();
+
+    let sharing = members(state, group.id)
+        .into_iter()
+        .filter(|&member| {
+            member == killer
+                || (death::is_alive(state, member)
+                    && match (killer_pos, positions.get(member)) {
+                        (Some(a), Some(b)) => a.0.distance(b.0) <= XP_SHARE_RANGE,
+                        _ => false,
+                    })
+        })
+        .collect::<Vec<_>>();
+    let share = (xp / sharing.len().max(1) as u32).max(1);
+    sharing.into_iter().map(|member| (member, share)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::Builder;
+    use vek::*;
+
+    fn player(state: &mut State, x: f32) -> EcsEntity {
+        state
+            .ecs_mut()
+            .create_entity_synced()
+            .with(comp::Player::new("test".to_owned(), None))
+            .with(Pos(Vec3::new(x, 0.0, 0.0)))
+            .with(comp::Stats::default())
+            .build()
+    }
+
+    #[test]
+    fn invite_accept_leave() {
+        let mut state = State::new();
+        let (a, b, c) = (
+            player(&mut state, 0.0),
+            player(&mut state, 10.0),
+            player(&mut state, 1000.0),
+        );
+
+        assert_eq!(accept(&mut state, b), Err(GroupError::NoInvite));
+        assert_eq!(invite(&mut state, a, a), Err(GroupError::Yourself));
+        assert_eq!(invite(&mut state, a, b), Ok(()));
+        let group = accept(&mut state, b).unwrap();
+        assert_eq!(Some(group.leader), uid(&state, a));
+        assert_eq!(members(&state, group.id), vec![a, b]);
+
+        // Only the leader invites, and members can't be invited elsewhere
+        assert_eq!(invite(&mut state, b, c), Err(GroupError::NotLeader));
+        assert_eq!(invite(&mut state, c, b), Err(GroupError::AlreadyInGroup));
+        invite(&mut state, a, c).unwrap();
+        accept(&mut state, c).unwrap();
+
+        // Only members close to the kill share its experience
+        assert_eq!(share_xp(&state, b, 30), vec![(a, 15), (b, 15)]);
+
+        // The leader leaving hands the group over, and their invitations are void
+        let d = player(&mut state, 0.0);
+        invite(&mut state, a, d).unwrap();
+        assert_eq!(leave(&mut state, a), Ok(group));
+        let group = state.read_component_cloned::<Group>(b).unwrap();
+        assert_eq!(Some(group.leader), uid(&state, b));
+        assert_eq!(kick(&mut state, c, b), Err(GroupError::NotLeader));
+        invite(&mut state, b, a).unwrap();
+        accept(&mut state, a).unwrap();
+        assert_eq!(accept(&mut state, d), Err(GroupError::NoInvite));
+        assert_eq!(state.read_component_cloned::<Group>(d), None);
+        assert_eq!(leave(&mut state, a), Ok(group));
+
+        // Kicking the second to last member disbands the group
+        assert!(kick(&mut state, b, c).is_ok());
+        assert_eq!(state.read_component_cloned::<Group>(b), None);
+        assert_eq!(leave(&mut state, b), Err(GroupError::NotInGroup));
+    }
+}
@@ -0,0 +1,240 @@
+//! Items lying around in the world: the loot dead NPCs leave behind, and picking it up.
+
+use crate::death;
+use common::{
+    comp::{self, phys::Pos},
+    inventory::{item::Item, Dropped, Inventory, InventoryUpdate, PICKUP_RANGE},
+    loot::loot_table,
+    npc::NpcKind,
+    state::{SimRng, State},
+    terrain::TerrainMap,
+    vol::ReadVol,
+};
+use log::warn;
+use rand::Rng;
+use specs::{Builder, Entity as EcsEntity, Join};
+use std::fmt;
+use vek::*;
+
+// How far (in blocks) dropped items get scattered around where they were dropped
+const SCATTER: f32 = 1.0;
+// How far (in blocks) dropped items fall to reach the ground at most
+const MAX_FALL: i32 = 256;
+/// How long (in seconds) items lie around before they disappear.
+pub const ITEM_LIFETIME: f64 = 300.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupError {
+    Dead,
+    NoItem,
+    TooFar,
+    InventoryFull,
+}
+
+impl fmt::Display for PickupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PickupError::Dead => "You can't pick up items while dead!",
+            PickupError::NoItem => "There is nothing to pick up there!",
+            PickupError::TooFar => "That item is too far away!",
+            PickupError::InventoryFull => "Your inventory is full!",
+        })
+    }
+}
+
+// Where an item dropped at `pos` comes to rest. Items don't move once they're in the world, so
+// they're put onto the ground right away. Items above terrain that isn't loaded stay where they are.
+fn ground_below(terrain: &TerrainMap, pos: Vec3<f32>) -> Vec3<f32> {
+    let start = pos.z.floor() as i32;
+    let z = (start - MAX_FALL..=start).rev().find_map(|z| {
+        match terrain.get(Vec3::new(pos.x.floor() as i32, pos.y.floor() as i32, z - 1)) {
+            Ok(block) if block.is_solid() => Some(z as f32),
+            Ok(_) => None,
+            Err(_) => Some(pos.z),
+        }
+    });
+    Vec3::new(pos.x, pos.y, z.unwrap_or(pos.z))
+}
+
+/// Put an item into the world, on the ground below `pos`.
+pub fn drop_item(state: &mut State, pos: Vec3<f32>, item: Item) -> EcsEntity {
+    let offset = {
+        let mut rng = state.ecs().write_resource::<SimRng>();
+        Vec2::new(rng.0.gen::<f32>() - 0.5, rng.0.gen::<f32>() - 0.5) * 2.0 * SCATTER
+    };
+    let pos = ground_below(&state.terrain(), pos + Vec3::from(offset));
+    let time = state.get_time();
+    state
+        .ecs_mut()
+        .create_entity_synced()
+        .with(Pos(pos))
+        .with(comp::phys::Vel(Vec3::zero()))
+        .with(comp::phys::Ori(Vec3::unit_y()))
+        .with(item)
+        .with(Dropped(time))
+        .with(comp::phys::ForceUpdate)
+        .build()
+}
+
+/// Drop the loot of an NPC that died, according to the loot table of its kind.
+pub fn drop_loot(state: &mut State, entity: EcsEntity) {
+    let (pos, body) = match (
+        state.read_component_cloned::<Pos>(entity),
+        state.read_component_cloned::<comp::Actor>(entity),
+    ) {
+        (Some(pos), Some(comp::Actor::Character { body, .. })) => (pos, body),
+        _ => return,
+    };
+
+    let loot =
+        loot_table(NpcKind::from_body(&body)).roll(&mut state.ecs().write_resource::<SimRng>().0);
+    for item in loot {
+        drop_item(state, pos.0, item);
+    }
+}
+
+/// Remove the items that lay around for longer than `ITEM_LIFETIME`.
+pub fn despawn_expired(state: &mut State) {
+    let now = state.get_time();
+    let expired = (
+        &state.ecs().entities(),
+        &state.ecs().read_storage::<Dropped>(),
+    )
+        .join()
+        .filter(|(_, dropped)| now - dropped.0 > ITEM_LIFETIME)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    for entity in expired {
+        if let Err(err) = state.ecs_mut().delete_entity_synced(entity) {
+            warn!("Failed to delete expired item: {:?}", err);
+        }
+    }
+}
+
+/// Move an item lying in the world into the inventory of a character. Items that only partly fit
+/// leave the rest lying around.
+pub fn pickup(state: &mut State, entity: EcsEntity, item_uid: u64) -> Result<(), PickupError> {
+    if !death::is_alive(state, entity) {
+        return Err(PickupError::Dead);
+    }
+
+    let item_entity = state
+        .ecs()
+        .entity_from_uid(item_uid)
+        .ok_or(PickupError::NoItem)?;
+    let item = state
+        .read_component_cloned::<Item>(item_entity)
+        .ok_or(PickupError::NoItem)?;
+    match (
+        state.read_component_cloned::<Pos>(entity),
+        state.read_component_cloned::<Pos>(item_entity),
+    ) {
+        (Some(pos), Some(item_pos)) if pos.0.distance(item_pos.0) <= PICKUP_RANGE => {}
+        _ => return Err(PickupError::TooFar),
+    }
+
+    let rest = match state.ecs().write_storage::<Inventory>().get_mut(entity) {
+        Some(inventory) => inventory.push(item),
+        None => return Err(PickupError::InventoryFull),
+    };
+
+    match rest {
+        Some(rest) if rest == item => return Err(PickupError::InventoryFull),
+        Some(rest) => state.write_component(item_entity, rest),
+        None => {
+            if let Err(err) = state.ecs_mut().delete_entity_synced(item_entity) {
+                warn!("Failed to delete picked up item: {:?}", err);
+            }
+        }
+    }
+    state.write_component(entity, InventoryUpdate);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{
+        inventory::item::Ingredient,
+        state::Time,
+        terrain::{Block, TerrainChunk, TerrainChunkMeta},
+        vol::Vox,
+    };
+
+    fn character(state: &mut State, pos: Vec3<f32>) -> EcsEntity {
+        let body = comp::Body::Humanoid(comp::HumanoidBody::random());
+        state
+            .ecs_mut()
+            .create_entity_synced()
+            .with(Pos(pos))
+            .with(comp::Actor::Character {
+                name: "test".to_owned(),
+                body,
+            })
+            .with(Inventory::new())
+            .with(comp::Stats::default())
+            .build()
+    }
+
+    fn coins(amount: u32) -> Item {
+        Item::Ingredient {
+            kind: Ingredient::Coin,
+            amount,
+        }
+    }
+
+    #[test]
+    fn pickup_stacks_and_leaves_the_rest() {
+        let mut state = State::new();
+        let entity = character(&mut state, Vec3::zero());
+        let item = drop_item(&mut state, Vec3::zero(), coins(60));
+        let uid = state.ecs().uid_from_entity(item).unwrap().into();
+        {
+            let mut inventories = state.ecs().write_storage::<Inventory>();
+            let inventory = inventories.get_mut(entity).unwrap();
+            inventory.swap(0, coins(50));
+            for cell in 1..24 {
+                inventory.swap(cell, Item::starter_weapon(comp::actor::Weapon::Axe));
+            }
+        }
+
+        assert_eq!(pickup(&mut state, entity, uid), Ok(()));
+        let inventory = state.read_component_cloned::<Inventory>(entity).unwrap();
+        assert_eq!(inventory.get(0), Some(coins(99)));
+        assert_eq!(state.read_component_cloned::<Item>(item), Some(coins(11)));
+
+        assert_eq!(
+            pickup(&mut state, entity, uid),
+            Err(PickupError::InventoryFull)
+        );
+    }
+
+    #[test]
+    fn pickup_needs_to_be_close() {
+        let mut state = State::new();
+        let entity = character(&mut state, Vec3::zero());
+        let item = drop_item(&mut state, Vec3::new(10.0, 0.0, 0.0), coins(1));
+        let uid = state.ecs().uid_from_entity(item).unwrap().into();
+
+        assert_eq!(pickup(&mut state, entity, uid), Err(PickupError::TooFar));
+        assert_eq!(pickup(&mut state, entity, 12345), Err(PickupError::NoItem));
+    }
+
+    #[test]
+    fn items_land_on_the_ground_and_disappear() {
+        let mut state = State::new();
+        let stone = Block::new(2, Rgb::broadcast(128));
+        state.insert_chunk(
+            Vec2::zero(),
+            TerrainChunk::new(0, stone, Block::empty(), TerrainChunkMeta::void()),
+        );
+        let item = drop_item(&mut state, Vec3::new(8.0, 8.0, 20.0), coins(1));
+        assert_eq!(state.read_component_cloned::<Pos>(item).unwrap().0.z, 0.0);
+
+        despawn_expired(&mut state);
+        assert!(state.ecs().is_alive(item));
+        state.ecs_mut().write_resource::<Time>().0 += ITEM_LIFETIME + 1.0;
+        despawn_expired(&mut state);
+        assert!(!state.ecs().is_alive(item));
+    }
+}
@@ -1,21 +1,42 @@
 #![feature(drain_filter)]
 
+pub mod chat;
 pub mod client;
 pub mod cmd;
+pub mod death;
+pub mod equipment;
 pub mod error;
+pub mod group;
 pub mod input;
+pub mod items;
+pub mod pets;
+pub mod pvp;
+pub mod session;
+pub mod spawner;
 
 // Reexports
-pub use crate::{error::Error, input::Input};
+pub use crate::{
+    chat::ChatSettings, death::DeathSettings, error::Error, input::Input, spawner::SpawnSettings,
+};
 
 use crate::{
+    chat::{ChatFilter, ChatLog, RateLimiter},
     client::{Client, Clients},
     cmd::CHAT_COMMANDS,
+    death::SpawnPoint,
+    group::GroupError,
+    pets::{PetError, SavedPets},
+    session::{OrphanedSession, Sessions},
+    spawner::Spawner,
 };
 use common::{
-    comp,
-    msg::{ClientMsg, ClientState, RequestStateError, ServerInfo, ServerMsg},
+    comp::{self, pvp::PvpRules},
+    inventory::{Inventory, InventoryUpdate},
+    msg::{
+        ChatMsg, ClientMsg, ClientState, RequestStateError, ServerInfo, ServerMsg, PROTOCOL_VERSION,
+    },
     net::PostOffice,
+    npc::get_npc_name,
     state::{State, Uid},
     terrain::{TerrainChunk, TerrainChunkSize},
     vol::VolSize,
@@ -26,6 +47,7 @@ use std::{
     collections::HashSet,
     i32,
     net::SocketAddr,
+    path::Path,
     sync::{mpsc, Arc},
     time::Duration,
 };
@@ -37,6 +59,9 @@ const CLIENT_TIMEOUT: f64 = 20.0; // Seconds
 
 const DEFAULT_WORLD_SEED: u32 = 1337;
 
+/// How far (in blocks) players can be heard when they talk in chat.
+const SAY_DISTANCE: f32 = 100.0;
+
 pub enum Event {
     ClientConnected {
         entity: EcsEntity,
@@ -50,15 +75,18 @@ pub enum Event {
     },
 }
 
-#[derive(Copy, Clone)]
-struct SpawnPoint(Vec3<f32>);
-
 pub struct Server {
     state: State,
     world: Arc<World>,
 
     postoffice: PostOffice<ServerMsg, ClientMsg>,
     clients: Clients,
+    sessions: Sessions,
+
+    chat_filter: ChatFilter,
+    chat_log: ChatLog,
+    spawner: Spawner,
+    death_settings: DeathSettings,
 
     thread_pool: ThreadPool,
     chunk_tx: mpsc::Sender<(Vec2<i32>, TerrainChunk)>,
@@ -80,10 +108,12 @@ impl Server {
     pub fn bind<A: Into<SocketAddr>>(addrs: A) -> Result<Self, Error> {
         let (chunk_tx, chunk_rx) = mpsc::channel();
 
+        let death_settings = DeathSettings::default();
         let mut state = State::new();
         state
             .ecs_mut()
-            .add_resource(SpawnPoint(Vec3::new(16_384.0, 16_384.0, 280.0)));
+            .add_resource(SpawnPoint(death_settings.spawn_point));
+        state.ecs_mut().add_resource(SavedPets::default());
 
         let this = Self {
             state,
@@ -91,6 +121,12 @@ impl Server {
 
             postoffice: PostOffice::bind(addrs.into())?,
             clients: Clients::empty(),
+            sessions: Sessions::empty(),
+
+            chat_filter: ChatFilter::new(ChatSettings::default()),
+            chat_log: ChatLog::none(),
+            spawner: Spawner::new(SpawnSettings::default()),
+            death_settings,
 
             thread_pool: threadpool::Builder::new()
                 .thread_name("veloren-worker".into())
@@ -108,6 +144,51 @@ impl Server {
         Ok(this)
     }
 
+    /// Record the network traffic of every new client into its own capture file in the given
+    /// directory.
+    #[allow(dead_code)]
+    pub fn record_network<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Error> {
+        Ok(self.postoffice.record_to(dir)?)
+    }
+
+    /// Use the given chat moderation settings.
+    #[allow(dead_code)]
+    pub fn with_chat_settings(mut self, settings: ChatSettings) -> Self {
+        self.chat_log = match &settings.log_path {
+            Some(path) => ChatLog::open(path).unwrap_or_else(|err| {
+                warn!("Failed to open chat log at {:?}: {:?}", path, err);
+                ChatLog::none()
+            }),
+            None => ChatLog::none(),
+        };
+        self.chat_filter = ChatFilter::new(settings);
+        self
+    }
+
+    /// Use the given settings for populating the world with NPCs.
+    #[allow(dead_code)]
+    pub fn with_spawn_settings(mut self, settings: SpawnSettings) -> Self {
+        self.spawner = Spawner::new(settings);
+        self
+    }
+
+    /// Use the given rules for fights between players.
+    #[allow(dead_code)]
+    pub fn with_pvp_rules(mut self, rules: PvpRules) -> Self {
+        *self.state.ecs_mut().write_resource::<PvpRules>() = rules;
+        self
+    }
+
+    /// Use the given spawn point and death penalty.
+    #[allow(dead_code)]
+    pub fn with_death_settings(mut self, settings: DeathSettings) -> Self {
+        self.state
+            .ecs_mut()
+            .add_resource(SpawnPoint(settings.spawn_point));
+        self.death_settings = settings;
+        self
+    }
+
     #[allow(dead_code)]
     pub fn with_thread_pool(mut self, thread_pool: ThreadPool) -> Self {
         self.thread_pool = thread_pool;
@@ -139,17 +220,43 @@ impl Server {
         name: String,
         body: comp::Body,
     ) -> EcsEntityBuilder {
-        self.state
-            .ecs_mut()
-            .create_entity_synced()
-            .with(pos)
-            .with(comp::phys::Vel(Vec3::zero()))
-            .with(comp::phys::Ori(Vec3::unit_y()))
-            .with(comp::Control::default())
-            .with(comp::AnimationInfo::default())
-            .with(comp::Actor::Character { name, body })
-            .with(comp::Stats::default())
-            .with(comp::phys::ForceUpdate)
+        npc_builder(&mut self.state, pos, name, body)
+    }
+
+    /// Give experience to an entity, and tell everyone when it reaches a new level.
+    fn give_xp(&mut self, entity: EcsEntity, xp: u32) {
+        if !death::is_alive(&self.state, entity) {
+            return;
+        }
+        let level = match self
+            .state
+            .ecs()
+            .write_storage::<comp::Stats>()
+            .get_mut(entity)
+        {
+            Some(stats) if stats.gain_xp(xp) > 0 => stats.level,
+            _ => return,
+        };
+
+        // Levelling up makes characters stronger and heals them fully.
+        equipment::refresh(&mut self.state, entity);
+        if let Some(stats) = self
+            .state
+            .ecs()
+            .write_storage::<comp::Stats>()
+            .get_mut(entity)
+        {
+            let maximum = stats.hp.get_maximum();
+            stats.hp.set_to(maximum, comp::HealthSource::Unknown);
+        }
+
+        if let Some(name) = display_name(&self.state, entity) {
+            self.clients
+                .notify_registered(ServerMsg::Chat(ChatMsg::system(format!(
+                    "{} reached level {}!",
+                    name, level
+                ))));
+        }
     }
 
     pub fn create_player_character(
@@ -162,13 +269,20 @@ impl Server {
         let spawn_point = state.ecs().read_resource::<SpawnPoint>().0;
 
         state.write_component(entity, comp::Actor::Character { name, body });
+        state.write_component(entity, comp::Equipment::for_body(&body));
+        state.write_component(entity, Inventory::new());
+        state.write_component(entity, InventoryUpdate);
         state.write_component(entity, comp::Stats::default());
+        state.write_component(entity, comp::LifeState::Alive);
         state.write_component(entity, comp::AnimationInfo::default());
         state.write_component(entity, comp::phys::Pos(spawn_point));
         state.write_component(entity, comp::phys::Vel(Vec3::zero()));
         state.write_component(entity, comp::phys::Ori(Vec3::unit_y()));
         // Make sure physics are accepted.
         state.write_component(entity, comp::phys::ForceUpdate);
+        if let Some(session_token) = client.previous_session_token {
+            pets::restore(state, entity, session_token);
+        }
 
         // Tell the client its request was successful.
         client.allow_state(ClientState::Character);
@@ -187,7 +301,8 @@ impl Server {
         //    to the state of the game
         // 3) Go through all incoming client network communications, apply them to the game state
         // 4) Perform a single LocalState tick (i.e: update the world and entities in the world)
-        // 5) Go through the terrain update queue and apply all changes to the terrain
+        // 5) Go through the terrain update queue and apply all changes to the terrain, and
+        //    populate it with NPCs
         // 6) Send relevant state updates to all clients
         // 7) Finish the tick, passing control of the main thread back to the frontend
 
@@ -199,7 +314,27 @@ impl Server {
             return Err(err.into());
         }
 
-        // 2)
+        // 2) Give up on dropped clients that didn't reconnect in time.
+        for (session_token, session) in self.sessions.drain_expired(self.state.get_time()) {
+            if let Some(player) = self
+                .state
+                .ecs()
+                .read_storage::<comp::Player>()
+                .get(session.entity)
+            {
+                self.clients
+                    .notify_registered(ServerMsg::Chat(ChatMsg::system(format!(
+                        "{} disconnected",
+                        &player.alias
+                    ))));
+            }
+            // Players that aren't in a group have nothing to leave.
+            let _ = self.leave_group(session.entity, None);
+            pets::save(&mut self.state, session.entity, session_token);
+            if let Err(err) = self.state.ecs_mut().delete_entity_synced(session.entity) {
+                warn!("Failed to delete expired session entity: {:?}", err);
+            }
+        }
 
         // 3) Handle inputs from clients
         frontend_events.append(&mut self.handle_new_connections()?);
@@ -207,41 +342,65 @@ impl Server {
 
         // 4) Tick the client's LocalState.
         self.state.tick(dt);
+        self.state.maintain_projectiles();
+        items::despawn_expired(&mut self.state);
+
+        // Tell players why their attacks on other players did nothing.
+        let mut blocked_hits = self.state.take_blocked_hits();
+        blocked_hits.dedup_by_key(|hit| (u64::from(hit.attacker), hit.reason.clone()));
+        for hit in blocked_hits {
+            if let Some(attacker) = self.state.ecs().entity_from_uid(hit.attacker.into()) {
+                self.clients.notify(
+                    attacker,
+                    ServerMsg::Chat(ChatMsg::system(hit.reason.to_string())),
+                );
+            }
+        }
 
         // Tick the world
         self.world.tick(dt);
 
         // Sync deaths.
-        let ecs = &self.state.ecs();
+        let state = &self.state;
+        let ecs = state.ecs();
         let clients = &mut self.clients;
+        let mut xp_rewards = Vec::new();
         let todo_kill = (&ecs.entities(), &ecs.read_storage::<comp::Dying>())
             .join()
             .map(|(entity, dying)| {
                 // Chat message
                 if let Some(player) = ecs.read_storage::<comp::Player>().get(entity) {
-                    let msg = if let comp::HealthSource::Attack { by } = dying.cause {
-                        ecs.entity_from_uid(by.into()).and_then(|attacker| {
-                            ecs.read_storage::<comp::Player>()
-                                .get(attacker)
-                                .map(|attacker_alias| {
-                                    format!(
-                                        "{} was killed by {}",
-                                        &player.alias, &attacker_alias.alias
-                                    )
-                                })
-                        })
-                    } else {
-                        None
-                    }
-                    .unwrap_or(format!("{} died", &player.alias));
+                    let msg = death_message(state, &player.alias, dying.cause);
+                    clients.notify_registered(ServerMsg::Chat(ChatMsg::death(msg)));
+                }
 
-                    clients.notify_registered(ServerMsg::Chat(msg));
+                // Whoever dealt the killing blow gets experience
+                let killer = match dying.cause {
+                    comp::HealthSource::Attack { by } => Some(by),
+                    comp::HealthSource::Poison { by } => by,
+                    _ => None,
+                };
+                if let Some(by) = killer {
+                    if let (Some(killer), Some(stats)) = (
+                        ecs.entity_from_uid(by.into()),
+                        ecs.read_storage::<comp::Stats>().get(entity),
+                    ) {
+                        if killer != entity {
+                            xp_rewards.push((killer, stats.xp_reward()));
+                        }
+                    }
                 }
 
                 entity
             })
             .collect::<Vec<_>>();
 
+        for (killer, xp) in xp_rewards {
+            for (entity, xp) in group::share_xp(&self.state, killer, xp) {
+                self.give_xp(entity, xp);
+            }
+        }
+
         // Actually kill them
         for entity in todo_kill {
             if let Some(client) = self.clients.get_mut(&entity) {
@@ -249,7 +408,19 @@ impl Server {
                     .write_component(entity, comp::phys::Vel(Vec3::zero()));
                 self.state.write_component(entity, comp::phys::ForceUpdate);
                 client.force_state(ClientState::Dead);
+                let penalty = death::apply_penalty(&mut self.state, entity, &self.death_settings);
+                self.clients.notify(
+                    entity,
+                    ServerMsg::Chat(ChatMsg::system(penalty.to_string())),
+                );
+            } else if let Some(session) = self.sessions.get_mut_by_entity(entity) {
+                // Keep the entity around so its owner can still reclaim it.
+                self.state
+                    .write_component(entity, comp::phys::Vel(Vec3::zero()));
+                session.client_state = ClientState::Dead;
+                death::apply_penalty(&mut self.state, entity, &self.death_settings);
             } else {
+                items::drop_loot(&mut self.state, entity);
                 if let Err(err) = self.state.ecs_mut().delete_entity_synced(entity) {
                     warn!("Failed to delete client not found in kill list: {:?}", err);
                 }
@@ -269,15 +440,7 @@ impl Server {
         for entity in todo_respawn {
             if let Some(client) = self.clients.get_mut(&entity) {
                 client.allow_state(ClientState::Character);
-                self.state.write_component(entity, comp::Stats::default());
-                self.state
-                    .ecs_mut()
-                    .write_storage::<comp::phys::Pos>()
-                    .get_mut(entity)
-                    .map(|pos| pos.0.z += 100.0);
-                self.state
-                    .write_component(entity, comp::phys::Vel(Vec3::zero()));
-                self.state.write_component(entity, comp::phys::ForceUpdate);
+                death::respawn(&mut self.state, entity);
             }
         }
 
@@ -350,6 +513,15 @@ impl Server {
             self.state.remove_chunk(key);
         }
 
+        // Populate the terrain around players with NPCs, and remove the ones nobody is near.
+        for spawn in self.spawner.maintain(&mut self.state, &self.world) {
+            let entity = self
+                .create_npc(spawn.pos, get_npc_name(spawn.kind), spawn.body)
+                .with(spawn.agent)
+                .build();
+            self.spawner.register(entity);
+        }
+
         // 6) Synchronise clients with the new state of the world.
         self.sync_clients();
 
@@ -380,15 +552,27 @@ impl Server {
             let entity = self.state.ecs_mut().create_entity_synced().build();
             let mut client = Client {
                 client_state: ClientState::Connected,
+                version_pending: true,
                 postbox,
                 last_ping: self.state.get_time(),
+                session_token: self.sessions.generate_token(),
+                previous_session_token: None,
+                last_whisper_from: None,
+                chat_limiter: RateLimiter::new(),
             };
 
+            // This must always be the first message, so that clients with a different protocol
+            // version can still understand it.
+            client.notify(ServerMsg::VersionInfo {
+                protocol_version: PROTOCOL_VERSION,
+            });
+
             // Return the state of the current world (all of the components that Sphynx tracks).
             client.notify(ServerMsg::InitialSync {
                 ecs_state: self.state.ecs().gen_state_package(),
                 entity_uid: self.state.ecs().uid_from_entity(entity).unwrap().into(), // Can't fail.
                 server_info: self.server_info.clone(),
+                session_token: client.session_token,
             });
 
             self.clients.add(entity, client);
@@ -404,12 +588,18 @@ impl Server {
         let mut frontend_events = Vec::new();
 
         let state = &mut self.state;
+        let chat_filter = &self.chat_filter;
         let mut new_chat_msgs = Vec::new();
         let mut disconnected_clients = Vec::new();
+        let mut dropped_clients = Vec::new();
+        let mut resume_requests = Vec::new();
+        let mut group_msgs = Vec::new();
+        let mut pet_msgs = Vec::new();
         let mut requested_chunks = Vec::new();
 
         self.clients.remove_if(|entity, client| {
             let mut disconnect = false;
+            let mut dropped = false;
             let new_msgs = client.postbox.new_messages();
 
             // Update client ping.
@@ -418,7 +608,40 @@ impl Server {
 
                 // Process incoming messages.
                 for msg in new_msgs {
+                    // Clients have to tell their protocol version first. Everything else they
+                    // send might not mean what we think it does until then, so they get our
+                    // version (which every client understands) and are disconnected.
+                    if client.version_pending {
+                        match msg {
+                            ClientMsg::VersionInfo { protocol_version }
+                                if protocol_version == PROTOCOL_VERSION =>
+                            {
+                                client.version_pending = false;
+                                continue;
+                            }
+                            ClientMsg::VersionInfo { protocol_version } => warn!(
+                                "Client uses protocol version {} but the server uses {}",
+                                protocol_version, PROTOCOL_VERSION
+                            ),
+                            _ => warn!("Client sent a message before its protocol version"),
+                        }
+                        client.notify(ServerMsg::VersionInfo {
+                            protocol_version: PROTOCOL_VERSION,
+                        });
+                        disconnect = true;
+                        break;
+                    }
+
                     match msg {
+                        ClientMsg::VersionInfo { protocol_version } => {
+                            if protocol_version != PROTOCOL_VERSION {
+                                warn!(
+                                    "Client uses protocol version {} but the server uses {}",
+                                    protocol_version, PROTOCOL_VERSION
+                                );
+                                disconnect = true;
+                            }
+                        }
                         ClientMsg::RequestState(requested_state) => match requested_state {
                             ClientState::Connected => disconnect = true, // Default state
                             ClientState::Registered => match client.client_state {
@@ -492,6 +715,10 @@ impl Server {
                             }
                             ClientState::Pending => {}
                         },
+                        ClientMsg::Resume { session_token } => match client.client_state {
+                            ClientState::Connected => resume_requests.push((entity, session_token)),
+                            _ => client.error_state(RequestStateError::Impossible),
+                        },
                         ClientMsg::Attack => match client.client_state {
                             ClientState::Character => {
                                 if state
@@ -518,7 +745,18 @@ impl Server {
                             ClientState::Registered
                             | ClientState::Spectator
                             | ClientState::Dead
-                            | ClientState::Character => new_chat_msgs.push((Some(entity), msg)),
+                            | ClientState::Character => {
+                                match chat_filter.check(
+                                    &mut client.chat_limiter,
+                                    state.get_time(),
+                                    msg,
+                                ) {
+                                    Ok(msg) => new_chat_msgs.push((Some(entity), msg)),
+                                    Err(reason) => {
+                                        client.notify(ServerMsg::Chat(ChatMsg::system(reason)))
+                                    }
+                                }
+                            }
                             ClientState::Pending => {}
                         },
                         ClientMsg::PlayerAnimation(animation_info) => {
@@ -564,6 +802,52 @@ impl Server {
                         ClientMsg::Disconnect => {
                             disconnect = true;
                         }
+                        ClientMsg::Equip { cell } => match client.client_state {
+                            ClientState::Character | ClientState::Dead => {
+                                if let Err(err) = equipment::equip(state, entity, cell) {
+                                    client
+                                        .notify(ServerMsg::Chat(ChatMsg::system(err.to_string())));
+                                }
+                            }
+                            _ => client.error_state(RequestStateError::Impossible),
+                        },
+                        ClientMsg::Pickup(uid) => match client.client_state {
+                            ClientState::Character | ClientState::Dead => {
+                                if let Err(err) = items::pickup(state, entity, uid) {
+                                    client
+                                        .notify(ServerMsg::Chat(ChatMsg::system(err.to_string())));
+                                }
+                            }
+                            _ => client.error_state(RequestStateError::Impossible),
+                        },
+                        ClientMsg::Unequip(slot) => match client.client_state {
+                            ClientState::Character | ClientState::Dead => {
+                                if let Err(err) = equipment::unequip(state, entity, slot) {
+                                    client
+                                        .notify(ServerMsg::Chat(ChatMsg::system(err.to_string())));
+                                }
+                            }
+                            _ => client.error_state(RequestStateError::Impossible),
+                        },
+                        // Telling other players about group changes needs all clients.
+                        ClientMsg::GroupInvite(_)
+                        | ClientMsg::GroupAccept
+                        | ClientMsg::GroupLeave
+                        | ClientMsg::GroupKick(_) => match client.client_state {
+                            ClientState::Character | ClientState::Dead => {
+                                group_msgs.push((entity, msg))
+                            }
+                            _ => client.error_state(RequestStateError::Impossible),
+                        },
+                        // Taming and pet commands need the spawner and all clients.
+                        ClientMsg::Tame(_) | ClientMsg::PetCommand(_) => {
+                            match client.client_state {
+                                ClientState::Character | ClientState::Dead => {
+                                    pet_msgs.push((entity, msg))
+                                }
+                                _ => client.error_state(RequestStateError::Impossible),
+                            }
+                        }
                     }
                 }
             } else if state.get_time() - client.last_ping > CLIENT_TIMEOUT || // Timeout
@@ -571,16 +855,33 @@ impl Server {
             // Postbox error
             {
                 disconnect = true;
+                dropped = true;
             } else if state.get_time() - client.last_ping > CLIENT_TIMEOUT * 0.5 {
                 // Try pinging the client if the timeout is nearing.
                 client.postbox.send_message(ServerMsg::Ping);
             }
 
             if disconnect {
+                // Characters of clients that lost their connection stay in the world for a while
+                // so that the client can reconnect and reclaim them.
+                let orphan = dropped
+                    && (client.client_state == ClientState::Character
+                        || client.client_state == ClientState::Dead);
                 if let Some(player) = state.ecs().read_storage::<comp::Player>().get(entity) {
-                    new_chat_msgs.push((None, format!("{} disconnected", &player.alias)));
+                    new_chat_msgs.push((
+                        None,
+                        if orphan {
+                            format!("{} lost connection", &player.alias)
+                        } else {
+                            format!("{} disconnected", &player.alias)
+                        },
+                    ));
+                }
+                if orphan {
+                    dropped_clients.push((entity, client.session_token, client.client_state));
+                } else {
+                    disconnected_clients.push((entity, client.session_token));
                 }
-                disconnected_clients.push(entity);
                 client.postbox.send_message(ServerMsg::Disconnect);
                 true
             } else {
@@ -591,26 +892,37 @@ impl Server {
         // Handle new chat messages.
         for (entity, msg) in new_chat_msgs {
             if let Some(entity) = entity {
+                match self.state.ecs().read_storage::<comp::Player>().get(entity) {
+                    Some(player) => self.chat_log.log(&player.alias, &msg),
+                    None => self.chat_log.log("<anon>", &msg),
+                }
+
                 // Handle chat commands.
                 if msg.starts_with("/") && msg.len() > 1 {
                     let argv = String::from(&msg[1..]);
                     self.process_chat_cmd(entity, argv);
                 } else {
-                    self.clients.notify_registered(ServerMsg::Chat(
-                        match self.state.ecs().read_storage::<comp::Player>().get(entity) {
-                            Some(player) => format!("[{}] {}", &player.alias, msg),
-                            None => format!("[<anon>] {}", msg),
-                        },
-                    ));
+                    self.say(entity, msg.clone());
                 }
             } else {
-                self.clients.notify_registered(ServerMsg::Chat(msg.clone()));
+                self.clients
+                    .notify_registered(ServerMsg::Chat(ChatMsg::system(msg.clone())));
             }
             frontend_events.push(Event::Chat { entity, msg });
         }
 
+        for (entity, msg) in group_msgs {
+            self.handle_group_msg(entity, msg);
+        }
+        for (entity, msg) in pet_msgs {
+            self.handle_pet_msg(entity, msg);
+        }
+
         // Handle client disconnects.
-        for entity in disconnected_clients {
+        for (entity, session_token) in disconnected_clients {
+            // Players that aren't in a group have nothing to leave.
+            let _ = self.leave_group(entity, None);
+            pets::save(&mut self.state, entity, session_token);
             if let Err(err) = self.state.ecs_mut().delete_entity_synced(entity) {
                 warn!("Failed to delete disconnected client: {:?}", err);
             }
@@ -618,6 +930,79 @@ impl Server {
             frontend_events.push(Event::ClientDisconnected { entity });
         }
 
+        // Keep the entities of dropped clients around until their session expires.
+        for (entity, session_token, client_state) in dropped_clients {
+            self.state.write_component(entity, comp::Control::default());
+            self.sessions.orphan(
+                session_token,
+                OrphanedSession {
+                    entity,
+                    client_state,
+                    disconnected_at: self.state.get_time(),
+                },
+            );
+
+            frontend_events.push(Event::ClientDisconnected { entity });
+        }
+
+        // Hand orphaned entities back to reconnecting clients.
+        for (entity, session_token) in resume_requests {
+            let session = match self.sessions.reclaim(session_token, self.state.get_time()) {
+                Some(session) => session,
+                None => {
+                    if let Some(client) = self.clients.get_mut(&entity) {
+                        // The session is gone, but its pets may have been saved.
+                        client.previous_session_token = Some(session_token);
+                        client.error_state(RequestStateError::Denied);
+                    }
+                    continue;
+                }
+            };
+            let mut client = match self.clients.remove(&entity) {
+                Some(client) => client,
+                None => continue,
+            };
+
+            // The placeholder entity created for the new connection is no longer needed.
+            if let Err(err) = self.state.ecs_mut().delete_entity_synced(entity) {
+                warn!(
+                    "Failed to delete placeholder entity of resumed client: {:?}",
+                    err
+                );
+            }
+
+            client.notify(ServerMsg::SetPlayerEntity(
+                self.state
+                    .ecs()
+                    .uid_from_entity(session.entity)
+                    .unwrap() // Can't fail, orphaned entities are never deleted.
+                    .into(),
+            ));
+            client.force_state(session.client_state);
+            self.state
+                .write_component(session.entity, comp::phys::ForceUpdate);
+            // The new connection has never been sent the inventory.
+            self.state.write_component(session.entity, InventoryUpdate);
+
+            if let Some(player) = self
+                .state
+                .ecs()
+                .read_storage::<comp::Player>()
+                .get(session.entity)
+            {
+                self.clients
+                    .notify_registered(ServerMsg::Chat(ChatMsg::system(format!(
+                        "{} reconnected",
+                        &player.alias
+                    ))));
+            }
+            self.clients.add(session.entity, client);
+
+            frontend_events.push(Event::ClientConnected {
+                entity: session.entity,
+            });
+        }
+
         // Generate requested chunks.
         for key in requested_chunks {
             self.generate_chunk(key);
@@ -752,6 +1137,23 @@ impl Server {
             }
         }
 
+        // Sync changed inventories to their owners
+        for (entity, inventory, _) in (
+            &self.state.ecs().entities(),
+            &self.state.ecs().read_storage::<Inventory>(),
+            &self.state.ecs().read_storage::<InventoryUpdate>(),
+        )
+            .join()
+        {
+            if let Some(client) = self.clients.get_mut(&entity) {
+                client.notify(ServerMsg::InventoryUpdate(inventory.clone()));
+            }
+        }
+        self.state
+            .ecs_mut()
+            .write_storage::<InventoryUpdate>()
+            .clear();
+
         // Remove all force flags.
         self.state
             .ecs_mut()
@@ -759,6 +1161,153 @@ impl Server {
             .clear();
     }
 
+    /// Send a chat message from a player to every player within earshot.
+    fn say(&mut self, entity: EcsEntity, text: String) {
+        let ecs = self.state.ecs();
+        let positions = ecs.read_storage::<comp::phys::Pos>();
+        let msg = ServerMsg::Chat(ChatMsg::say(
+            ecs.read_storage::<comp::Player>()
+                .get(entity)
+                .map(|player| player.alias.clone()),
+            text,
+        ));
+
+        match positions.get(entity) {
+            Some(sender_pos) => self.clients.notify_registered_if(msg, |e| {
+                positions
+                    .get(e)
+                    .map(|pos| pos.0.distance_squared(sender_pos.0) < SAY_DISTANCE.powf(2.0))
+                    .unwrap_or(false)
+            }),
+            // Players without a position (e.g. spectators) are heard by everybody.
+            None => self.clients.notify_registered(msg),
+        }
+    }
+
+    /// Send a message to every member of a group.
+    fn notify_group(&mut self, id: u64, msg: ServerMsg) {
+        for member in group::members(&self.state, id) {
+            self.clients.notify(member, msg.clone());
+        }
+    }
+
+    /// Carry out a request of a player to change groups, and tell everybody involved.
+    fn handle_group_msg(&mut self, entity: EcsEntity, msg: ClientMsg) {
+        let name = display_name(&self.state, entity).unwrap_or_default();
+        let result = match msg {
+            ClientMsg::GroupInvite(uid) => match self.state.ecs().entity_from_uid(uid) {
+                Some(target) => group::invite(&mut self.state, entity, target).map(|()| {
+                    self.clients.notify(
+                        target,
+                        ServerMsg::Chat(ChatMsg::system(format!(
+                            "{} invited you to their group. Type /accept to join.",
+                            name
+                        ))),
+                    );
+                    let target_name = display_name(&self.state, target).unwrap_or_default();
+                    self.clients.notify(
+                        entity,
+                        ServerMsg::Chat(ChatMsg::system(format!(
+                            "You invited {} to your group.",
+                            target_name
+                        ))),
+                    );
+                }),
+                None => Err(GroupError::NoPlayer),
+            },
+            ClientMsg::GroupAccept => group::accept(&mut self.state, entity).map(|group| {
+                self.notify_group(
+                    group.id,
+                    ServerMsg::Chat(ChatMsg::system(format!("{} joined the group.", name))),
+                )
+            }),
+            ClientMsg::GroupLeave => self.leave_group(entity, None),
+            ClientMsg::GroupKick(uid) => match self.state.ecs().entity_from_uid(uid) {
+                Some(target) => self.leave_group(target, Some(entity)),
+                None => Err(GroupError::NoPlayer),
+            },
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            self.clients
+                .notify(entity, ServerMsg::Chat(ChatMsg::system(err.to_string())));
+        }
+    }
+
+    /// Carry out a request of a player to tame an animal or order its pets around.
+    fn handle_pet_msg(&mut self, entity: EcsEntity, msg: ClientMsg) {
+        let result = match msg {
+            ClientMsg::Tame(uid) => match self.state.ecs().entity_from_uid(uid) {
+                Some(target) => pets::tame(&mut self.state, entity, target).map(|()| {
+                    // Tamed animals stop being tracked by the spawner.
+                    self.spawner.forget(target);
+                    let name = display_name(&self.state, target).unwrap_or_default();
+                    format!("{} is your pet now!", name)
+                }),
+                None => Err(PetError::NoAnimal),
+            },
+            ClientMsg::PetCommand(command) => {
+                pets::command(&mut self.state, entity, command).map(|_| {
+                    String::from(match command {
+                        comp::PetCommand::Follow => "Your pets follow you.",
+                        comp::PetCommand::Stay => "Your pets stay where they are.",
+                        comp::PetCommand::Attack(_) => "Your pets attack!",
+                    })
+                })
+            }
+            _ => return,
+        };
+
+        let text = match result {
+            Ok(text) => text,
+            Err(err) => err.to_string(),
+        };
+        self.clients
+            .notify(entity, ServerMsg::Chat(ChatMsg::system(text)));
+    }
+
+    /// Take a player out of their group, either on their own or kicked by the group leader, and
+    /// tell everybody that was in the group.
+    fn leave_group(
+        &mut self,
+        entity: EcsEntity,
+        kicked_by: Option<EcsEntity>,
+    ) -> Result<(), GroupError> {
+        let members = match self.state.read_component_cloned::<comp::Group>(entity) {
+            Some(group) => group::members(&self.state, group.id),
+            None => Vec::new(),
+        };
+        let group = match kicked_by {
+            Some(leader) => group::kick(&mut self.state, leader, entity)?,
+            None => group::leave(&mut self.state, entity)?,
+        };
+
+        let name = display_name(&self.state, entity).unwrap_or_default();
+        let mut msgs = vec![match kicked_by {
+            Some(_) => format!("{} was kicked from the group.", name),
+            None => format!("{} left the group.", name),
+        }];
+        match group::members(&self.state, group.id).first() {
+            None => msgs.push(String::from("The group was disbanded.")),
+            Some(&member) => {
+                let leader = self.state.read_component_cloned::<comp::Group>(member);
+                if leader.map_or(false, |new| new.leader != group.leader) {
+                    let new_leader = display_name(&self.state, member).unwrap_or_default();
+                    msgs.push(format!("{} now leads the group.", new_leader));
+                }
+            }
+        }
+
+        for msg in msgs {
+            for &member in &members {
+                self.clients
+                    .notify(member, ServerMsg::Chat(ChatMsg::system(msg.clone())));
+            }
+        }
+        Ok(())
+    }
+
     pub fn generate_chunk(&mut self, key: Vec2<i32>) {
         if self.pending_chunks.insert(key) {
             let chunk_tx = self.chunk_tx.clone();
@@ -785,16 +1334,88 @@ impl Server {
             None => {
                 self.clients.notify(
                     entity,
-                    ServerMsg::Chat(format!(
+                    ServerMsg::Chat(ChatMsg::command(format!(
                         "Unrecognised command: '/{}'\ntype '/help' for a list of available commands",
                         kwd
-                    )),
+                    ))),
                 );
             }
         }
     }
 }
 
+/// Build a non-player character, see `Server::create_npc`.
+fn npc_builder(
+    state: &mut State,
+    pos: comp::phys::Pos,
+    name: String,
+    body: comp::Body,
+) -> EcsEntityBuilder {
+    state
+        .ecs_mut()
+        .create_entity_synced()
+        .with(pos)
+        .with(comp::phys::Vel(Vec3::zero()))
+        .with(comp::phys::Ori(Vec3::unit_y()))
+        .with(comp::Control::default())
+        .with(comp::AnimationInfo::default())
+        .with(comp::Actor::Character { name, body })
+        .with(comp::Equipment::for_body(&body))
+        .with(comp::Stats::default())
+        .with(comp::phys::ForceUpdate)
+}
+
+/// The name of a player, or of a character if it isn't a player's.
+fn display_name(state: &State, entity: EcsEntity) -> Option<String> {
+    let ecs = state.ecs();
+    ecs.read_storage::<comp::Player>()
+        .get(entity)
+        .map(|player| player.alias.clone())
+        .or_else(|| {
+            ecs.read_storage::<comp::Actor>()
+                .get(entity)
+                .map(|actor| match actor {
+                    comp::Actor::Character { name, .. } => name.clone(),
+                })
+        })
+}
+
+/// Describe how the player with the given alias died.
+fn death_message(state: &State, alias: &str, cause: comp::HealthSource) -> String {
+    match cause {
+        comp::HealthSource::Attack { by } => {
+            let attacker = state
+                .ecs()
+                .entity_from_uid(by.into())
+                .and_then(|attacker| display_name(state, attacker));
+
+            match attacker {
+                Some(attacker) => format!("{} was killed by {}", alias, attacker),
+                None => format!("{} was killed", alias),
+            }
+        }
+        comp::HealthSource::Poison { by } => {
+            let poisoner = by.and_then(|by| {
+                let poisoner = state.ecs().entity_from_uid(by.into())?;
+                display_name(state, poisoner)
+            });
+
+            match poisoner {
+                Some(poisoner) => format!("{} was poisoned by {}", alias, poisoner),
+                None => format!("{} succumbed to poison", alias),
+            }
+        }
+        // Healing can't kill, but in case it ever does
+        comp::HealthSource::Heal => format!("{} died", alias),
+        comp::HealthSource::Suicide => format!("{} gave up on life", alias),
+        comp::HealthSource::Drowning => format!("{} drowned", alias),
+        comp::HealthSource::Fall => format!("{} hit the ground too hard", alias),
+        comp::HealthSource::Lava => format!("{} tried to swim in lava", alias),
+        comp::HealthSource::Void => format!("{} fell out of the world", alias),
+        comp::HealthSource::Unknown => format!("{} died", alias),
+    }
+}
+
 impl Drop for Server {
     fn drop(&mut self) {
         self.clients.notify_registered(ServerMsg::Shutdown);
@@ -0,0 +1,295 @@
+//! Taming animals, giving them orders, and keeping them with the character of their owner while
+//! the owner is offline.
+
+use crate::{death, npc_builder};
+use common::{
+    comp::{self, phys::Pos, Agent, PetCommand},
+    state::State,
+};
+use log::warn;
+use specs::{Builder, Entity as EcsEntity, Join};
+use std::{collections::HashMap, fmt};
+use vek::*;
+
+/// The most pets a character can have at once.
+pub const MAX_PETS: usize = 3;
+/// How close (in blocks) an animal has to be to tame it.
+pub const TAME_RANGE: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PetError {
+    NoAnimal,
+    TooFar,
+    Tamed,
+    Hostile,
+    TooManyPets,
+    NoPets,
+    Player,
+    Dead,
+}
+
+impl fmt::Display for PetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PetError::NoAnimal => "There's no animal to tame there!",
+            PetError::TooFar => "That animal is too far away!",
+            PetError::Tamed => "That animal already has an owner!",
+            PetError::Hostile => "That animal is too angry to be tamed!",
+            PetError::TooManyPets => "You can't look after any more pets!",
+            PetError::NoPets => "You don't have any pets!",
+            PetError::Player => "Your pets won't attack other players!",
+            PetError::Dead => "You can't do that while dead!",
+        })
+    }
+}
+
+/// A pet that is waiting for its owner to come back.
+#[derive(Clone, Debug)]
+pub struct SavedPet {
+    pub name: String,
+    pub body: comp::Body,
+    pub stats: comp::Stats,
+}
+
+/// The pets of characters whose players left, by the session token of the player's last
+/// connection and the character name. Unlike aliases, session tokens are only known to their
+/// client, so nobody else can claim the pets. Pets come back when the same client reconnects and
+/// plays the same character again.
+#[derive(Default)]
+pub struct SavedPets(pub HashMap<(u64, String), Vec<SavedPet>>);
+
+fn uid(state: &State, entity: EcsEntity) -> Option<u64> {
+    state.ecs().uid_from_entity(entity).map(u64::from)
+}
+
+// Pets are kept with the character of a player, rather than the player itself.
+fn character_key(state: &State, entity: EcsEntity, session_token: u64) -> Option<(u64, String)> {
+    match state.ecs().read_storage::<comp::Actor>().get(entity)? {
+        comp::Actor::Character { name, .. } => Some((session_token, name.clone())),
+    }
+}
+
+/// The pets of a player.
+pub fn pets(state: &State, owner: EcsEntity) -> Vec<EcsEntity> {
+    let owner = match uid(state, owner) {
+        Some(owner) => owner,
+        None => return Vec::new(),
+    };
+    (
+        &state.ecs().entities(),
+        &state.ecs().read_storage::<Agent>(),
+    )
+        .join()
+        .filter(|(_, agent)| agent.owner() == Some(owner))
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Make a wild animal next to a player its pet. Animals that are after the player can't be tamed.
+pub fn tame(state: &mut State, entity: EcsEntity, target: EcsEntity) -> Result<(), PetError> {
+    if !death::is_alive(state, entity) {
+        return Err(PetError::Dead);
+    }
+    match state.read_component_cloned::<comp::Actor>(target) {
+        Some(comp::Actor::Character {
+            body: comp::Body::Quadruped(_),
+            ..
+        })
+        | Some(comp::Actor::Character {
+            body: comp::Body::QuadrupedMedium(_),
+            ..
+        }) if death::is_alive(state, target) => {}
+        _ => return Err(PetError::NoAnimal),
+    }
+    let agent = state
+        .read_component_cloned::<Agent>(target)
+        .ok_or(PetError::NoAnimal)?;
+    if agent.owner().is_some() {
+        return Err(PetError::Tamed);
+    }
+    if agent.threats.iter().any(|threat| threat.entity == entity) {
+        return Err(PetError::Hostile);
+    }
+    match (
+        state.read_component_cloned::<Pos>(entity),
+        state.read_component_cloned::<Pos>(target),
+    ) {
+        (Some(pos), Some(target_pos)) if pos.0.distance(target_pos.0) <= TAME_RANGE => {}
+        _ => return Err(PetError::TooFar),
+    }
+    if pets(state, entity).len() >= MAX_PETS {
+        return Err(PetError::TooManyPets);
+    }
+
+    let owner = uid(state, entity).ok_or(PetError::NoAnimal)?;
+    state.write_component(target, Agent::pet(owner));
+    Ok(())
+}
+
+/// Give an order to all pets of a player, returning how many pets got it. Pets don't attack
+/// players, so that they can't be used to get around the PvP rules.
+pub fn command(
+    state: &mut State,
+    entity: EcsEntity,
+    command: PetCommand,
+) -> Result<usize, PetError> {
+    let pets = pets(state, entity);
+    if pets.is_empty() {
+        return Err(PetError::NoPets);
+    }
+    if let PetCommand::Attack(target) = command {
+        let target = state.ecs().entity_from_uid(target);
+        if target.map_or(false, |target| {
+            state
+                .read_component_cloned::<comp::Player>(target)
+                .is_some()
+        }) {
+            return Err(PetError::Player);
+        }
+    }
+    let mut agents = state.ecs().write_storage::<Agent>();
+    for &pet in &pets {
+        if let Some(agent) = agents.get_mut(pet) {
+            if let comp::AgentKind::Pet { owner, .. } = agent.kind {
+                agent.kind = comp::AgentKind::Pet { owner, command };
+                agent.threats.clear();
+            }
+        }
+    }
+    Ok(pets.len())
+}
+
+/// Take the pets of a player that is leaving out of the world, and keep them with its character
+/// and the token of its session.
+pub fn save(state: &mut State, owner: EcsEntity, session_token: u64) {
+    let pets = pets(state, owner);
+    let key = match character_key(state, owner, session_token) {
+        Some(key) if !pets.is_empty() => key,
+        _ => return,
+    };
+
+    let saved = pets
+        .iter()
+        .filter(|&&pet| death::is_alive(state, pet))
+        .filter_map(
+            |&pet| match state.read_component_cloned::<comp::Actor>(pet)? {
+                comp::Actor::Character { name, body } => Some(SavedPet {
+                    name,
+                    body,
+                    stats: state.read_component_cloned::<comp::Stats>(pet)?,
+                }),
+            },
+        )
+        .collect::<Vec<_>>();
+    state
+        .ecs_mut()
+        .write_resource::<SavedPets>()
+        .0
+        .insert(key, saved);
+
+    for pet in pets {
+        if let Err(err) = state.ecs_mut().delete_entity_synced(pet) {
+            warn!("Failed to delete pet of leaving player: {:?}", err);
+        }
+    }
+}
+
+/// Bring back the pets kept with the character of a player and the token of its previous session,
+/// next to it.
+pub fn restore(state: &mut State, owner: EcsEntity, session_token: u64) {
+    let (key, pos, owner_uid) = match (
+        character_key(state, owner, session_token),
+        state.read_component_cloned::<Pos>(owner),
+        uid(state, owner),
+    ) {
+        (Some(key), Some(pos), Some(owner_uid)) => (key, pos, owner_uid),
+        _ => return,
+    };
+    let saved = match state.ecs_mut().write_resource::<SavedPets>().0.remove(&key) {
+        Some(saved) => saved,
+        None => return,
+    };
+
+    for (i, pet) in saved.into_iter().enumerate() {
+        let offset = Vec3::new(1.0 + i as f32, 1.0, 0.0);
+        let entity = npc_builder(state, Pos(pos.0 + offset), pet.name, pet.body)
+            .with(Agent::pet(owner_uid))
+            .build();
+        state.write_component(entity, pet.stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.ecs_mut().add_resource(SavedPets::default());
+        state
+    }
+
+    fn player(state: &mut State) -> EcsEntity {
+        npc_builder(
+            state,
+            Pos(Vec3::zero()),
+            "Character".to_owned(),
+            comp::Body::Humanoid(comp::HumanoidBody::random()),
+        )
+        .with(comp::Player::new("test".to_owned(), None))
+        .build()
+    }
+
+    fn pig(state: &mut State, x: f32) -> EcsEntity {
+        npc_builder(
+            state,
+            Pos(Vec3::new(x, 0.0, 0.0)),
+            "Pig".to_owned(),
+            comp::Body::Quadruped(comp::QuadrupedBody::random()),
+        )
+        .with(Agent::wanderer())
+        .build()
+    }
+
+    #[test]
+    fn tame_and_command() {
+        let mut state = state();
+        let owner = player(&mut state);
+        let (near, far) = (pig(&mut state, 2.0), pig(&mut state, 50.0));
+
+        assert_eq!(tame(&mut state, owner, owner), Err(PetError::NoAnimal));
+        assert_eq!(tame(&mut state, owner, far), Err(PetError::TooFar));
+        assert_eq!(
+            command(&mut state, owner, PetCommand::Stay),
+            Err(PetError::NoPets)
+        );
+        assert_eq!(tame(&mut state, owner, near), Ok(()));
+        assert_eq!(tame(&mut state, owner, near), Err(PetError::Tamed));
+        assert_eq!(pets(&state, owner), vec![near]);
+
+        assert_eq!(command(&mut state, owner, PetCommand::Stay), Ok(1));
+        let agent = state.read_component_cloned::<Agent>(near).unwrap();
+        assert_eq!(agent.command(), Some(PetCommand::Stay));
+    }
+
+    #[test]
+    fn pets_wait_for_their_owner() {
+        let mut state = state();
+        let owner = player(&mut state);
+        let pet = pig(&mut state, 1.0);
+        tame(&mut state, owner, pet).unwrap();
+
+        save(&mut state, owner, 1);
+        assert!(!state.ecs().is_alive(pet));
+        assert!(pets(&state, owner).is_empty());
+
+        // Other clients don't get them, even if they play a character with the same name
+        restore(&mut state, owner, 2);
+        assert!(pets(&state, owner).is_empty());
+
+        // The same character of the same client gets them back
+        restore(&mut state, owner, 1);
+        assert_eq!(pets(&state, owner).len(), 1);
+        assert!(state.ecs().read_resource::<SavedPets>().0.is_empty());
+    }
+}
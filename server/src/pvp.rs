@@ -0,0 +1,100 @@
+//! Loading the rules for fights between players, see `common::comp::pvp`.
+
+use common::comp::pvp::{PvpMode, SafeZone};
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+use vek::*;
+
+/// Parse a `PvpMode` from `off`, `on` or `opt-in`, ignoring case.
+pub fn parse_mode(mode: &str) -> Option<PvpMode> {
+    match mode.trim().to_lowercase().as_str() {
+        "off" => Some(PvpMode::Off),
+        "on" => Some(PvpMode::On),
+        "opt-in" | "optin" => Some(PvpMode::OptIn),
+        _ => None,
+    }
+}
+
+/// Parse a safe zone from `<min x> <min y> <max x> <max y> <name>`. The name may contain spaces.
+fn parse_safe_zone(line: &str) -> Option<SafeZone> {
+    let mut parts = line.split_whitespace();
+    let mut coord = || parts.next()?.parse::<f32>().ok();
+    let (min_x, min_y, max_x, max_y) = (coord()?, coord()?, coord()?, coord()?);
+    let name = parts.collect::<Vec<_>>().join(" ");
+    if name.is_empty() || min_x > max_x || min_y > max_y {
+        return None;
+    }
+    Some(SafeZone {
+        name,
+        min: Vec2::new(min_x, min_y),
+        max: Vec2::new(max_x, max_y),
+    })
+}
+
+/// Read the safe zones listed in a file, one per line as `<min x> <min y> <max x> <max y> <name>`.
+/// Empty lines and lines starting with `#` are skipped.
+pub fn load_safe_zones<P: AsRef<Path>>(path: P) -> Result<Vec<SafeZone>, io::Error> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .map(|(i, line)| {
+            parse_safe_zone(line).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid safe zone on line {}: {}", i + 1, line),
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn modes() {
+        assert_eq!(parse_mode("off"), Some(PvpMode::Off));
+        assert_eq!(parse_mode("On"), Some(PvpMode::On));
+        assert_eq!(parse_mode(" opt-in\n"), Some(PvpMode::OptIn));
+        assert_eq!(parse_mode("sometimes"), None);
+    }
+
+    #[test]
+    fn safe_zones() {
+        assert_eq!(
+            parse_safe_zone("-10 -20.5 30 40 Old Town"),
+            Some(SafeZone {
+                name: "Old Town".to_owned(),
+                min: Vec2::new(-10.0, -20.5),
+                max: Vec2::new(30.0, 40.0),
+            })
+        );
+        // Missing name, missing coordinate, corners the wrong way around
+        assert_eq!(parse_safe_zone("0 0 10 10"), None);
+        assert_eq!(parse_safe_zone("0 0 10 Town"), None);
+        assert_eq!(parse_safe_zone("10 0 0 10 Town"), None);
+
+        let path = env::temp_dir().join("veloren-safe-zones-test.txt");
+        fs::write(
+            &path,
+            "# Spawn\n0 0 64 64 Spawn\n\n100 100 200 200 Market\n",
+        )
+        .unwrap();
+        let zones = load_safe_zones(&path).unwrap();
+        assert_eq!(
+            zones.iter().map(|zone| &zone.name[..]).collect::<Vec<_>>(),
+            vec!["Spawn", "Market"]
+        );
+
+        fs::write(&path, "0 0 64 64 Spawn\nsomewhere\n").unwrap();
+        let err = load_safe_zones(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
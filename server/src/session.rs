@@ -0,0 +1,133 @@
+use common::msg::ClientState;
+use specs::Entity as EcsEntity;
+use std::collections::HashMap;
+
+/// How long (in seconds) the entity of a client that lost its connection is kept in the world,
+/// waiting for the client to reconnect and reclaim it.
+pub const SESSION_GRACE_PERIOD: f64 = 60.0;
+
+/// The remains of a client session whose connection dropped unexpectedly.
+pub struct OrphanedSession {
+    pub entity: EcsEntity,
+    pub client_state: ClientState,
+    pub disconnected_at: f64,
+}
+
+impl OrphanedSession {
+    fn expired(&self, time: f64) -> bool {
+        time - self.disconnected_at > SESSION_GRACE_PERIOD
+    }
+}
+
+/// Keeps track of orphaned sessions by the token that was handed to their client on connection.
+pub struct Sessions {
+    orphaned: HashMap<u64, OrphanedSession>,
+}
+
+impl Sessions {
+    pub fn empty() -> Self {
+        Self {
+            orphaned: HashMap::new(),
+        }
+    }
+
+    /// Generate a new, unguessable session token.
+    pub fn generate_token(&self) -> u64 {
+        loop {
+            let token = rand::random::<u64>();
+            if !self.orphaned.contains_key(&token) {
+                break token;
+            }
+        }
+    }
+
+    pub fn orphan(&mut self, token: u64, session: OrphanedSession) {
+        self.orphaned.insert(token, session);
+    }
+
+    /// Take the orphaned session with the given token, if it hasn't expired yet. Expired sessions
+    /// are left for `drain_expired` to clean up.
+    pub fn reclaim(&mut self, token: u64, time: f64) -> Option<OrphanedSession> {
+        match self.orphaned.get(&token) {
+            Some(session) if !session.expired(time) => self.orphaned.remove(&token),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut_by_entity<'a>(
+        &'a mut self,
+        entity: EcsEntity,
+    ) -> Option<&'a mut OrphanedSession> {
+        self.orphaned
+            .values_mut()
+            .find(|session| session.entity == entity)
+    }
+
+    /// Remove and return every session that has been orphaned for longer than the grace period,
+    /// with its token.
+    pub fn drain_expired(&mut self, time: f64) -> Vec<(u64, OrphanedSession)> {
+        let expired = self
+            .orphaned
+            .iter()
+            .filter(|(_, session)| session.expired(time))
+            .map(|(token, _)| *token)
+            .collect::<Vec<_>>();
+
+        expired
+            .into_iter()
+            .filter_map(|token| Some((token, self.orphaned.remove(&token)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, World};
+
+    fn session(world: &mut World, disconnected_at: f64) -> OrphanedSession {
+        OrphanedSession {
+            entity: world.create_entity().build(),
+            client_state: ClientState::Character,
+            disconnected_at,
+        }
+    }
+
+    #[test]
+    fn orphan_and_reclaim() {
+        let mut world = World::new();
+        let mut sessions = Sessions::empty();
+        let orphaned = session(&mut world, 10.0);
+        let entity = orphaned.entity;
+        let token = sessions.generate_token();
+        sessions.orphan(token, orphaned);
+
+        assert!(sessions.get_mut_by_entity(entity).is_some());
+        assert!(sessions.reclaim(token.wrapping_add(1), 20.0).is_none());
+        assert_eq!(
+            sessions.reclaim(token, 20.0).map(|s| s.entity),
+            Some(entity)
+        );
+        // Sessions can only be reclaimed once
+        assert!(sessions.reclaim(token, 20.0).is_none());
+        assert!(sessions.get_mut_by_entity(entity).is_none());
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let mut world = World::new();
+        let mut sessions = Sessions::empty();
+        sessions.orphan(1, session(&mut world, 0.0));
+        sessions.orphan(2, session(&mut world, 30.0));
+
+        let late = SESSION_GRACE_PERIOD + 10.0;
+        assert!(sessions.reclaim(1, late).is_none());
+        let expired = sessions.drain_expired(late);
+        assert_eq!(
+            expired.iter().map(|(token, _)| *token).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert!(sessions.reclaim(1, late).is_none());
+        assert!(sessions.reclaim(2, late).is_some());
+    }
+}
@@ -0,0 +1,341 @@
+//! Populates the terrain around players with NPCs that suit the biome they live in, and removes
+//! them again once nobody is around.
+
+use common::{
+    comp::{self, phys::Pos},
+    npc::NpcKind,
+    state::State,
+    terrain::{BiomeKind, TerrainMap},
+    vol::{ReadVol, Vox},
+};
+use log::warn;
+use rand::Rng;
+use specs::{Entity as EcsEntity, Join};
+use std::collections::{HashMap, HashSet};
+use vek::*;
+use world::{
+    sim::{SimChunk, MOUNTAIN_HEIGHT, SEA_LEVEL},
+    World,
+};
+
+// The chance that a chunk with room for one more NPC gets one each time the spawner runs
+const SPAWN_CHANCE: f32 = 0.25;
+
+#[derive(Clone, Debug)]
+pub struct SpawnSettings {
+    /// The number of NPCs a chunk holds in the most crowded of biomes.
+    pub max_per_chunk: f32,
+    /// NPCs spawn in the chunks within this many chunks of a player...
+    pub spawn_radius: i32,
+    /// ...but no closer than this many blocks to any player.
+    pub min_spawn_dist: f32,
+    /// NPCs that are further than this many blocks away from every player get removed.
+    pub despawn_dist: f32,
+    /// How often (in seconds) to spawn and despawn NPCs.
+    pub interval: f64,
+}
+
+impl Default for SpawnSettings {
+    fn default() -> Self {
+        Self {
+            max_per_chunk: 2.0,
+            spawn_radius: 4,
+            min_spawn_dist: 48.0,
+            despawn_dist: 192.0,
+            interval: 2.0,
+        }
+    }
+}
+
+/// The creatures that live in a biome: how common each of them is relative to the others, and
+/// the largest group they roam in.
+fn habitants(biome: BiomeKind) -> &'static [(NpcKind, f32, u32)] {
+    match biome {
+        BiomeKind::Grassland => &[
+            (NpcKind::Pig, 3.0, 3),
+            (NpcKind::Wolf, 1.0, 3),
+            (NpcKind::Humanoid, 0.5, 1),
+        ],
+        BiomeKind::Forest => &[
+            (NpcKind::Wolf, 2.0, 4),
+            (NpcKind::Pig, 1.0, 2),
+            (NpcKind::Humanoid, 0.5, 1),
+        ],
+        BiomeKind::Mountain => &[(NpcKind::Wolf, 1.0, 2)],
+        BiomeKind::Snowlands => &[(NpcKind::Wolf, 1.0, 5)],
+        BiomeKind::Desert => &[(NpcKind::Humanoid, 1.0, 2)],
+        BiomeKind::Swamp => &[(NpcKind::Humanoid, 1.0, 1), (NpcKind::Pig, 0.5, 2)],
+        BiomeKind::Ocean | BiomeKind::Void => &[],
+    }
+}
+
+/// How crowded a chunk gets, as a fraction of `SpawnSettings::max_per_chunk`.
+pub fn density(biome: BiomeKind, sim_chunk: &SimChunk) -> f32 {
+    let base = match biome {
+        BiomeKind::Forest => 1.0,
+        BiomeKind::Grassland => 0.8,
+        BiomeKind::Swamp => 0.6,
+        BiomeKind::Mountain => 0.3,
+        BiomeKind::Snowlands | BiomeKind::Desert => 0.2,
+        BiomeKind::Ocean | BiomeKind::Void => 0.0,
+    };
+    // Trees give shelter, while high places are cold and barren.
+    let shelter = 0.5 + sim_chunk.tree_density.max(0.0).min(1.0) * 0.5;
+    let altitude = ((sim_chunk.alt - SEA_LEVEL) / MOUNTAIN_HEIGHT)
+        .max(0.0)
+        .min(0.8);
+    base * shelter * (1.0 - altitude)
+}
+
+/// The highest spot of a column of the terrain that a creature can stand on, out of the water.
+fn find_ground(
+    terrain: &TerrainMap,
+    wpos2d: Vec2<i32>,
+    min_z: i32,
+    max_z: i32,
+) -> Option<Vec3<f32>> {
+    let block = |z| terrain.get(Vec3::new(wpos2d.x, wpos2d.y, z)).ok();
+    (min_z + 1..max_z).rev().find_map(|z| {
+        let floor = block(z - 1)?;
+        let (feet, head) = (block(z)?, block(z + 1)?);
+        if floor.is_solid() && feet.is_empty() && head.is_empty() {
+            Some(Vec3::new(
+                wpos2d.x as f32 + 0.5,
+                wpos2d.y as f32 + 0.5,
+                z as f32,
+            ))
+        } else {
+            None
+        }
+    })
+}
+
+/// An NPC the server should create.
+pub struct Spawn {
+    pub pos: Pos,
+    pub kind: NpcKind,
+    pub body: comp::Body,
+    pub agent: comp::Agent,
+}
+
+pub struct Spawner {
+    settings: SpawnSettings,
+    spawned: Vec<EcsEntity>,
+    next_pack: u32,
+    last_run: f64,
+}
+
+impl Spawner {
+    pub fn new(settings: SpawnSettings) -> Self {
+        Self {
+            settings,
+            spawned: Vec::new(),
+            next_pack: 0,
+            last_run: 0.0,
+        }
+    }
+
+    /// Keep track of an NPC created for a `Spawn`, so that it gets removed when nobody is near.
+    pub fn register(&mut self, entity: EcsEntity) {
+        self.spawned.push(entity);
+    }
+
+    /// Stop keeping track of an NPC, e.g. because a player tamed it.
+    pub fn forget(&mut self, entity: EcsEntity) {
+        self.spawned.retain(|&spawned| spawned != entity);
+    }
+
+    /// Remove the NPCs that nobody is near anymore, and decide which NPCs to create in the
+    /// chunks around players.
+    pub fn maintain(&mut self, state: &mut State, world: &World) -> Vec<Spawn> {
+        let time = state.get_time();
+        if time - self.last_run < self.settings.interval {
+            return Vec::new();
+        }
+        self.last_run = time;
+
+        let players = (
+            &state.ecs().read_storage::<comp::Player>(),
+            &state.ecs().read_storage::<Pos>(),
+        )
+            .join()
+            .map(|(_, pos)| pos.0)
+            .collect::<Vec<_>>();
+
+        self.despawn(state, &players);
+
+        // Count the NPCs in each chunk
+        let terrain = state.terrain();
+        let mut population = HashMap::new();
+        for &entity in &self.spawned {
+            if let Some(pos) = state.ecs().read_storage::<Pos>().get(entity) {
+                *population
+                    .entry(terrain.pos_key(pos.0.map(|e| e.floor() as i32)))
+                    .or_insert(0.0) += 1.0;
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut spawns = Vec::new();
+        let mut visited = HashSet::new();
+        let radius = self.settings.spawn_radius;
+        for player_pos in &players {
+            let player_key = terrain.pos_key(player_pos.map(|e| e.floor() as i32));
+            for x in -radius..=radius {
+                for y in -radius..=radius {
+                    let key = player_key + Vec2::new(x, y);
+                    if !visited.insert(key) {
+                        continue;
+                    }
+
+                    let (chunk, sim_chunk) =
+                        match (terrain.get_key(key), world.sim().get(key.map(|e| e as u32))) {
+                            (Some(chunk), Some(sim_chunk)) => (chunk, sim_chunk),
+                            _ => continue,
+                        };
+                    let biome = chunk.meta().biome();
+                    let room = density(biome, sim_chunk) * self.settings.max_per_chunk
+                        - population.get(&key).cloned().unwrap_or(0.0);
+                    if rng.gen::<f32>() >= room.min(1.0) * SPAWN_CHANCE {
+                        continue;
+                    }
+
+                    // Pick a creature that lives here
+                    let habitants = habitants(biome);
+                    let total = habitants.iter().map(|(_, weight, _)| weight).sum::<f32>();
+                    let mut pick = rng.gen::<f32>() * total;
+                    let (kind, max_group) = match habitants.iter().find(|(_, weight, _)| {
+                        pick -= weight;
+                        pick < 0.0
+                    }) {
+                        Some(&(kind, _, max_group)) => (kind, max_group),
+                        None => continue,
+                    };
+
+                    // And a place to put it that nobody is looking at
+                    let wpos2d = terrain.key_pos(key)
+                        + Vec2::new(
+                            rng.gen_range(0, TerrainMap::chunk_size().x as i32),
+                            rng.gen_range(0, TerrainMap::chunk_size().y as i32),
+                        );
+                    let pos =
+                        match find_ground(&terrain, wpos2d, chunk.get_min_z(), chunk.get_max_z()) {
+                            Some(pos)
+                                if players.iter().all(|player_pos| {
+                                    player_pos.distance(pos) > self.settings.min_spawn_dist
+                                }) =>
+                            {
+                                pos
+                            }
+                            _ => continue,
+                        };
+
+                    let group = rng
+                        .gen_range(1, max_group + 1)
+                        .min(room.ceil().max(1.0) as u32);
+                    let pack = if group > 1 {
+                        self.next_pack = self.next_pack.wrapping_add(1);
+                        Some(self.next_pack)
+                    } else {
+                        None
+                    };
+
+                    for _ in 0..group {
+                        let (body, agent) = match kind {
+                            NpcKind::Pig => (
+                                comp::Body::Quadruped(comp::QuadrupedBody::random()),
+                                comp::Agent::wanderer(),
+                            ),
+                            NpcKind::Wolf => (
+                                comp::Body::QuadrupedMedium(comp::QuadrupedMediumBody::random()),
+                                comp::Agent::enemy(),
+                            ),
+                            NpcKind::Humanoid => (
+                                comp::Body::Humanoid(comp::HumanoidBody::random()),
+                                comp::Agent::enemy(),
+                            ),
+                        };
+                        spawns.push(Spawn {
+                            pos: Pos(pos),
+                            kind,
+                            body,
+                            agent: match pack {
+                                Some(pack) => agent.with_pack(pack),
+                                None => agent,
+                            },
+                        });
+                    }
+                    *population.entry(key).or_insert(0.0) += group as f32;
+                }
+            }
+        }
+
+        spawns
+    }
+
+    /// Remove the spawned NPCs that are far away from every player, and forget the ones that
+    /// are gone.
+    fn despawn(&mut self, state: &mut State, players: &[Vec3<f32>]) {
+        let despawn_dist = self.settings.despawn_dist;
+        let mut far_away = Vec::new();
+        {
+            let ecs = state.ecs();
+            let entities = ecs.entities();
+            let positions = ecs.read_storage::<Pos>();
+            self.spawned.retain(|&entity| {
+                if !entities.is_alive(entity) {
+                    return false;
+                }
+                match positions.get(entity) {
+                    Some(pos) if players.iter().any(|p| p.distance(pos.0) <= despawn_dist) => true,
+                    _ => {
+                        far_away.push(entity);
+                        false
+                    }
+                }
+            });
+        }
+
+        for entity in far_away {
+            if let Err(err) = state.ecs_mut().delete_entity_synced(entity) {
+                warn!("Failed to delete far away NPC: {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sim_chunk(alt: f32, tree_density: f32) -> SimChunk {
+        SimChunk {
+            chaos: 0.0,
+            alt_base: 0.0,
+            alt,
+            temp: 0.0,
+            rockiness: 0.0,
+            tree_density,
+        }
+    }
+
+    #[test]
+    fn oceans_are_empty() {
+        let chunk = sim_chunk(SEA_LEVEL - 10.0, 0.0);
+        assert_eq!(density(BiomeKind::Ocean, &chunk), 0.0);
+        assert!(habitants(BiomeKind::Ocean).is_empty());
+    }
+
+    #[test]
+    fn forests_are_crowded_and_peaks_are_barren() {
+        let forest = density(BiomeKind::Forest, &sim_chunk(SEA_LEVEL + 20.0, 1.0));
+        let grassland = density(BiomeKind::Grassland, &sim_chunk(SEA_LEVEL + 20.0, 0.2));
+        let peak = density(
+            BiomeKind::Mountain,
+            &sim_chunk(SEA_LEVEL + MOUNTAIN_HEIGHT, 0.0),
+        );
+        assert!(forest > grassland);
+        assert!(grassland > peak);
+        assert!(peak > 0.0);
+    }
+}
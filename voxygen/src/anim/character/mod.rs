@@ -3,6 +3,7 @@ pub mod gliding;
 pub mod idle;
 pub mod jump;
 pub mod run;
+pub mod swim;
 
 // Reexports
 pub use self::attack::AttackAnimation;
@@ -10,6 +11,7 @@ pub use self::gliding::GlidingAnimation;
 pub use self::idle::IdleAnimation;
 pub use self::jump::JumpAnimation;
 pub use self::run::RunAnimation;
+pub use self::swim::SwimAnimation;
 
 use super::{Bone, Skeleton};
 use crate::render::FigureBoneData;
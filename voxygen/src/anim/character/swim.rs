@@ -0,0 +1,102 @@
+use super::{super::Animation, CharacterSkeleton};
+use std::{f32::consts::PI, ops::Mul};
+use vek::*;
+
+pub struct SwimAnimation;
+
+impl Animation for SwimAnimation {
+    type Skeleton = CharacterSkeleton;
+    type Dependency = (f32, f64);
+
+    fn update_skeleton(
+        skeleton: &Self::Skeleton,
+        (velocity, global_time): Self::Dependency,
+        anim_time: f64,
+    ) -> Self::Skeleton {
+        let mut next = (*skeleton).clone();
+
+        let wave = (anim_time as f32 * 4.0).sin();
+        let wave_cos = (anim_time as f32 * 4.0).cos();
+        let wave_kick = (anim_time as f32 * 8.0).sin();
+
+        let head_look = Vec2::new(
+            ((global_time + anim_time) as f32 / 3.0)
+                .floor()
+                .mul(7331.0)
+                .sin()
+                * 0.2,
+            ((global_time + anim_time) as f32 / 3.0)
+                .floor()
+                .mul(1337.0)
+                .sin()
+                * 0.1,
+        );
+
+        // Lean forward the faster the character swims
+        let lean = (velocity * 0.15).min(1.0);
+
+        next.head.offset = Vec3::new(0.0, 3.0, 12.0 + wave_cos * 0.5);
+        next.head.ori =
+            Quaternion::rotation_z(head_look.x) * Quaternion::rotation_x(head_look.y + lean * 0.9);
+        next.head.scale = Vec3::one();
+
+        next.chest.offset = Vec3::new(0.0, 0.0, 7.0 + wave_cos * 0.5);
+        next.chest.ori = Quaternion::rotation_z(wave * 0.1);
+        next.chest.scale = Vec3::one();
+
+        next.belt.offset = Vec3::new(0.0, 0.0, 5.0 + wave_cos * 0.5);
+        next.belt.ori = Quaternion::rotation_z(wave * 0.15);
+        next.belt.scale = Vec3::one();
+
+        next.shorts.offset = Vec3::new(0.0, 0.0, 2.0 + wave_cos * 0.5);
+        next.shorts.ori = Quaternion::rotation_z(wave * 0.2);
+        next.shorts.scale = Vec3::one();
+
+        // Breaststroke
+        next.l_hand.offset = Vec3::new(-6.0 - wave * 4.0, 6.0 + wave_cos * 4.0, 13.0) / 11.0;
+        next.l_hand.ori = Quaternion::rotation_x(PI / 2.0 * lean + wave_cos * 0.4);
+        next.l_hand.scale = Vec3::one() / 11.0;
+
+        next.r_hand.offset = Vec3::new(6.0 + wave * 4.0, 6.0 + wave_cos * 4.0, 13.0) / 11.0;
+        next.r_hand.ori = Quaternion::rotation_x(PI / 2.0 * lean + wave_cos * 0.4);
+        next.r_hand.scale = Vec3::one() / 11.0;
+
+        next.l_foot.offset = Vec3::new(-3.4, -1.0, 6.0 + wave_kick * 0.5);
+        next.l_foot.ori = Quaternion::rotation_x(-0.4 + wave_kick * 0.6);
+        next.l_foot.scale = Vec3::one();
+
+        next.r_foot.offset = Vec3::new(3.4, -1.0, 6.0 - wave_kick * 0.5);
+        next.r_foot.ori = Quaternion::rotation_x(-0.4 - wave_kick * 0.6);
+        next.r_foot.scale = Vec3::one();
+
+        next.weapon.offset = Vec3::new(-7.0, -5.0, 15.0);
+        next.weapon.ori = Quaternion::rotation_y(2.5) * Quaternion::rotation_z(1.57);
+        next.weapon.scale = Vec3::one();
+
+        next.l_shoulder.offset = Vec3::new(-10.0, -3.2, 2.5);
+        next.l_shoulder.ori = Quaternion::rotation_x(0.0);
+        next.l_shoulder.scale = Vec3::one() * 1.04;
+
+        next.r_shoulder.offset = Vec3::new(0.0, -3.2, 2.5);
+        next.r_shoulder.ori = Quaternion::rotation_x(0.0);
+        next.r_shoulder.scale = Vec3::one() * 1.04;
+
+        next.draw.offset = Vec3::new(0.0, 5.0, 0.0);
+        next.draw.ori = Quaternion::rotation_y(0.0);
+        next.draw.scale = Vec3::one() * 0.0;
+
+        next.left_equip.offset = Vec3::new(0.0, 0.0, 5.0) / 11.0;
+        next.left_equip.ori = Quaternion::rotation_x(0.0);
+        next.left_equip.scale = Vec3::one() * 0.0;
+
+        next.right_equip.offset = Vec3::new(0.0, 0.0, 5.0) / 11.0;
+        next.right_equip.ori = Quaternion::rotation_x(0.0);
+        next.right_equip.scale = Vec3::one() * 0.0;
+
+        next.torso.offset = Vec3::new(0.0, -0.2, 0.4);
+        next.torso.ori = Quaternion::rotation_x(-lean * 1.2 - wave_cos * 0.05);
+        next.torso.scale = Vec3::one() / 11.0;
+
+        next
+    }
+}
@@ -1,4 +1,5 @@
 use super::{img_ids::Imgs, Fonts, TEXT_COLOR, XP_COLOR};
+use common::comp::{stats::xp_needed, Equipment, Stats};
 use conrod_core::{
     color,
     widget::{self, Button, Image, Rectangle, Text},
@@ -69,16 +70,28 @@ widget_ids! {
 pub struct CharacterWindow<'a> {
     imgs: &'a Imgs,
     fonts: &'a Fonts,
+    name: &'a str,
+    stats: &'a Stats,
+    equipment: &'a Equipment,
 
     #[conrod(common_builder)]
     common: widget::CommonBuilder,
 }
 
 impl<'a> CharacterWindow<'a> {
-    pub fn new(imgs: &'a Imgs, fonts: &'a Fonts) -> Self {
+    pub fn new(
+        imgs: &'a Imgs,
+        fonts: &'a Fonts,
+        name: &'a str,
+        stats: &'a Stats,
+        equipment: &'a Equipment,
+    ) -> Self {
         Self {
             imgs,
             fonts,
+            name,
+            stats,
+            equipment,
             common: widget::CommonBuilder::default(),
         }
     }
@@ -104,8 +117,8 @@ impl<'a> Widget for CharacterWindow<'a> {
     fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
         let widget::UpdateArgs { id, state, ui, .. } = args;
 
-        // TODO: Read from parameter/character struct.
-        let xp_percentage = 0.4;
+        let xp_needed = xp_needed(self.stats.level);
+        let xp_percentage = self.stats.xp as f64 / xp_needed as f64;
 
         // Frame
         Image::new(self.imgs.window_3)
@@ -133,8 +146,7 @@ impl<'a> Widget for CharacterWindow<'a> {
         }
 
         // Title
-        // TODO: Use an actual character name.
-        Text::new("Character Name")
+        Text::new(self.name)
             .mid_top_with_margin_on(state.charwindow_frame, 6.0)
             .font_id(self.fonts.metamorph)
             .font_size(14)
@@ -330,8 +342,7 @@ impl<'a> Widget for CharacterWindow<'a> {
         //.label_font_size(14)
         //.set(state.charwindow_tab1, ui);
 
-        // TODO: Use an actual character level.
-        Text::new("1")
+        Text::new(&self.stats.level.to_string())
             .mid_top_with_margin_on(state.charwindow_rectangle, 10.0)
             .font_id(self.fonts.opensans)
             .font_size(30)
@@ -344,7 +355,7 @@ impl<'a> Widget for CharacterWindow<'a> {
             .set(state.charwindow_exp_rectangle, ui);
 
         // Exp-Bar Progress
-        Rectangle::fill_with([170.0 * (xp_percentage), 6.0], XP_COLOR) // 0.8 = Experience percentage
+        Rectangle::fill_with([170.0 * (xp_percentage), 6.0], XP_COLOR)
             .mid_left_with_margin_on(state.charwindow_tab1_expbar, 1.0)
             .set(state.charwindow_exp_progress_rectangle, ui);
 
@@ -355,8 +366,7 @@ impl<'a> Widget for CharacterWindow<'a> {
             .set(state.charwindow_tab1_expbar, ui);
 
         // Exp-Text
-        // TODO: Shows current Exp over the next threshold Exp.
-        Text::new("120/170")
+        Text::new(&format!("{}/{}", self.stats.xp, xp_needed))
             .mid_top_with_margin_on(state.charwindow_tab1_expbar, 10.0)
             .font_id(self.fonts.opensans)
             .font_size(15)
@@ -372,13 +382,11 @@ impl<'a> Widget for CharacterWindow<'a> {
 
         // Stats
         Text::new(
-            "Stamina\n\
+            "Health\n\
              \n\
-             Strength\n\
+             Damage\n\
              \n\
-             Dexterity\n\
-             \n\
-             Intelligence",
+             Defense",
         )
         .top_left_with_margins_on(state.charwindow_rectangle, 140.0, 5.0)
         .font_id(self.fonts.opensans)
@@ -386,16 +394,15 @@ impl<'a> Widget for CharacterWindow<'a> {
         .color(TEXT_COLOR)
         .set(state.charwindow_tab1_statnames, ui);
 
-        // TODO: Shows actual stat points.
-        Text::new(
-            "1234\n\
-             \n\
-             12312\n\
-             \n\
-             12414\n\
-             \n\
-             124124",
-        )
+        let damage =
+            (self.equipment.weapon_stats().damage as f32 * self.stats.damage_scale()).round();
+        Text::new(&format!(
+            "{}/{}\n\n{}\n\n{}",
+            self.stats.hp.get_current(),
+            self.stats.hp.get_maximum(),
+            damage,
+            self.equipment.defense(),
+        ))
         .top_right_with_margins_on(state.charwindow_rectangle, 140.0, 5.0)
         .font_id(self.fonts.opensans)
         .font_size(16)
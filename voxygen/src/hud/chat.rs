@@ -1,5 +1,7 @@
-use super::{img_ids::Imgs, Fonts, TEXT_COLOR};
+use super::{img_ids::Imgs, Fonts, GROUP_COLOR, TEXT_COLOR};
+use common::msg::{ChatKind, ChatMsg};
 use conrod_core::{
+    color::Color,
     input::Key,
     position::Dimension,
     widget::{self, Button, Id, List, Rectangle, Text, TextEdit},
@@ -19,9 +21,14 @@ widget_ids! {
 
 const MAX_MESSAGES: usize = 100;
 
+const WHISPER_COLOR: Color = Color::Rgba(0.9, 0.6, 1.0, 1.0);
+const SYSTEM_COLOR: Color = Color::Rgba(1.0, 0.9, 0.4, 1.0);
+const DEATH_COLOR: Color = Color::Rgba(1.0, 0.3, 0.3, 1.0);
+const COMMAND_COLOR: Color = Color::Rgba(0.7, 0.7, 0.7, 1.0);
+
 #[derive(WidgetCommon)]
 pub struct Chat<'a> {
-    new_messages: &'a mut VecDeque<String>,
+    new_messages: &'a mut VecDeque<ChatMsg>,
 
     imgs: &'a Imgs,
     fonts: &'a Fonts,
@@ -31,7 +38,7 @@ pub struct Chat<'a> {
 }
 
 impl<'a> Chat<'a> {
-    pub fn new(new_messages: &'a mut VecDeque<String>, imgs: &'a Imgs, fonts: &'a Fonts) -> Self {
+    pub fn new(new_messages: &'a mut VecDeque<ChatMsg>, imgs: &'a Imgs, fonts: &'a Fonts) -> Self {
         Self {
             new_messages,
             imgs,
@@ -55,8 +62,19 @@ impl<'a> Chat<'a> {
     }
 }
 
+fn kind_color(kind: &ChatKind) -> Color {
+    match kind {
+        ChatKind::Say => TEXT_COLOR,
+        ChatKind::Whisper { .. } => WHISPER_COLOR,
+        ChatKind::Group => GROUP_COLOR,
+        ChatKind::System => SYSTEM_COLOR,
+        ChatKind::Death => DEATH_COLOR,
+        ChatKind::Command => COMMAND_COLOR,
+    }
+}
+
 pub struct State {
-    messages: VecDeque<String>,
+    messages: VecDeque<ChatMsg>,
     input: String,
 
     ids: Ids,
@@ -149,13 +167,17 @@ impl<'a> Widget for Chat<'a> {
             .scroll_kids_vertically()
             .set(state.ids.message_box, ui);
         while let Some(item) = items.next(ui) {
+            let msg = state
+                .messages
+                .get(item.i)
+                .map(|msg| (msg.to_string(), kind_color(&msg.kind)));
             // This would be easier if conrod used the v-metrics from rusttype.
-            let widget = if item.i < state.messages.len() {
-                let text = Text::new(&state.messages[item.i])
+            let widget = if let Some((msg, color)) = &msg {
+                let text = Text::new(msg)
                     .font_size(15)
                     .font_id(self.fonts.opensans)
                     .w(470.0)
-                    .color(TEXT_COLOR)
+                    .color(*color)
                     .line_spacing(2.0);
                 // Add space between messages.
                 let y = match text.get_y_dimension(ui) {
@@ -4,7 +4,11 @@ use conrod_core::{
     widget_ids, Colorable, Positionable, Sizeable, Widget, WidgetCommon,
 };
 
-use super::{img_ids::Imgs, Fonts, Show, TEXT_COLOR};
+use super::{img_ids::Imgs, Fonts, Show, GROUP_COLOR, TEXT_COLOR};
+use vek::*;
+
+// How many pixels of the minimap a block takes up
+const MAP_SCALE: f64 = 0.5;
 
 widget_ids! {
     struct Ids {
@@ -12,6 +16,7 @@ widget_ids! {
         mmap_frame_bg,
         mmap_location,
         mmap_button,
+        member_markers[],
     }
 }
 
@@ -21,17 +26,20 @@ pub struct MiniMap<'a> {
 
     imgs: &'a Imgs,
     fonts: &'a Fonts,
+    /// Where the other members of the player's group are, relative to the player.
+    members: Vec<Vec2<f32>>,
 
     #[conrod(common_builder)]
     common: widget::CommonBuilder,
 }
 
 impl<'a> MiniMap<'a> {
-    pub fn new(show: &'a Show, imgs: &'a Imgs, fonts: &'a Fonts) -> Self {
+    pub fn new(show: &'a Show, imgs: &'a Imgs, fonts: &'a Fonts, members: Vec<Vec2<f32>>) -> Self {
         Self {
             show,
             imgs,
             fonts,
+            members,
             common: widget::CommonBuilder::default(),
         }
     }
@@ -72,6 +80,23 @@ impl<'a> Widget for MiniMap<'a> {
             Rectangle::fill_with([92.0 * 2.0, 82.0 * 2.0], color::TRANSPARENT)
                 .mid_top_with_margin_on(state.ids.mmap_frame, 13.0 * 2.0 + 2.0)
                 .set(state.ids.mmap_frame_bg, ui);
+
+            // Group members, pinned to the edge of the map when they're off it
+            if state.ids.member_markers.len() < self.members.len() {
+                let len = self.members.len();
+                state.update(|s| {
+                    s.ids
+                        .member_markers
+                        .resize(len, &mut ui.widget_id_generator())
+                });
+            }
+            for (offset, &id) in self.members.iter().zip(state.ids.member_markers.iter()) {
+                let x = (offset.x as f64 * MAP_SCALE).max(-90.0).min(90.0);
+                let y = (offset.y as f64 * MAP_SCALE).max(-80.0).min(80.0);
+                Rectangle::fill_with([6.0, 6.0], GROUP_COLOR)
+                    .x_y_relative_to(state.ids.mmap_frame_bg, x, y)
+                    .set(id, ui);
+            }
         } else {
             Image::new(self.imgs.mmap_frame_closed)
                 .w_h(100.0 * 2.0, 11.0 * 2.0)
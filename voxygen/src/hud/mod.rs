@@ -31,7 +31,13 @@ use crate::{
     GlobalState,
 };
 use client::Client;
-use common::{comp, terrain::TerrainChunkSize, vol::VolSize};
+use common::{
+    comp,
+    inventory::item::{Item, Rarity},
+    msg::ChatMsg,
+    terrain::TerrainChunkSize,
+    vol::VolSize,
+};
 use conrod_core::{
     widget::{self, Button, Image, Rectangle, Text},
     widget_ids, Color, Colorable, Labelable, Positionable, Sizeable, Widget,
@@ -44,6 +50,26 @@ const XP_COLOR: Color = Color::Rgba(0.59, 0.41, 0.67, 1.0);
 const TEXT_COLOR: Color = Color::Rgba(1.0, 1.0, 1.0, 1.0);
 const HP_COLOR: Color = Color::Rgba(0.33, 0.63, 0.0, 1.0);
 const MANA_COLOR: Color = Color::Rgba(0.42, 0.41, 0.66, 1.0);
+const GROUP_COLOR: Color = Color::Rgba(0.4, 0.8, 1.0, 1.0);
+
+/// The text and color of the label shown above an item lying on the ground.
+fn item_label(item: &Item) -> (String, Color) {
+    let rarity_color = |rarity| match rarity {
+        Rarity::Common => TEXT_COLOR,
+        Rarity::Uncommon => Color::Rgba(0.3, 0.9, 0.3, 1.0),
+        Rarity::Rare => Color::Rgba(0.3, 0.5, 1.0, 1.0),
+        Rarity::Legendary => Color::Rgba(1.0, 0.6, 0.1, 1.0),
+    };
+    match *item {
+        Item::Weapon {
+            variant, rarity, ..
+        } => (format!("{:?}", variant), rarity_color(rarity)),
+        Item::Armor {
+            variant, rarity, ..
+        } => (format!("{:?}", variant), rarity_color(rarity)),
+        Item::Ingredient { kind, amount } => (format!("{:?} x{}", kind, amount), TEXT_COLOR),
+    }
+}
 
 widget_ids! {
     struct Ids {
@@ -52,6 +78,8 @@ widget_ids! {
         // Health Bars
         health_bars[],
         health_bar_backs[],
+        // Items lying on the ground
+        item_labels[],
 
         // Test
         bag_space_add,
@@ -245,7 +273,7 @@ pub struct Hud {
     ids: Ids,
     imgs: Imgs,
     fonts: Fonts,
-    new_messages: VecDeque<String>,
+    new_messages: VecDeque<ChatMsg>,
     inventory_space: u32,
     show: Show,
     to_focus: Option<Option<widget::Id>>,
@@ -311,6 +339,7 @@ impl Hud {
             let actor = ecs.read_storage::<comp::Actor>();
             let pos = ecs.read_storage::<comp::phys::Pos>();
             let stats = ecs.read_storage::<comp::Stats>();
+            let life_states = ecs.read_storage::<comp::LifeState>();
             let player = ecs.read_storage::<comp::Player>();
             let entities = ecs.entities();
             let me = client.entity();
@@ -325,11 +354,43 @@ impl Hud {
             let mut name_id_walker = self.ids.name_tags.walk();
             let mut health_id_walker = self.ids.health_bars.walk();
             let mut health_back_id_walker = self.ids.health_bar_backs.walk();
+            let mut item_id_walker = self.ids.item_labels.walk();
+
+            // Render labels on items lying on the ground, as they don't have a figure.
+            for (pos, item) in (&pos, &ecs.read_storage::<Item>())
+                .join()
+                // Don't process labels outside the vd (visibility further limited by ui backend)
+                .filter(|(pos, _)| {
+                    (pos.0 - player_pos)
+                        .map2(TerrainChunkSize::SIZE, |d, sz| {
+                            (d.abs() as u32) < view_distance * sz as u32
+                        })
+                        .reduce_and()
+                })
+            {
+                let id = item_id_walker.next(
+                    &mut self.ids.item_labels,
+                    &mut ui_widgets.widget_id_generator(),
+                );
+                let (label, color) = item_label(item);
+                Text::new(&label)
+                    .font_size(16)
+                    .color(color)
+                    .x_y(0.0, 0.0)
+                    .position_ingame(pos.0 + Vec3::new(0.0, 0.0, 0.5))
+                    .resolution(100.0)
+                    .set(id, ui_widgets);
+            }
 
             // Render Name Tags
             for (pos, name) in (&entities, &pos, &actor, &stats, player.maybe())
                 .join()
-                .filter(|(entity, _, _, stats, _)| *entity != me && !stats.is_dead)
+                .filter(|(entity, _, _, _, _)| {
+                    *entity != me
+                        && life_states
+                            .get(*entity)
+                            .map_or(true, comp::LifeState::is_alive)
+                })
                 // Don't process nametags outside the vd (visibility further limited by ui backend)
                 .filter(|(_, pos, _, _, _)| {
                     (pos.0 - player_pos)
@@ -371,7 +432,9 @@ impl Hud {
                 .join()
                 .filter(|(entity, _, stats)| {
                     *entity != me
-                        && !stats.is_dead
+                        && life_states
+                            .get(*entity)
+                            .map_or(true, comp::LifeState::is_alive)
                         && stats.hp.get_current() != stats.hp.get_maximum()
                 })
                 // Don't process health bars outside the vd (visibility further limited by ui backend)
@@ -506,7 +569,24 @@ impl Hud {
         }
 
         // MiniMap
-        match MiniMap::new(&self.show, &self.imgs, &self.fonts).set(self.ids.minimap, ui_widgets) {
+        let members = {
+            let ecs = client.state().ecs();
+            let positions = ecs.read_storage::<comp::phys::Pos>();
+            let groups = ecs.read_storage::<comp::Group>();
+            match (groups.get(client.entity()), positions.get(client.entity())) {
+                (Some(group), Some(player_pos)) => (&ecs.entities(), &groups, &positions)
+                    .join()
+                    .filter(|(entity, member_group, _)| {
+                        *entity != client.entity() && member_group.id == group.id
+                    })
+                    .map(|(_, _, pos)| Vec2::from(pos.0 - player_pos.0))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        match MiniMap::new(&self.show, &self.imgs, &self.fonts, members)
+            .set(self.ids.minimap, ui_widgets)
+        {
             Some(minimap::Event::Toggle) => self.show.toggle_mini_map(),
             None => {}
         }
@@ -533,7 +613,14 @@ impl Hud {
             .get(client.entity())
             .map(|&s| s)
             .unwrap_or_default();
-        Skillbar::new(&self.imgs, &self.fonts, stats).set(self.ids.skillbar, ui_widgets);
+        let effects = client
+            .state()
+            .ecs()
+            .read_storage::<comp::Effects>()
+            .get(client.entity())
+            .cloned()
+            .unwrap_or_default();
+        Skillbar::new(&self.imgs, &self.fonts, stats, effects).set(self.ids.skillbar, ui_widgets);
 
         // Chat box
         match Chat::new(&mut self.new_messages, &self.imgs, &self.fonts)
@@ -598,7 +685,20 @@ impl Hud {
 
         // Character Window
         if let Windows::CharacterAnd(small) = self.show.open_windows {
-            match CharacterWindow::new(&self.imgs, &self.fonts)
+            let ecs = client.state().ecs();
+            let name = ecs
+                .read_storage::<comp::Actor>()
+                .get(client.entity())
+                .map(|actor| match actor {
+                    comp::Actor::Character { name, .. } => name.clone(),
+                })
+                .unwrap_or_default();
+            let equipment = ecs
+                .read_storage::<comp::Equipment>()
+                .get(client.entity())
+                .cloned()
+                .unwrap_or_default();
+            match CharacterWindow::new(&self.imgs, &self.fonts, &name, &stats, &equipment)
                 .set(self.ids.character_window, ui_widgets)
             {
                 Some(character_window::Event::Close) => {
@@ -643,7 +743,7 @@ impl Hud {
         events
     }
 
-    pub fn new_message(&mut self, msg: String) {
+    pub fn new_message(&mut self, msg: ChatMsg) {
         self.new_messages.push_back(msg);
     }
 
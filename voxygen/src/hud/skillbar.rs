@@ -1,5 +1,5 @@
 use super::{img_ids::Imgs, Fonts, HP_COLOR, MANA_COLOR, TEXT_COLOR, XP_COLOR};
-use common::comp::Stats;
+use common::comp::{stats::xp_needed, EffectKind, Effects, Stats, MAX_ENERGY};
 use conrod_core::{
     widget::{self, Image, Rectangle, Text},
     widget_ids, Colorable, Positionable, Sizeable, Widget, WidgetCommon,
@@ -7,6 +7,8 @@ use conrod_core::{
 
 widget_ids! {
     struct Ids {
+        buffs,
+        debuffs,
         health_bar,
         health_bar_color,
         l_click,
@@ -32,17 +34,19 @@ pub struct Skillbar<'a> {
     fonts: &'a Fonts,
 
     stats: Stats,
+    effects: Effects,
 
     #[conrod(common_builder)]
     common: widget::CommonBuilder,
 }
 
 impl<'a> Skillbar<'a> {
-    pub fn new(imgs: &'a Imgs, fonts: &'a Fonts, stats: Stats) -> Self {
+    pub fn new(imgs: &'a Imgs, fonts: &'a Fonts, stats: Stats, effects: Effects) -> Self {
         Self {
             imgs,
             fonts,
             stats,
+            effects,
             common: widget::CommonBuilder::default(),
         }
     }
@@ -72,14 +76,10 @@ impl<'a> Widget for Skillbar<'a> {
     fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
         let widget::UpdateArgs { state, ui, .. } = args;
 
-        // TODO: remove this
-        let level = (self.stats.xp as f64).log(4.0).trunc() as u32 + 1;
-        let start_level_xp = ((level - 1) as f64).powi(4);
-        let next_level_xp = (level as f64).powi(4) - start_level_xp;
-        // TODO: We need a max xp value
-        let xp_percentage = (self.stats.xp as f64 - start_level_xp) / next_level_xp;
+        let level = self.stats.level;
+        let xp_percentage = self.stats.xp as f64 / xp_needed(level) as f64;
         let hp_percentage = self.stats.hp.get_current() as f64 / self.stats.hp.get_maximum() as f64;
-        let mana_percentage = 1.0;
+        let mana_percentage = (self.stats.energy / MAX_ENERGY) as f64;
 
         // TODO: Only show while aiming with a bow or when casting a spell.
         // Crosshair
@@ -160,22 +160,55 @@ impl<'a> Widget for Skillbar<'a> {
             .set(state.ids.mana_bar_color, ui);
 
         // Buffs/Debuffs
+        // Each kind is listed once, with the number of stacks and how long the longest one lasts.
+        let describe = |harmful: bool| {
+            EffectKind::ALL
+                .iter()
+                .filter(|kind| kind.is_harmful() == harmful)
+                .filter_map(|&kind| {
+                    let stacks = self.effects.0.iter().filter(|e| e.kind == kind);
+                    let count = stacks.clone().count();
+                    let remaining = stacks.map(|e| e.remaining).fold(0.0, f32::max);
+                    match count {
+                        0 => None,
+                        1 => Some(format!("{} {:.0}s", kind.name(), remaining.ceil())),
+                        _ => Some(format!(
+                            "{} x{} {:.0}s",
+                            kind.name(),
+                            count,
+                            remaining.ceil()
+                        )),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ")
+        };
 
         // Buffs
+        Text::new(&describe(false))
+            .up_from(state.ids.mana_bar, 4.0)
+            .align_left_of(state.ids.mana_bar)
+            .font_size(12)
+            .color(TEXT_COLOR)
+            .set(state.ids.buffs, ui);
 
         // Debuffs
+        Text::new(&describe(true))
+            .up_from(state.ids.health_bar, 4.0)
+            .align_right_of(state.ids.health_bar)
+            .font_size(12)
+            .color(HP_COLOR)
+            .set(state.ids.debuffs, ui);
 
         // Level Display
 
         // TODO: don't construct a new string here
-        // TODO: Insert actual Level here.
         Text::new(&level.to_string())
             .left_from(state.ids.xp_bar, -15.0)
             .font_size(10)
             .color(TEXT_COLOR)
             .set(state.ids.level_text, ui);
 
-        // TODO: Insert next Level here.
         Text::new(&(level + 1).to_string())
             .right_from(state.ids.xp_bar, -15.0)
             .font_size(10)
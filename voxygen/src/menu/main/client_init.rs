@@ -16,6 +16,8 @@ pub enum Error {
     NoAddress,
     // Parsing/host name resolution successful but could not connect.
     ConnectionFailed(ClientError),
+    // The server speaks a different version of the network protocol.
+    ProtocolMismatch { server_version: u32 },
     ClientCrashed,
 }
 
@@ -62,6 +64,12 @@ impl ClientInit {
                                     ClientError::Network(_) => {
                                         last_err = Some(Error::ConnectionFailed(err))
                                     }
+                                    // Other addresses will lead to the same server.
+                                    ClientError::ProtocolMismatch { server_version } => {
+                                        let _ = tx
+                                            .send(Err(Error::ProtocolMismatch { server_version }));
+                                        return;
+                                    }
                                     // TODO: Handle errors?
                                     _ => panic!(
                                         "Unexpected non-network error when creating client: {:?}",
@@ -76,6 +76,7 @@ impl PlayState for MainMenuState {
                         match err {
                             InitError::BadAddress(_) | InitError::NoAddress => "Server not found",
                             InitError::ConnectionFailed(_) => "Connection failed",
+                            InitError::ProtocolMismatch { .. } => "Incompatible server version",
                             InitError::ClientCrashed => "Client crashed",
                         }
                         .to_string(),
@@ -488,7 +488,7 @@ impl FigureMgr {
             &ecs.read_storage::<comp::phys::Ori>(),
             &ecs.read_storage::<comp::Actor>(),
             &ecs.read_storage::<comp::AnimationInfo>(),
-            ecs.read_storage::<comp::Stats>().maybe(),
+            ecs.read_storage::<comp::LifeState>().maybe(),
         )
             .join()
         {
@@ -563,6 +563,11 @@ impl FigureMgr {
                                     animation_info.time,
                                 )
                             }
+                            comp::Animation::Swim => character::SwimAnimation::update_skeleton(
+                                state.skeleton_mut(),
+                                (vel.0.magnitude(), time),
+                                animation_info.time,
+                            ),
                         };
 
                         state.skeleton.interpolate(&target_skeleton);
@@ -672,7 +677,7 @@ impl FigureMgr {
             &ecs.read_storage::<comp::phys::Ori>(),
             &ecs.read_storage::<comp::Actor>(),
             &ecs.read_storage::<comp::AnimationInfo>(),
-            ecs.read_storage::<comp::Stats>().maybe(),
+            ecs.read_storage::<comp::LifeState>().maybe(),
         )
             .join()
             // Don't render figures outside the vd
@@ -684,7 +689,9 @@ impl FigureMgr {
                     .reduce_and()
             })
             // Don't render dead entities
-            .filter(|(_, _, _, _, _, _, stats)| stats.map_or(true, |s| !s.is_dead))
+            .filter(|(_, _, _, _, _, _, life_state)| {
+                life_state.map_or(true, comp::LifeState::is_alive)
+            })
         {
             match actor {
                 comp::Actor::Character { body, .. } => {
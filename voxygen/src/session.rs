@@ -128,6 +128,9 @@ impl PlayState for SessionState {
                     Event::InputUpdate(GameInput::Jump, true) => {
                         self.client.borrow_mut().jump();
                     }
+                    Event::InputUpdate(GameInput::Interact, true) => {
+                        self.client.borrow_mut().pick_up_nearest();
+                    }
                     Event::InputUpdate(GameInput::MoveForward, state) => self.key_state.up = state,
                     Event::InputUpdate(GameInput::MoveBack, state) => self.key_state.down = state,
                     Event::InputUpdate(GameInput::MoveLeft, state) => self.key_state.left = state,
@@ -146,7 +149,12 @@ impl PlayState for SessionState {
             // Perform an in-game tick.
             if let Err(err) = self.tick(clock.get_last_delta()) {
                 error!("Failed to tick the scene: {:?}", err);
-                return PlayStateResult::Pop;
+
+                // Try to resume the session before giving up on it.
+                if let Err(err) = self.client.borrow_mut().reconnect() {
+                    error!("Failed to reconnect to the server: {:?}", err);
+                    return PlayStateResult::Pop;
+                }
             }
 
             // Maintain global state
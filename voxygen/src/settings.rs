@@ -16,8 +16,10 @@ pub struct Settings {
     pub show_disclaimer: bool,
 }
 
-/// `ControlSettings` contains keybindings.
+/// `ControlSettings` contains keybindings. Bindings missing from the file (such as ones added
+/// after it was saved) get their default.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ControlSettings {
     pub toggle_cursor: KeyMouse,
     pub escape: KeyMouse,
@@ -44,9 +46,11 @@ pub struct ControlSettings {
     pub pan_sensitivity: f32,
     pub zoom_sensitivity: f32,
     pub attack: KeyMouse,
+    pub interact: KeyMouse,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NetworkingSettings {
     pub username: String,
     pub servers: Vec<String>,
@@ -54,11 +58,13 @@ pub struct NetworkingSettings {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Log {
     pub file: PathBuf,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GraphicsSettings {
     pub view_distance: u32,
 }
@@ -66,6 +72,7 @@ pub struct GraphicsSettings {
 /// AudioSettings controls the volume of different audio subsystems and which
 /// device is used.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AudioSettings {
     pub music_volume: f32,
     pub sfx_volume: f32,
@@ -74,50 +81,81 @@ pub struct AudioSettings {
     pub audio_device: Option<String>,
 }
 
+impl Default for ControlSettings {
+    fn default() -> Self {
+        ControlSettings {
+            toggle_cursor: KeyMouse::Key(VirtualKeyCode::Tab),
+            escape: KeyMouse::Key(VirtualKeyCode::Escape),
+            enter: KeyMouse::Key(VirtualKeyCode::Return),
+            move_forward: KeyMouse::Key(VirtualKeyCode::W),
+            move_left: KeyMouse::Key(VirtualKeyCode::A),
+            move_back: KeyMouse::Key(VirtualKeyCode::S),
+            move_right: KeyMouse::Key(VirtualKeyCode::D),
+            jump: KeyMouse::Key(VirtualKeyCode::Space),
+            glide: KeyMouse::Key(VirtualKeyCode::LShift),
+            map: KeyMouse::Key(VirtualKeyCode::M),
+            bag: KeyMouse::Key(VirtualKeyCode::B),
+            quest_log: KeyMouse::Key(VirtualKeyCode::L),
+            character_window: KeyMouse::Key(VirtualKeyCode::C),
+            social: KeyMouse::Key(VirtualKeyCode::O),
+            spellbook: KeyMouse::Key(VirtualKeyCode::P),
+            settings: KeyMouse::Key(VirtualKeyCode::N),
+            help: KeyMouse::Key(VirtualKeyCode::F1),
+            toggle_interface: KeyMouse::Key(VirtualKeyCode::F2),
+            toggle_debug: KeyMouse::Key(VirtualKeyCode::F3),
+            fullscreen: KeyMouse::Key(VirtualKeyCode::F11),
+            screenshot: KeyMouse::Key(VirtualKeyCode::F4),
+            toggle_ingame_ui: KeyMouse::Key(VirtualKeyCode::F6),
+            pan_sensitivity: 1.0,
+            zoom_sensitivity: 1.0,
+            attack: KeyMouse::Mouse(MouseButton::Left),
+            interact: KeyMouse::Key(VirtualKeyCode::E),
+        }
+    }
+}
+
+impl Default for NetworkingSettings {
+    fn default() -> Self {
+        NetworkingSettings {
+            username: "Username".to_string(),
+            servers: vec!["server.veloren.net".to_string()],
+            default_server: 0,
+        }
+    }
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            file: "voxygen.log".into(),
+        }
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings { view_distance: 5 }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            music_volume: 0.5,
+            sfx_volume: 0.5,
+            audio_device: None,
+        }
+    }
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
-            controls: ControlSettings {
-                toggle_cursor: KeyMouse::Key(VirtualKeyCode::Tab),
-                escape: KeyMouse::Key(VirtualKeyCode::Escape),
-                enter: KeyMouse::Key(VirtualKeyCode::Return),
-                move_forward: KeyMouse::Key(VirtualKeyCode::W),
-                move_left: KeyMouse::Key(VirtualKeyCode::A),
-                move_back: KeyMouse::Key(VirtualKeyCode::S),
-                move_right: KeyMouse::Key(VirtualKeyCode::D),
-                jump: KeyMouse::Key(VirtualKeyCode::Space),
-                glide: KeyMouse::Key(VirtualKeyCode::LShift),
-                map: KeyMouse::Key(VirtualKeyCode::M),
-                bag: KeyMouse::Key(VirtualKeyCode::B),
-                quest_log: KeyMouse::Key(VirtualKeyCode::L),
-                character_window: KeyMouse::Key(VirtualKeyCode::C),
-                social: KeyMouse::Key(VirtualKeyCode::O),
-                spellbook: KeyMouse::Key(VirtualKeyCode::P),
-                settings: KeyMouse::Key(VirtualKeyCode::N),
-                help: KeyMouse::Key(VirtualKeyCode::F1),
-                toggle_interface: KeyMouse::Key(VirtualKeyCode::F2),
-                toggle_debug: KeyMouse::Key(VirtualKeyCode::F3),
-                fullscreen: KeyMouse::Key(VirtualKeyCode::F11),
-                screenshot: KeyMouse::Key(VirtualKeyCode::F4),
-                toggle_ingame_ui: KeyMouse::Key(VirtualKeyCode::F6),
-                pan_sensitivity: 1.0,
-                zoom_sensitivity: 1.0,
-                attack: KeyMouse::Mouse(MouseButton::Left),
-            },
-            networking: NetworkingSettings {
-                username: "Username".to_string(),
-                servers: vec!["server.veloren.net".to_string()],
-                default_server: 0,
-            },
-            log: Log {
-                file: "voxygen.log".into(),
-            },
-            graphics: GraphicsSettings { view_distance: 5 },
-            audio: AudioSettings {
-                music_volume: 0.5,
-                sfx_volume: 0.5,
-                audio_device: None,
-            },
+            controls: ControlSettings::default(),
+            networking: NetworkingSettings::default(),
+            log: Log::default(),
+            graphics: GraphicsSettings::default(),
+            audio: AudioSettings::default(),
             show_disclaimer: true,
         }
     }
@@ -35,6 +35,7 @@ pub enum GameInput {
     ToggleIngameUi,
     Attack,
     Respawn,
+    Interact,
 }
 
 /// Represents an incoming event from the window.
@@ -134,6 +135,7 @@ impl Window {
             GameInput::ToggleIngameUi,
         );
         key_map.insert(settings.controls.attack, GameInput::Attack);
+        key_map.insert(settings.controls.interact, GameInput::Interact);
 
         Ok(Self {
             events_loop,
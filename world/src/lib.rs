@@ -1,10 +1,10 @@
 #![feature(euclidean_division)]
 
-mod sim;
+pub mod sim;
 mod structure;
 
 use common::{
-    terrain::{Block, TerrainChunk, TerrainChunkMeta, TerrainChunkSize},
+    terrain::{BiomeKind, Block, TerrainChunk, TerrainChunkMeta, TerrainChunkSize, WATER_KIND},
     vol::{VolSize, Vox, WriteVol},
 };
 use fxhash::FxHashMap;
@@ -41,7 +41,7 @@ impl World {
 
         let air = Block::empty();
         let stone = Block::new(1, Rgb::new(200, 220, 255));
-        let water = Block::new(5, Rgb::new(100, 150, 255));
+        let water = Block::new(WATER_KIND, Rgb::new(100, 150, 255));
 
         let warp_nz = BasicMulti::new().set_octaves(3).set_seed(self.sim.seed + 0);
 
@@ -54,7 +54,12 @@ impl World {
             None => return TerrainChunk::new(0, water, air, TerrainChunkMeta::void()),
         };
 
-        let mut chunk = TerrainChunk::new(base_z - 8, stone, air, TerrainChunkMeta::void());
+        let biome = self
+            .sim
+            .get(chunk_pos.map(|e| e as u32))
+            .map(|chunk| chunk.get_biome())
+            .unwrap_or(BiomeKind::Void);
+        let mut chunk = TerrainChunk::new(base_z - 8, stone, air, TerrainChunkMeta::new(biome));
 
         let mut world_sampler = self.sim.sampler();
 
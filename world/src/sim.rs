@@ -1,7 +1,7 @@
 use crate::{structure::StructureGen2d, Cache};
 use common::{
     assets,
-    terrain::{Block, Structure, TerrainChunkSize},
+    terrain::{BiomeKind, Block, Structure, TerrainChunkSize, WATER_KIND},
     vol::{ReadVol, VolSize, Vox},
 };
 use lazy_static::lazy_static;
@@ -299,7 +299,7 @@ impl<'a> Sampler<'a> {
         let stone = Block::new(2, Rgb::new(200, 220, 255));
         let dirt = Block::new(1, Rgb::new(128, 90, 0));
         let sand = Block::new(1, Rgb::new(180, 150, 50));
-        let water = Block::new(1, Rgb::new(100, 150, 255));
+        let water = Block::new(WATER_KIND, Rgb::new(100, 150, 255));
         let warm_stone = Block::new(1, Rgb::new(165, 165, 90));
 
         let block = if (wposf.z as f32) < height - 4.0 {
@@ -474,6 +474,26 @@ impl SimChunk {
     pub fn get_max_z(&self) -> f32 {
         (self.alt + Z_TOLERANCE.1).max(SEA_LEVEL + 1.0)
     }
+
+    pub fn get_biome(&self) -> BiomeKind {
+        if self.alt < SEA_LEVEL {
+            BiomeKind::Ocean
+        } else if self.alt > SEA_LEVEL + 0.15 * MOUNTAIN_HEIGHT {
+            if self.temp < 0.0 {
+                BiomeKind::Snowlands
+            } else {
+                BiomeKind::Mountain
+            }
+        } else if self.temp > 0.5 && self.tree_density < 0.3 {
+            BiomeKind::Desert
+        } else if self.alt < SEA_LEVEL + 8.0 && self.tree_density > 0.5 {
+            BiomeKind::Swamp
+        } else if self.tree_density > 0.6 {
+            BiomeKind::Forest
+        } else {
+            BiomeKind::Grassland
+        }
+    }
 }
 
 lazy_static! {